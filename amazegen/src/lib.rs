@@ -1,7 +1,7 @@
 #![allow(mixed_script_confusables)]
 pub mod maze;
 
-use maze::feature::{Algorithm, Configuration, Shape};
+use maze::feature::Configuration;
 use serde::Serialize;
 use serde_wasm_bindgen::Serializer;
 use wasm_bindgen::prelude::*;
@@ -18,50 +18,567 @@ pub struct WebResponse {
     pub hash: String,
 }
 
+/// Generate the maze described by `js`. Fails with a message for the user
+/// if the configuration can't produce a maze, e.g. because the algorithm
+/// doesn't work with the shape.
 #[wasm_bindgen]
-pub fn run_configuration(js: JsValue) -> JsValue {
+pub fn run_configuration(js: JsValue) -> Result<JsValue, JsValue> {
     let configuration: Configuration = serde_wasm_bindgen::from_value(js).unwrap_or_else(|err| {
         log(&format!(
             "Error parsing configuration. Using default. {:?}",
             err
         ));
         Configuration {
-            algorithm: Algorithm::GrowingTree,
             colour: "#FFFFFF".into(),
-            features: vec![],
             seed: generate_seed(),
-            shape: Shape::Rectilinear(10, 10),
-            stroke_width: 8.0,
+            ..Default::default()
         }
     });
 
-    configuration
+    let response = configuration
         .execute_for_web()
+        .map_err(|err| JsValue::from_str(&err.to_string()))?;
+    response
         .serialize(&Serializer::new())
-        .unwrap_or_else(|err| {
-            log(&format!("Error while writing response: {:?}", err));
-            JsValue::NULL
-        })
+        .map_err(|err| JsValue::from_str(&format!("Error while writing response: {}", err)))
 }
 
 #[cfg(test)]
 mod test {
-    use crate::maze::feature::Configuration;
+    use crate::maze::{algorithms::Selection, feature::Configuration, shape::wrap::Wrap};
 
     #[test]
     fn mkae_svg_maze_should_return_svg_when_params_are_valid() {
         let svg = Configuration {
-            algorithm: crate::maze::feature::Algorithm::GrowingTree,
-            colour: "000000".into(),
-            features: vec![],
+            algorithm: crate::maze::feature::Algorithm::GrowingTree(Selection::default()),
             seed: 1,
             shape: crate::maze::feature::Shape::Rectilinear(10, 10),
-            stroke_width: 8.0,
+            ..Default::default()
         }
         .execute_for_web()
+        .unwrap()
         .svg;
         assert!(svg.contains("<svg"))
     }
+
+    #[test]
+    fn aldous_broder_configuration_is_hashed() {
+        let response = Configuration {
+            algorithm: crate::maze::feature::Algorithm::AldousBroder,
+            seed: 1,
+            shape: crate::maze::feature::Shape::Theta(5),
+            ..Default::default()
+        }
+        .execute_for_web()
+        .unwrap();
+        assert!(response.svg.contains("<svg"));
+        assert_eq!(response.hash, "T5|AldousBroder|1");
+    }
+
+    #[test]
+    fn braided_mazes_can_be_solved_and_are_hashed() {
+        use crate::maze::feature::{Algorithm, Feature, Shape};
+        for shape in [
+            Shape::Rectilinear(10, 10),
+            Shape::Theta(6),
+            Shape::Sigma(8),
+            Shape::Delta(8),
+            Shape::Layered(6, 3),
+        ] {
+            let response = Configuration {
+                algorithm: Algorithm::Kruskal,
+                features: vec![Feature::Stain, Feature::Solve],
+                seed: 1,
+                shape,
+                braid: 100,
+                ..Default::default()
+            }
+            .execute_for_web()
+            .unwrap();
+            assert!(response.svg.contains("<svg"));
+            assert!(response.hash.ends_with("|Kruskal|1|B100"));
+        }
+    }
+
+    #[test]
+    fn layered_configuration_is_hashed() {
+        let response = Configuration {
+            algorithm: crate::maze::feature::Algorithm::Kruskal,
+            seed: 1,
+            shape: crate::maze::feature::Shape::Layered(6, 3),
+            ..Default::default()
+        }
+        .execute_for_web()
+        .unwrap();
+        assert!(response.svg.contains("<svg"));
+        assert_eq!(response.hash, "L6x3|Kruskal|1");
+    }
+
+    #[test]
+    fn biased_configuration_is_hashed() {
+        use crate::maze::algorithms::Bias;
+        use crate::maze::feature::{Algorithm, Shape};
+        use crate::maze::interface::Axis;
+        for (axis, suffix) in [(Axis::Horizontal, "|H60"), (Axis::Vertical, "|V60")] {
+            let response = Configuration {
+                algorithm: Algorithm::GrowingTree(Selection::default()),
+                seed: 1,
+                shape: Shape::Theta(6),
+                braid: 20,
+                bias: Some(Bias { axis, strength: 60 }),
+                ..Default::default()
+            }
+            .execute_for_web()
+            .unwrap();
+            assert!(response.svg.contains("<svg"));
+            assert_eq!(response.hash, format!("T6|GrowingTree|1|B20{}", suffix));
+        }
+    }
+
+    #[test]
+    fn pictures_work_for_every_shape() {
+        use crate::maze::feature::{Algorithm, Feature, Shape};
+        use crate::maze::picture::Picture;
+        let mut picture = Picture::new(2, 2, vec![0, 255, 255, 0]).unwrap();
+        picture.stain = true;
+        for shape in [
+            Shape::Rectilinear(10, 10),
+            Shape::Theta(6),
+            Shape::Sigma(8),
+            Shape::Delta(8),
+            Shape::Layered(6, 3),
+        ] {
+            let response = Configuration {
+                algorithm: Algorithm::Kruskal,
+                features: vec![Feature::Stain, Feature::Solve],
+                seed: 1,
+                shape,
+                picture: Some(picture.clone()),
+                ..Default::default()
+            }
+            .execute_for_web()
+            .unwrap();
+            assert!(response.svg.contains("<svg"));
+            assert!(response.hash.ends_with("|Kruskal|1"));
+        }
+    }
+
+    #[test]
+    fn one_way_passages_work_for_every_shape() {
+        use crate::maze::feature::{Algorithm, Feature, Shape};
+        for (shape, algorithm) in [
+            (Shape::Rectilinear(10, 10), Algorithm::Kruskal),
+            (Shape::Rectilinear(10, 10), Algorithm::Weave(30)),
+            (Shape::Theta(6), Algorithm::Kruskal),
+            (Shape::Sigma(8), Algorithm::Kruskal),
+            (Shape::Delta(8), Algorithm::Kruskal),
+            (Shape::Layered(6, 3), Algorithm::Kruskal),
+        ] {
+            let response = Configuration {
+                algorithm,
+                features: vec![Feature::Stain, Feature::Solve],
+                seed: 1,
+                shape,
+                braid: 100,
+                one_way: 30,
+                ..Default::default()
+            }
+            .execute_for_web()
+            .unwrap();
+            assert!(response.svg.contains("<svg"));
+            assert!(response.hash.ends_with("|1|B100|O30"));
+        }
+    }
+
+    #[test]
+    fn terrain_works_for_every_shape() {
+        use crate::maze::feature::{Algorithm, Feature, Shape};
+        for shape in [
+            Shape::Rectilinear(10, 10),
+            Shape::Theta(6),
+            Shape::Sigma(8),
+            Shape::Delta(8),
+            Shape::Layered(6, 3),
+        ] {
+            let response = Configuration {
+                algorithm: Algorithm::Kruskal,
+                features: vec![Feature::Stain, Feature::Solve],
+                seed: 1,
+                shape,
+                braid: 50,
+                terrain: 40,
+                ..Default::default()
+            }
+            .execute_for_web()
+            .unwrap();
+            assert!(response.svg.contains("<pattern"));
+            assert!(response.hash.ends_with("|1|B50|C40"));
+        }
+    }
+
+    #[test]
+    fn portals_work_for_every_shape() {
+        use crate::maze::feature::{Algorithm, Feature, Shape};
+        for shape in [
+            Shape::Rectilinear(10, 10),
+            Shape::Theta(6),
+            Shape::Sigma(8),
+            Shape::Delta(8),
+            Shape::Layered(6, 3),
+        ] {
+            let configuration = |portals| Configuration {
+                algorithm: Algorithm::Kruskal,
+                features: vec![Feature::Solve],
+                seed: 1,
+                shape: shape.clone(),
+                portals,
+                ..Default::default()
+            };
+            let plain = configuration(0).execute_for_web().unwrap();
+            let response = configuration(3).execute_for_web().unwrap();
+            // the symbols come on top of the same maze
+            assert!(response.svg.len() > plain.svg.len());
+            assert!(response.hash.ends_with("|1|P3"));
+        }
+    }
+
+    #[test]
+    fn checkpoints_work_for_every_shape() {
+        use crate::maze::feature::{Algorithm, Feature, Shape};
+        for shape in [
+            Shape::Rectilinear(10, 10),
+            Shape::Theta(6),
+            Shape::Sigma(8),
+            Shape::Delta(8),
+            Shape::Layered(6, 3),
+        ] {
+            let response = Configuration {
+                algorithm: Algorithm::Kruskal,
+                features: vec![Feature::Solve],
+                seed: 1,
+                shape,
+                checkpoints: 3,
+                ..Default::default()
+            }
+            .execute_for_web()
+            .unwrap();
+            assert_eq!(response.svg.matches("<text").count(), 3);
+            assert!(response.hash.ends_with("|1|W3"));
+        }
+    }
+
+    #[test]
+    fn locks_work_for_every_shape() {
+        use crate::maze::feature::{Algorithm, Feature, Shape};
+        for shape in [
+            Shape::Rectilinear(10, 10),
+            Shape::Theta(6),
+            Shape::Sigma(8),
+            Shape::Delta(8),
+            Shape::Layered(6, 3),
+        ] {
+            let response = Configuration {
+                algorithm: Algorithm::Kruskal,
+                features: vec![Feature::Solve],
+                seed: 1,
+                shape,
+                locks: 2,
+                ..Default::default()
+            }
+            .execute_for_web()
+            .unwrap();
+            // each door and its key carry the lock's letter
+            assert!(response.svg.matches("\nA\n</text>").count() >= 2);
+            assert!(response.svg.matches("\nB\n</text>").count() >= 2);
+            assert!(response.hash.ends_with("|1|K2"));
+        }
+    }
+
+    #[test]
+    fn puzzles_are_limited_in_size() {
+        use crate::maze::feature::{Algorithm, Shape};
+        let configuration = |portals, checkpoints, locks, pairs| Configuration {
+            algorithm: Algorithm::Kruskal,
+            seed: 1,
+            shape: Shape::Rectilinear(16, 16),
+            portals,
+            checkpoints,
+            locks,
+            pairs,
+            ..Default::default()
+        };
+        for (configuration, message) in [
+            (
+                configuration(13, 0, 0, 0),
+                "a maze can't have more than 12 pairs of portals",
+            ),
+            (
+                configuration(0, 21, 0, 0),
+                "a maze can't have more than 20 checkpoints",
+            ),
+            (
+                configuration(0, 0, 33, 0),
+                "a maze can't have more than 8 locks",
+            ),
+            (
+                configuration(0, 0, 0, 9),
+                "a maze can't have more than 8 start and finish pairs",
+            ),
+        ] {
+            let err = configuration.execute_for_web().err().unwrap();
+            assert_eq!(err.to_string(), message);
+        }
+        for configuration in [
+            configuration(12, 0, 0, 0),
+            configuration(0, 20, 0, 0),
+            configuration(0, 0, 8, 0),
+            configuration(0, 0, 0, 8),
+        ] {
+            assert!(configuration.execute_for_web().is_ok());
+        }
+    }
+
+    #[test]
+    fn symmetry_works_for_rectilinear_and_theta_mazes() {
+        use crate::maze::feature::{Algorithm, Feature, Shape};
+        use crate::maze::interface::Symmetry;
+        let configuration = |shape, symmetry| Configuration {
+            algorithm: Algorithm::Kruskal,
+            features: vec![Feature::Solve],
+            seed: 1,
+            shape,
+            symmetry: Some(symmetry),
+            ..Default::default()
+        };
+        for (shape, symmetry, hash) in [
+            (Shape::Rectilinear(10, 10), Symmetry::Mirror, "|1|YM"),
+            (Shape::Rectilinear(9, 9), Symmetry::Rotation(4), "|1|Y4"),
+            (Shape::Theta(6), Symmetry::Rotation(8), "|1|Y8"),
+        ] {
+            let response = configuration(shape, symmetry).execute_for_web().unwrap();
+            assert!(response.hash.ends_with(hash));
+        }
+        for shape in [Shape::Sigma(5), Shape::Delta(5), Shape::Layered(5, 2)] {
+            let err = configuration(shape, Symmetry::Mirror)
+                .execute_for_web()
+                .err()
+                .unwrap();
+            assert_eq!(err.to_string(), "this maze can't have mirror symmetry");
+        }
+        let err = configuration(Shape::Theta(6), Symmetry::Rotation(3))
+            .execute_for_web()
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "this maze can't have 3-fold rotational symmetry"
+        );
+    }
+
+    #[test]
+    fn wrapping_works_for_rectilinear_and_sigma_mazes() {
+        use crate::maze::feature::{Algorithm, Feature, Shape};
+        let configuration = |algorithm, shape, wrap| Configuration {
+            algorithm,
+            features: vec![Feature::Solve],
+            seed: 1,
+            shape,
+            wrap,
+            ..Default::default()
+        };
+        let wrap = |horizontal, vertical, twist| Wrap {
+            horizontal,
+            vertical,
+            twist,
+        };
+        for (shape, wrap, hash) in [
+            (Shape::Rectilinear(10, 8), wrap(true, false, false), "|1|XH"),
+            (Shape::Rectilinear(10, 8), wrap(true, true, true), "|1|XHVT"),
+            (Shape::Sigma(8), wrap(true, true, false), "|1|XHV"),
+            (Shape::Sigma(9), wrap(false, true, true), "|1|XVT"),
+        ] {
+            let response = configuration(Algorithm::Kruskal, shape, wrap)
+                .execute_for_web()
+                .unwrap();
+            assert!(response.hash.ends_with(hash));
+            // both ends of the first passage around the edges are numbered
+            assert!(response.svg.matches("\n1\n</text>").count() >= 2);
+        }
+        let torus = wrap(true, true, false);
+        let err = configuration(Algorithm::Kruskal, Shape::Theta(6), torus)
+            .execute_for_web()
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "only rectilinear and sigma mazes can wrap around"
+        );
+        let err = configuration(Algorithm::Eller, Shape::Rectilinear(10, 8), torus)
+            .execute_for_web()
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Eller can't generate mazes that wrap around"
+        );
+        let err = configuration(Algorithm::Kruskal, Shape::Sigma(7), torus)
+            .execute_for_web()
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "sigma mazes need an even size to wrap around horizontally"
+        );
+    }
+
+    #[test]
+    fn pairs_are_labelled_on_every_shape_but_layered() {
+        use crate::maze::feature::{Algorithm, Feature, Shape};
+        let configuration = |shape| Configuration {
+            algorithm: Algorithm::Kruskal,
+            features: vec![Feature::Solve],
+            seed: 1,
+            shape,
+            braid: 30,
+            pairs: 3,
+            ..Default::default()
+        };
+        for shape in [
+            Shape::Rectilinear(10, 10),
+            Shape::Theta(6),
+            Shape::Sigma(8),
+            Shape::Delta(8),
+        ] {
+            let response = configuration(shape).execute_for_web().unwrap();
+            assert!(response.hash.ends_with("|1|B30|A3"));
+            // both ends of the entrance and exit, and of the next pair
+            assert_eq!(response.svg.matches("\nA\n</text>").count(), 2);
+            assert_eq!(response.svg.matches("\nB\n</text>").count(), 2);
+        }
+        let err = configuration(Shape::Layered(6, 2))
+            .execute_for_web()
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "layered mazes only have one start and finish"
+        );
+    }
+
+    #[test]
+    fn rectilinear_only_algorithms_reject_other_shapes() {
+        use crate::maze::feature::{Algorithm, Shape};
+        let configuration = |algorithm, shape| Configuration {
+            algorithm,
+            seed: 1,
+            shape,
+            ..Default::default()
+        };
+        for algorithm in [
+            Algorithm::Eller,
+            Algorithm::RecursiveDivision,
+            Algorithm::BinaryTree,
+            Algorithm::Sidewinder,
+            Algorithm::Weave(20),
+            Algorithm::Dungeon(4),
+        ] {
+            assert!(configuration(algorithm.clone(), Shape::Rectilinear(5, 5))
+                .execute_for_web()
+                .is_ok());
+            for shape in [
+                Shape::Theta(5),
+                Shape::Sigma(5),
+                Shape::Delta(5),
+                Shape::Layered(5, 2),
+            ] {
+                let err = configuration(algorithm.clone(), shape)
+                    .execute_for_web()
+                    .err()
+                    .unwrap();
+                assert_eq!(
+                    err.to_string(),
+                    format!("{} can only generate rectilinear mazes", algorithm)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn masked_mazes_can_be_solved() {
+        use crate::maze::feature::{Algorithm, Feature, Shape};
+        use crate::maze::shape::mask::Mask;
+        let mask = Mask::from_ascii("X..X\n....\n....\nX..X").unwrap();
+        for shape in [
+            Shape::Rectilinear(12, 12),
+            Shape::Sigma(12),
+            Shape::Delta(12),
+        ] {
+            for algorithm in [
+                Algorithm::Kruskal,
+                Algorithm::Weave(20),
+                Algorithm::Dungeon(4),
+            ] {
+                if matches!(algorithm, Algorithm::Weave(_) | Algorithm::Dungeon(_))
+                    && !matches!(shape, Shape::Rectilinear(_, _))
+                {
+                    continue;
+                }
+                let response = Configuration {
+                    algorithm,
+                    features: vec![Feature::Stain, Feature::Solve],
+                    seed: 1,
+                    shape: shape.clone(),
+                    braid: 50,
+                    mask: Some(mask.clone()),
+                    ..Default::default()
+                }
+                .execute_for_web()
+                .unwrap();
+                assert!(response.svg.contains("<svg"));
+            }
+        }
+    }
+
+    #[test]
+    fn masks_are_rejected_where_they_dont_work() {
+        use crate::maze::feature::{Algorithm, Shape};
+        use crate::maze::shape::mask::Mask;
+        let configuration = |algorithm, shape, mask: &str| Configuration {
+            algorithm,
+            seed: 1,
+            shape,
+            mask: Some(Mask::from_ascii(mask).unwrap()),
+            ..Default::default()
+        };
+        let error = |configuration: Configuration| {
+            configuration.execute_for_web().err().unwrap().to_string()
+        };
+        assert_eq!(
+            error(configuration(
+                Algorithm::Kruskal,
+                Shape::Rectilinear(6, 6),
+                ".X.\n.X.\n.X."
+            )),
+            "the mask cuts the maze into pieces"
+        );
+        assert_eq!(
+            error(configuration(
+                Algorithm::Eller,
+                Shape::Rectilinear(6, 6),
+                "..\n.X"
+            )),
+            "Eller can't generate masked mazes"
+        );
+        assert!(
+            configuration(Algorithm::Eller, Shape::Rectilinear(6, 6), "..\n..")
+                .execute_for_web()
+                .is_ok()
+        );
+        assert_eq!(
+            error(configuration(Algorithm::Kruskal, Shape::Theta(6), "..\n.X")),
+            "only rectilinear, sigma and delta mazes can be masked"
+        );
+    }
 }
 
 // This will end up being a bigint in js-land.
@@ -3,4 +3,5 @@ pub mod arengee;
 pub mod feature;
 pub mod interface;
 pub mod paint;
+pub mod picture;
 pub mod shape;
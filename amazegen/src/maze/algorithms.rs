@@ -1,74 +1,357 @@
-use super::{arengee::Arengee, interface::Maze};
+pub mod binary_tree;
+pub mod division;
+pub mod dungeon;
+pub mod eller;
+pub mod weave;
 
-struct Kruskal<'a, M>
-where
-    M: Maze,
-{
-    maze: &'a mut M,
-    // we need a bijection between each node's class and all the nodes in a given class
-    // a class is just an integer that happens to be the same one as the node's original
-    // index from get_all_nodes()
-    // each node has exactly one class
-    // each class can have multiple nodes, but starts out with exactly one node
-    class_members: Vec<Vec<M::Idx>>,
-    classes: Vec<usize>,
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet, VecDeque},
+    fmt::Display,
+    str::FromStr,
+};
+
+use itertools::Itertools;
+use thiserror::Error;
+
+use super::{
+    arengee::Arengee,
+    interface::{Axis, Lock, Maze, Puzzle, Symmetry, Terrain},
+    paint::midpoint,
+    picture::Picture,
+};
+
+/// A disjoint-set forest over the cells' indices from `Maze::get_index`. Each
+/// set is a tree whose root represents the whole set.
+struct DisjointSets {
+    parents: Vec<usize>,
+    // upper bound on the height of the tree below each root
+    ranks: Vec<u8>,
 }
 
-impl<'a, M> Kruskal<'a, M>
-where
-    M: Maze,
-{
-    fn new(maze: &'a mut M) -> Self {
-        let all_nodes = maze.get_all_nodes().to_vec();
+impl DisjointSets {
+    fn new(size: usize) -> Self {
         Self {
-            maze,
-            classes: all_nodes.iter().enumerate().map(|(i, _)| i).collect(),
-            class_members: all_nodes
-                .iter()
-                .cloned()
-                .map(|n| vec![n])
-                .collect::<Vec<_>>(),
+            parents: (0..size).collect(),
+            ranks: vec![0; size],
         }
     }
 
-    fn link(&mut self, a: M::Idx, b: M::Idx) {
-        self.maze.carve(a, b);
-        let class_of_a = self.classes[self.maze.get_index(a)];
-        let class_of_b = self.classes[self.maze.get_index(b)];
-        let members_of_b = self.class_members[class_of_b].drain(..).collect::<Vec<_>>();
-        for member_of_b in members_of_b {
-            self.classes[self.maze.get_index(member_of_b)] = class_of_a;
-            self.class_members[class_of_a].push(member_of_b);
+    fn find(&mut self, node: usize) -> usize {
+        let mut root = node;
+        while self.parents[root] != root {
+            root = self.parents[root];
+        }
+        // path compression: hang everything we passed directly off the root
+        let mut cursor = node;
+        while self.parents[cursor] != root {
+            cursor = std::mem::replace(&mut self.parents[cursor], root);
         }
+        root
     }
 
-    fn classes_are_distinct(&self, a: M::Idx, b: M::Idx) -> bool {
-        self.classes[self.maze.get_index(a)] != self.classes[self.maze.get_index(b)]
+    /// Merge the sets of `a` and `b`. Returns false if they already were the
+    /// same set.
+    fn union(&mut self, a: usize, b: usize) -> bool {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return false;
+        }
+        // union by rank: hang the lower tree off the higher one
+        match self.ranks[a].cmp(&self.ranks[b]) {
+            std::cmp::Ordering::Less => self.parents[a] = b,
+            std::cmp::Ordering::Greater => self.parents[b] = a,
+            std::cmp::Ordering::Equal => {
+                self.parents[b] = a;
+                self.ranks[a] += 1;
+            }
+        }
+        true
     }
 }
 
-pub fn kruskal<M: Maze>(mut maze: M, rng: &mut Arengee) -> M {
+/// Makes `kruskal` and `jarník` prefer passages along one axis, which creates
+/// long corridors in that direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct Bias {
+    pub axis: Axis,
+    /// Percentage from 0 to 100. At 100, passages only run across the axis
+    /// where there's no other way.
+    pub strength: u8,
+}
+
+impl Bias {
+    /// The weight of a passage when picking one at random, from 0 to 100.
+    /// Passages that run neither way aren't affected.
+    fn weight(&self, axis: Option<Axis>) -> u32 {
+        match axis {
+            Some(axis) if axis != self.axis => 100 - self.strength.min(100) as u32,
+            _ => 100,
+        }
+    }
+}
+
+/// What `kruskal` and `jarník` weigh passages by. By default, they pick all
+/// passages with the same probability.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Weights<'a> {
+    pub bias: Option<Bias>,
+    /// Passages are only biased as much as the picture is bright where they
+    /// are. Without a bias, the picture makes them horizontal.
+    pub picture: Option<&'a Picture>,
+}
+
+impl Weights<'_> {
+    fn is_uniform(&self) -> bool {
+        self.bias.is_none() && self.picture.is_none()
+    }
+
+    /// The weight of the passage between two neighbouring cells, from 0 to
+    /// 100.
+    fn weight<M: Maze>(&self, maze: &M, a: M::Idx, b: M::Idx) -> u32 {
+        let bias = match (self.bias, self.picture) {
+            (Some(bias), None) => bias,
+            (bias, Some(picture)) => {
+                let bias = bias.unwrap_or(Bias {
+                    axis: Axis::Horizontal,
+                    strength: 100,
+                });
+                let brightness =
+                    picture.brightness(midpoint(maze.get_position(a), maze.get_position(b)));
+                Bias {
+                    strength: (bias.strength.min(100) as u32 * brightness as u32 / 255) as u8,
+                    ..bias
+                }
+            }
+            (None, None) => return 100,
+        };
+        bias.weight(maze.get_axis(a, b))
+    }
+
+    /// Pick one of the `neighbours` of `node` at random, according to their
+    /// weights.
+    fn choose<M: Maze>(
+        &self,
+        maze: &M,
+        node: M::Idx,
+        neighbours: &[M::Idx],
+        rng: &mut Arengee,
+    ) -> M::Idx {
+        let weights = neighbours
+            .iter()
+            .map(|n| self.weight(maze, node, *n))
+            .collect::<Vec<_>>();
+        let total = weights.iter().sum::<u32>();
+        if total == 0 {
+            return neighbours[rng.get_portable_usize(0..neighbours.len())];
+        }
+        let mut pick = rng.u32(0..total);
+        for (neighbour, weight) in neighbours.iter().zip(weights) {
+            if pick < weight {
+                return *neighbour;
+            }
+            pick -= weight;
+        }
+        unreachable!("pick is less than the sum of the weights")
+    }
+}
+
+pub fn kruskal<M: Maze>(mut maze: M, rng: &mut Arengee, weights: Weights) -> M {
     let mut edges = maze.get_all_edges();
-    let mut state = Kruskal::<M>::new(&mut maze);
+    let mut sets = DisjointSets::new(maze.get_index_bound());
     rng.shuffle(&mut edges);
+    if !weights.is_uniform() {
+        // Every edge gets a random rank, and lighter edges get pushed back by
+        // up to the whole range, so they tend to be carved last.
+        let ranks = edges
+            .iter()
+            .map(|&(a, b)| rng.u32(0..1000) + (100 - weights.weight(&maze, a, b)) * 10)
+            .collect::<Vec<_>>();
+        edges = edges
+            .into_iter()
+            .zip(ranks)
+            .sorted_by_key(|(_, rank)| *rank)
+            .map(|(edge, _)| edge)
+            .collect();
+    }
 
     for (a, b) in edges {
-        if state.classes_are_distinct(a, b) {
-            state.link(a, b);
+        if sets.union(maze.get_index(a), maze.get_index(b)) {
+            maze.carve(a, b);
         }
     }
 
     maze
 }
 
-pub fn jarník<M: Maze>(mut maze: M, rng: &mut Arengee) -> M {
+/// Which of the cells that GrowingTree has visited, but not yet exhausted,
+/// it grows from next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum Pick {
+    /// The most recently visited cell. Degenerates into a randomised depth-first search.
+    Newest,
+    /// The cell visited the longest time ago. Creates long, straight passages.
+    Oldest,
+    /// Any cell. Creates lots of short dead ends, similar to Prim's algorithm.
+    Random,
+    /// The cell in the middle of the list.
+    Middle,
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum SelectionError {
+    #[error("A selection needs at least one pick")]
+    Empty,
+    #[error("Unknown pick {0}, expected one of newest, oldest, random, middle")]
+    UnknownPick(String),
+    #[error("Illegal weight {0}, expected a positive integer")]
+    IllegalWeight(String),
+    #[error("The weights add up to more than {}", u32::MAX)]
+    TooHeavy,
+}
+
+/// A weighted mix of [`Pick`]s. The weights are relative to each other, so
+/// `newest:3,random:1` and `newest:75,random:25` select the same way.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(try_from = "Vec<(Pick, u32)>")]
+pub struct Selection(Vec<(Pick, u32)>);
+
+impl Default for Selection {
+    fn default() -> Self {
+        Self(vec![(Pick::Newest, 1)])
+    }
+}
+
+impl TryFrom<Vec<(Pick, u32)>> for Selection {
+    type Error = SelectionError;
+
+    fn try_from(weights: Vec<(Pick, u32)>) -> Result<Self, Self::Error> {
+        if weights.is_empty() {
+            return Err(SelectionError::Empty);
+        }
+        if let Some((_, weight)) = weights.iter().find(|(_, weight)| *weight == 0) {
+            return Err(SelectionError::IllegalWeight(weight.to_string()));
+        }
+        // `select` rolls a number below the total
+        let total = weights
+            .iter()
+            .try_fold(0u32, |total, (_, weight)| total.checked_add(*weight));
+        if total.is_none() {
+            return Err(SelectionError::TooHeavy);
+        }
+        Ok(Self(weights))
+    }
+}
+
+impl Selection {
+    /// Choose the position of the next cell among `len` cells that are ordered
+    /// from oldest to newest, along with the pick that led to it. Only consumes
+    /// randomness if it has to, so that the default selection yields the same
+    /// mazes it always did.
+    fn select(&self, len: usize, rng: &mut Arengee) -> (Pick, usize) {
+        let pick = if self.0.len() == 1 {
+            self.0[0].0
+        } else {
+            let total = self.0.iter().map(|(_, weight)| weight).sum::<u32>();
+            let mut roll = rng.u32(0..total);
+            self.0
+                .iter()
+                .find(|(_, weight)| {
+                    if roll < *weight {
+                        true
+                    } else {
+                        roll -= weight;
+                        false
+                    }
+                })
+                .map(|(pick, _)| *pick)
+                .unwrap_or(Pick::Newest)
+        };
+        let i = match pick {
+            Pick::Newest => len - 1,
+            Pick::Oldest => 0,
+            Pick::Middle => len / 2,
+            Pick::Random => rng.get_portable_usize(0..len),
+        };
+        (pick, i)
+    }
+}
+
+impl Display for Pick {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Pick::Newest => "newest",
+            Pick::Oldest => "oldest",
+            Pick::Random => "random",
+            Pick::Middle => "middle",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Pick {
+    type Err = SelectionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "newest" => Ok(Pick::Newest),
+            "oldest" => Ok(Pick::Oldest),
+            "random" => Ok(Pick::Random),
+            "middle" => Ok(Pick::Middle),
+            _ => Err(SelectionError::UnknownPick(s.to_string())),
+        }
+    }
+}
+
+impl Display for Selection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.0
+                .iter()
+                .map(|(pick, weight)| format!("{}:{}", pick, weight))
+                .join(",")
+        )
+    }
+}
+
+/// Parses comma separated picks with optional weights, e.g. `random` or
+/// `newest:75,random:25`. A pick without a weight has weight 1.
+impl FromStr for Selection {
+    type Err = SelectionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .filter(|part| !part.trim().is_empty())
+            .map(|part| match part.split_once(':') {
+                Some((pick, weight)) => Ok((
+                    pick.parse()?,
+                    weight
+                        .trim()
+                        .parse()
+                        .map_err(|_| SelectionError::IllegalWeight(weight.to_string()))?,
+                )),
+                None => Ok((part.parse()?, 1)),
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .and_then(Selection::try_from)
+    }
+}
+
+pub fn jarník<M: Maze>(
+    mut maze: M,
+    rng: &mut Arengee,
+    selection: &Selection,
+    weights: Weights,
+) -> M {
     let start = maze.get_random_node(rng);
-    let mut vertices: Vec<M::Idx> = vec![start];
-    let mut visited = vec![false; maze.get_all_nodes().len()];
+    let mut vertices: VecDeque<M::Idx> = VecDeque::from([start]);
+    let mut visited = vec![false; maze.get_index_bound()];
     visited[maze.get_index(start)] = true;
 
     while !vertices.is_empty() {
-        let i = vertices.len() - 1;
+        let (pick, i) = selection.select(vertices.len(), rng);
         let e = vertices[i];
         let possible_targets = maze
             .get_walls(e)
@@ -77,58 +360,1803 @@ pub fn jarník<M: Maze>(mut maze: M, rng: &mut Arengee) -> M {
             .filter(|n| !visited[maze.get_index(*n)])
             .collect::<Vec<_>>();
         if !possible_targets.is_empty() {
-            let target = possible_targets[rng.u32(0..possible_targets.len() as u32) as usize];
+            let target = if weights.is_uniform() {
+                possible_targets[rng.u32(0..possible_targets.len() as u32) as usize]
+            } else {
+                weights.choose(&maze, e, &possible_targets, rng)
+            };
             maze.carve(e, target);
             visited[maze.get_index(target)] = true;
-            vertices.push(target);
+            vertices.push_back(target);
         } else {
-            vertices.swap_remove(i);
+            match pick {
+                Pick::Newest => {
+                    vertices.pop_back();
+                }
+                Pick::Oldest => {
+                    vertices.pop_front();
+                }
+                // a random pick doesn't care about the order, so fill the gap
+                // with the newest cell instead of shifting everything after it
+                Pick::Random => {
+                    vertices.swap_remove_back(i);
+                }
+                Pick::Middle => {
+                    vertices.remove(i);
+                }
+            }
+        }
+    }
+
+    maze
+}
+
+/// How Prim's algorithm picks the next edge from its frontier.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum Frontier {
+    /// Any edge on the frontier, with the same probability.
+    #[default]
+    Random,
+    /// Every edge gets a random weight, and the lightest one on the frontier
+    /// goes first, which makes this a minimum spanning tree.
+    Weighted,
+}
+
+/// The walls between the cells in the maze and those outside of it.
+struct Walls<M: Maze> {
+    frontier: Frontier,
+    edges: Vec<(M::Idx, M::Idx)>,
+    // only used for Frontier::Weighted: weight and index into `edges`
+    weights: BinaryHeap<(Reverse<u32>, Reverse<usize>)>,
+}
+
+impl<M: Maze> Walls<M> {
+    fn new(frontier: Frontier) -> Self {
+        Self {
+            frontier,
+            edges: vec![],
+            weights: BinaryHeap::new(),
+        }
+    }
+
+    /// Each edge only ever enters the frontier once, from the side that's
+    /// already in the maze, so we can make up its weight when it does.
+    fn push(&mut self, edge: (M::Idx, M::Idx), rng: &mut Arengee) {
+        if self.frontier == Frontier::Weighted {
+            self.weights
+                .push((Reverse(rng.u32(0..u32::MAX)), Reverse(self.edges.len())));
+        }
+        self.edges.push(edge);
+    }
+
+    fn pop(&mut self, rng: &mut Arengee) -> Option<(M::Idx, M::Idx)> {
+        match self.frontier {
+            Frontier::Random if self.edges.is_empty() => None,
+            Frontier::Random => Some(
+                self.edges
+                    .swap_remove(rng.get_portable_usize(0..self.edges.len())),
+            ),
+            Frontier::Weighted => self.weights.pop().map(|(_, Reverse(i))| self.edges[i]),
+        }
+    }
+}
+
+/// Randomised Prim's algorithm. Unlike `jarník`, which picks a cell and then
+/// one of its walls, this keeps all walls between the maze and the cells
+/// outside of it, and carves one of them at a time. That creates lots of
+/// short dead ends all over the maze.
+pub fn prim<M: Maze>(mut maze: M, rng: &mut Arengee, frontier: Frontier) -> M {
+    let mut in_maze = vec![false; maze.get_index_bound()];
+    let mut walls = Walls::<M>::new(frontier);
+    let start = maze.get_random_node(rng);
+    in_maze[maze.get_index(start)] = true;
+    for neighbour in maze.get_walls(start) {
+        walls.push((start, neighbour), rng);
+    }
+
+    while let Some((from, to)) = walls.pop(rng) {
+        // the frontier still holds walls to cells that joined the maze later
+        if in_maze[maze.get_index(to)] {
+            continue;
+        }
+        maze.carve(from, to);
+        in_maze[maze.get_index(to)] = true;
+        for neighbour in maze.get_walls(to) {
+            if !in_maze[maze.get_index(neighbour)] {
+                walls.push((to, neighbour), rng);
+            }
+        }
+    }
+
+    maze
+}
+
+/// Hunt-and-kill walks randomly like GrowingTree with the newest selection, but
+/// when it gets stuck it doesn't backtrack. Instead, it hunts for an unvisited
+/// cell next to the maze by scanning all cells in order, and carries on from
+/// there. That way, it doesn't need to keep a stack of cells.
+pub fn hunt_and_kill<M: Maze>(mut maze: M, rng: &mut Arengee) -> M {
+    let nodes = maze.get_all_nodes();
+    let mut visited = vec![false; maze.get_index_bound()];
+    let start = maze.get_random_node(rng);
+    visited[maze.get_index(start)] = true;
+    let mut cursor = Some(start);
+    // every node before this one has been visited, so hunts can start here
+    let mut hunt_from = 0;
+
+    while let Some(current) = cursor {
+        let possible_targets = maze
+            .get_walls(current)
+            .into_iter()
+            .filter(|n| !visited[maze.get_index(*n)])
+            .collect::<Vec<_>>();
+        if !possible_targets.is_empty() {
+            let target = possible_targets[rng.get_portable_usize(0..possible_targets.len())];
+            maze.carve(current, target);
+            visited[maze.get_index(target)] = true;
+            cursor = Some(target);
+            continue;
+        }
+
+        cursor = None;
+        while hunt_from < nodes.len() && visited[maze.get_index(nodes[hunt_from])] {
+            hunt_from += 1;
+        }
+        for node in nodes[hunt_from..].iter() {
+            if visited[maze.get_index(*node)] {
+                continue;
+            }
+            let visited_neighbours = maze
+                .get_walls(*node)
+                .into_iter()
+                .filter(|n| visited[maze.get_index(*n)])
+                .collect::<Vec<_>>();
+            if !visited_neighbours.is_empty() {
+                let neighbour =
+                    visited_neighbours[rng.get_portable_usize(0..visited_neighbours.len())];
+                maze.carve(*node, neighbour);
+                visited[maze.get_index(*node)] = true;
+                cursor = Some(*node);
+                break;
+            }
+        }
+    }
+
+    maze
+}
+
+/// Walk randomly from `start`, carving into every cell the walk enters for
+/// the first time, until `target` cells are part of the maze. This is the
+/// Aldous–Broder algorithm.
+fn random_walk<M: Maze>(
+    maze: &mut M,
+    rng: &mut Arengee,
+    in_maze: &mut [bool],
+    start: M::Idx,
+    target: usize,
+) {
+    let mut cursor = start;
+    let mut count = in_maze.iter().filter(|visited| **visited).count();
+    if !in_maze[maze.get_index(cursor)] {
+        in_maze[maze.get_index(cursor)] = true;
+        count += 1;
+    }
+    while count < target {
+        let neighbours = maze.get_neighbours(cursor);
+        let next = neighbours[rng.get_portable_usize(0..neighbours.len())];
+        if !in_maze[maze.get_index(next)] {
+            maze.carve(cursor, next);
+            in_maze[maze.get_index(next)] = true;
+            count += 1;
+        }
+        cursor = next;
+    }
+}
+
+/// The Aldous–Broder algorithm creates uniformly random spanning trees, but
+/// it's slow: the walk keeps crossing the maze long after most cells have
+/// been visited.
+pub fn aldous_broder<M: Maze>(mut maze: M, rng: &mut Arengee) -> M {
+    let mut in_maze = vec![false; maze.get_index_bound()];
+    let target = maze.get_all_nodes().len();
+    let start = maze.get_random_node(rng);
+    random_walk(&mut maze, rng, &mut in_maze, start, target);
+    maze
+}
+
+/// Wilson's algorithm: loop-erased random walks from every cell outside the
+/// maze until they hit the maze, which yields a uniformly random spanning
+/// tree. The first walk takes very long to find the single cell the maze
+/// starts with, so `aldous_broder` is the percentage of cells that an
+/// Aldous–Broder random walk (see `random_walk`) adds to the maze before
+/// Wilson takes over.
+pub fn wilson<M: Maze>(mut maze: M, rng: &mut Arengee, aldous_broder: u8) -> M {
+    let nodes = maze.get_all_nodes();
+    let mut in_maze = vec![false; maze.get_index_bound()];
+    let target = (nodes.len() * aldous_broder.min(100) as usize / 100).max(1);
+    let start = maze.get_random_node(rng);
+    random_walk(&mut maze, rng, &mut in_maze, start, target);
+
+    let mut next: Vec<Option<M::Idx>> = vec![None; maze.get_index_bound()];
+    for node in nodes {
+        let mut cursor = node;
+        while !in_maze[maze.get_index(cursor)] {
+            let neighbours = maze.get_neighbours(cursor);
+            let step = neighbours[rng.get_portable_usize(0..neighbours.len())];
+            // overwriting the previous exit from a cell erases any loop the walk made
+            next[maze.get_index(cursor)] = Some(step);
+            cursor = step;
+        }
+
+        let mut cursor = node;
+        while !in_maze[maze.get_index(cursor)] {
+            let step =
+                next[maze.get_index(cursor)].expect("Loop-erased walk should lead into the maze.");
+            maze.carve(cursor, step);
+            in_maze[maze.get_index(cursor)] = true;
+            cursor = step;
+        }
+    }
+
+    maze
+}
+
+/// Rebuild the perfect `maze` so that it looks the same when it's mirrored or
+/// turned as in `symmetry`, keeping as many of its passages as it can. Every
+/// passage gets carved together with all of its images, and only if none of
+/// them closes a loop. Passages between a cell and its own image come last,
+/// since at most one of those fits into the maze. Some mazes can't be
+/// perfectly symmetric though, like rectilinear mazes with an even width and
+/// height turned upside down, and then the last few passages that join its
+/// parts break the symmetry. Panics if the maze can't have the `symmetry`, see
+/// `Maze::get_symmetric`.
+pub fn symmetrise<M: Maze>(mut maze: M, rng: &mut Arengee, symmetry: Symmetry) -> M {
+    let image = |maze: &M, node: M::Idx| {
+        maze.get_symmetric(node, symmetry)
+            .expect("The maze can't have this symmetry.")
+    };
+    let key = |maze: &M, (a, b): (M::Idx, M::Idx)| {
+        let (a, b) = (maze.get_index(a), maze.get_index(b));
+        (a.min(b), a.max(b))
+    };
+    let mut edges = HashSet::new();
+    // each passage with its images, and whether it joins a cell to an image
+    // of itself
+    let mut orbits = vec![];
+    for node in maze.get_all_nodes() {
+        for neighbour in maze.get_neighbours(node) {
+            if !edges.insert(key(&maze, (node, neighbour))) {
+                continue;
+            }
+            let mut orbit = vec![(node, neighbour)];
+            loop {
+                let (a, b) = *orbit.last().unwrap();
+                let next = (image(&maze, a), image(&maze, b));
+                if !edges.insert(key(&maze, next)) {
+                    break;
+                }
+                orbit.push(next);
+            }
+            let mut cell = image(&maze, node);
+            let mut joins_image = false;
+            while cell != node {
+                joins_image |= cell == neighbour;
+                cell = image(&maze, cell);
+            }
+            let carved = orbit
+                .iter()
+                .filter(|(a, b)| maze.get_paths(*a).contains(b))
+                .count();
+            orbits.push((joins_image, Reverse(carved), orbit));
+        }
+    }
+    rng.shuffle(&mut orbits);
+    orbits.sort_by_key(|(joins_image, carved, _)| (*joins_image, *carved));
+
+    for (_, _, orbit) in orbits.iter() {
+        for (a, b) in orbit {
+            maze.build_wall(*a, *b);
+        }
+    }
+    let mut sets = DisjointSets::new(maze.get_index_bound());
+    for (_, _, orbit) in orbits.iter() {
+        if closes_loop(&mut sets, &maze, orbit) {
+            continue;
+        }
+        for (a, b) in orbit {
+            sets.union(maze.get_index(*a), maze.get_index(*b));
+            maze.carve(*a, *b);
+        }
+    }
+    for (a, b) in orbits.into_iter().flat_map(|(_, _, orbit)| orbit) {
+        if sets.union(maze.get_index(a), maze.get_index(b)) {
+            maze.carve(a, b);
+        }
+    }
+    maze
+}
+
+/// Whether carving all the passages of `orbit` would close a loop in the
+/// maze whose cells are joined as in `sets`.
+fn closes_loop<M: Maze>(sets: &mut DisjointSets, maze: &M, orbit: &[(M::Idx, M::Idx)]) -> bool {
+    // the sets the passages join, numbered in the order they turn up
+    let mut roots = vec![];
+    let mut joined = DisjointSets::new(2 * orbit.len());
+    for (a, b) in orbit {
+        let [a, b] = [a, b].map(|node| {
+            let root = sets.find(maze.get_index(*node));
+            roots.iter().position(|r| *r == root).unwrap_or_else(|| {
+                roots.push(root);
+                roots.len() - 1
+            })
+        });
+        if !joined.union(a, b) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Remove `percentage` percent of the dead ends in `maze` by carving into one
+/// of their neighbours, which creates loops. A maze without any dead ends is
+/// called a braid maze. Where possible, a dead end gets connected to another
+/// dead end, so that one carve gets rid of both.
+pub fn braid<M: Maze>(mut maze: M, rng: &mut Arengee, percentage: u8) -> M {
+    let is_dead_end = |maze: &M, node: M::Idx| maze.get_paths(node).len() == 1;
+    let mut dead_ends = maze
+        .get_all_nodes()
+        .into_iter()
+        .filter(|node| is_dead_end(&maze, *node))
+        .collect::<Vec<_>>();
+    rng.shuffle(&mut dead_ends);
+    let count = dead_ends.len() * percentage.min(100) as usize / 100;
+
+    for node in dead_ends.into_iter().take(count) {
+        // a previous carve may already have opened this one up
+        if !is_dead_end(&maze, node) {
+            continue;
+        }
+        let walls = maze.get_walls(node);
+        let dead_end_neighbours = walls
+            .iter()
+            .cloned()
+            .filter(|n| is_dead_end(&maze, *n))
+            .collect::<Vec<_>>();
+        let candidates = if dead_end_neighbours.is_empty() {
+            walls
+        } else {
+            dead_end_neighbours
+        };
+        if !candidates.is_empty() {
+            let neighbour = candidates[rng.get_portable_usize(0..candidates.len())];
+            maze.carve(node, neighbour);
+        }
+    }
+
+    maze
+}
+
+/// Turn `percentage` percent of the passages of `maze` into one way passages,
+/// but only where every cell can still be reached from every other cell
+/// afterwards, so that there's a way out wherever you go. That's only ever the
+/// case for passages on a loop, so a perfect maze needs to be braided first.
+pub fn one_way<M: Maze>(mut maze: M, rng: &mut Arengee, percentage: u8) -> M {
+    let mut passages = vec![];
+    for node in maze.get_all_nodes() {
+        for neighbour in maze.get_paths(node) {
+            if maze.get_index(node) < maze.get_index(neighbour) {
+                passages.push((node, neighbour));
+            }
+        }
+    }
+    rng.shuffle(&mut passages);
+    let count = passages.len() * percentage.min(100) as usize / 100;
+
+    for (a, b) in passages.into_iter().take(count) {
+        let (from, to) = if rng.u32(0..2) == 0 { (a, b) } else { (b, a) };
+        let allowed = [(from, to), (to, from)].into_iter().find(|(from, to)| {
+            maze.overlay_mut().set_one_way(*from, *to);
+            // `from` is still reachable from everywhere if `to` can get back
+            is_reachable(&maze, *to, *from)
+        });
+        if allowed.is_none() {
+            maze.carve(a, b);
+        }
+    }
+
+    maze
+}
+
+/// Cover about `percentage` percent of the cells of `maze` with patches of mud
+/// and water, which cost more to walk through than plain ground. The solution
+/// takes the cheapest way through the maze, which can only differ from the
+/// shortest one if the maze has loops.
+pub fn terrain<M: Maze>(mut maze: M, rng: &mut Arengee, percentage: u8) -> M {
+    let mut nodes = maze.get_all_nodes();
+    let count = nodes.len() * percentage.min(100) as usize / 100;
+    rng.shuffle(&mut nodes);
+    let mut covered = 0;
+
+    for node in nodes {
+        if covered >= count {
+            break;
+        }
+        if maze.overlay().get_terrain(node) != Terrain::Ground {
+            continue;
+        }
+        let kind = if rng.u32(0..2) == 0 {
+            Terrain::Mud
+        } else {
+            Terrain::Water
+        };
+        let size = rng.u32(3..10) as usize;
+        maze.overlay_mut().set_terrain(node, kind);
+        covered += 1;
+        // patches spread across walls, the ground doesn't care about the maze
+        let mut patch = vec![node];
+        let mut i = 0;
+        while i < patch.len() && patch.len() < size && covered < count {
+            let mut neighbours = maze.get_neighbours(patch[i]);
+            rng.shuffle(&mut neighbours);
+            for neighbour in neighbours {
+                if patch.len() < size
+                    && covered < count
+                    && maze.overlay().get_terrain(neighbour) == Terrain::Ground
+                {
+                    maze.overlay_mut().set_terrain(neighbour, kind);
+                    patch.push(neighbour);
+                    covered += 1;
+                }
+            }
+            i += 1;
+        }
+    }
+
+    maze
+}
+
+/// How many pairs of portals a maze can have, see `portals`.
+pub const MAX_PORTALS: u8 = 12;
+
+/// Link `count` pairs of cells that are far apart with portals, which make
+/// shortcuts through the maze. Portals never lead to a neighbouring cell, and
+/// each cell has one portal at most, so small mazes may get fewer of them.
+pub fn portals<M: Maze>(mut maze: M, rng: &mut Arengee, count: u8) -> M {
+    for _ in 0..count {
+        let free = maze
+            .get_all_nodes()
+            .into_iter()
+            .filter(|n| maze.overlay().get_portal(*n).is_none())
+            .collect::<Vec<_>>();
+        if free.len() < 2 {
+            break;
+        }
+        let node = free[rng.get_portable_usize(0..free.len())];
+        let distances = cheapest(&maze, node, M::get_exits);
+        let furthest = distances.iter().max().copied().unwrap_or(0);
+        let neighbours = maze.get_neighbours(node);
+        // at least halfway across the maze, taking earlier portals into account
+        let far = free
+            .into_iter()
+            .filter(|n| {
+                *n != node
+                    && !neighbours.contains(n)
+                    && distances[maze.get_index(*n)] * 2 >= furthest
+            })
+            .collect::<Vec<_>>();
+        if !far.is_empty() {
+            let other = far[rng.get_portable_usize(0..far.len())];
+            maze.overlay_mut().add_portal(node, other);
         }
     }
 
     maze
 }
 
+/// Whether `to` can be reached from `from` by following the maze's passages.
+fn is_reachable<M: Maze>(maze: &M, from: M::Idx, to: M::Idx) -> bool {
+    let mut visited = vec![false; maze.get_index_bound()];
+    let mut frontier = vec![from];
+    visited[maze.get_index(from)] = true;
+    while let Some(cell) = frontier.pop() {
+        if cell == to {
+            return true;
+        }
+        for new in maze.get_exits(cell) {
+            if !visited[maze.get_index(new)] {
+                visited[maze.get_index(new)] = true;
+                frontier.push(new);
+            }
+        }
+    }
+    false
+}
+
+/// How much it costs to get from `origin` to each cell, indexed by
+/// `Maze::get_index`. Every cell on the way costs its `Terrain::cost`,
+/// including `origin` and the cell itself, so without any rough terrain it's
+/// the number of cells on the way. Cells that can't be reached at all are 0.
 pub fn dijkstra<M: Maze>(maze: &M, origin: M::Idx) -> Vec<usize>
 where
     M::Idx: std::fmt::Debug, // handy for when things go sideways
 {
-    let mut distances = maze.get_all_nodes().iter().map(|_| 0).collect::<Vec<_>>();
-    let mut frontier: Vec<M::Idx> = vec![origin];
-    distances[maze.get_index(origin)] = 1;
-
-    while !frontier.is_empty() {
-        let mut new_frontier: Vec<M::Idx> = vec![];
-        for cell in frontier.drain(..) {
-            for new in maze.get_paths(cell) {
-                if distances[maze.get_index(new)] == 0 {
-                    distances[maze.get_index(new)] = distances[maze.get_index(cell)] + 1;
-                    new_frontier.push(new);
-                }
+    cheapest(maze, origin, M::get_exits)
+}
+
+/// Like `dijkstra`, but how much it costs to get from each cell to `target`.
+/// That's the same unless there are one way passages.
+pub fn dijkstra_to<M: Maze>(maze: &M, target: M::Idx) -> Vec<usize>
+where
+    M::Idx: std::fmt::Debug,
+{
+    cheapest(maze, target, M::get_entries)
+}
+
+/// The cell that costs the most to get to from `origin`, for mazes whose
+/// entrance and exit aren't on an edge.
+pub fn furthest<M: Maze>(maze: &M, origin: M::Idx) -> M::Idx
+where
+    M::Idx: std::fmt::Debug,
+{
+    let topo = dijkstra(maze, origin);
+    maze.get_all_nodes()
+        .into_iter()
+        .max_by_key(|node| topo[maze.get_index(*node)])
+        .unwrap_or(origin)
+}
+
+/// What it costs to walk through each cell, by `Maze::get_index`. The
+/// searches need it for every step they take, so they look it up once.
+fn step_costs<M: Maze>(maze: &M) -> Vec<usize> {
+    let mut costs = vec![Terrain::Ground.cost(); maze.get_index_bound()];
+    for node in maze.get_all_nodes() {
+        costs[maze.get_index(node)] = maze.overlay().get_terrain(node).cost();
+    }
+    costs
+}
+
+fn cheapest<M: Maze>(
+    maze: &M,
+    origin: M::Idx,
+    next: impl Fn(&M, M::Idx) -> Vec<M::Idx>,
+) -> Vec<usize> {
+    let steps = step_costs(maze);
+    let mut costs = vec![0; maze.get_index_bound()];
+    costs[maze.get_index(origin)] = steps[maze.get_index(origin)];
+    // the queue refers to cells by their position in `cells`, because
+    // `M::Idx` can't be ordered
+    let mut cells = vec![origin];
+    let mut queue = BinaryHeap::from([Reverse((costs[maze.get_index(origin)], 0))]);
+
+    while let Some(Reverse((cost, i))) = queue.pop() {
+        let cell = cells[i];
+        if cost > costs[maze.get_index(cell)] {
+            // we've found a cheaper way to this cell since queueing it
+            continue;
+        }
+        for new in next(maze, cell) {
+            let index = maze.get_index(new);
+            let new_cost = cost + steps[index];
+            if costs[index] == 0 || new_cost < costs[index] {
+                costs[index] = new_cost;
+                cells.push(new);
+                queue.push(Reverse((new_cost, cells.len() - 1)));
             }
         }
-        frontier.append(&mut new_frontier);
     }
-    distances
+    costs
 }
 
-/// Find the shortest path from `entrance` to `exit` using `topo`, which is
-/// the *exit topology* of `maze`, i.e. the result of `dijkstra` run with
-/// `exit` as the origin.
+/// Walk from `entrance` to `exit` along the cheapest passages, where `topo` is
+/// how much it costs to get from each cell to the exit, see `dijkstra_to`.
 pub fn find_path<M: Maze>(maze: &M, topo: &[usize], entrance: M::Idx, exit: M::Idx) -> Vec<M::Idx> {
     let mut cursor: M::Idx = entrance;
     let mut path = vec![cursor];
     loop {
         cursor = *maze
-            .get_paths(cursor)
+            .get_exits(cursor)
             .iter()
+            .filter(|n| topo[maze.get_index(**n)] > 0)
             .min_by_key(|n| topo[maze.get_index(**n)])
             .expect("Couldn't find where to move next while tracing path from entrance to exit.");
         path.push(cursor);
-        if topo[maze.get_index(cursor)] == 1 {
+        if cursor == exit {
             break;
         }
     }
     path.push(exit);
     path
 }
+
+/// Pick up to `count` cells for a solution to visit on its way from
+/// `entrance` to `exit`, in the order they are to be visited. They stay clear
+/// of portals, so that their markers don't get in each other's way.
+pub fn checkpoints<M: Maze>(
+    maze: &M,
+    rng: &mut Arengee,
+    count: u8,
+    entrance: M::Idx,
+    exit: M::Idx,
+) -> Vec<M::Idx> {
+    if count == 0 {
+        return vec![];
+    }
+    let reachable = cheapest(maze, entrance, M::get_exits);
+    let mut candidates = maze
+        .get_all_nodes()
+        .into_iter()
+        .filter(|n| {
+            *n != entrance
+                && *n != exit
+                && maze.overlay().get_portal(*n).is_none()
+                && reachable[maze.get_index(*n)] > 0
+        })
+        .collect::<Vec<_>>();
+    rng.shuffle(&mut candidates);
+    candidates.truncate(count as usize);
+    candidates
+}
+
+/// Place the `puzzle`'s checkpoints between `entrance` and `exit`, then lock
+/// its doors, and find the way that solves it. Returns that way, and the
+/// checkpoints in the order it visits them.
+pub fn solve_puzzle<M: Maze>(
+    maze: &mut M,
+    rng: &mut Arengee,
+    puzzle: Puzzle,
+    entrance: M::Idx,
+    exit: M::Idx,
+) -> (Vec<M::Idx>, Vec<M::Idx>) {
+    let checkpoints = checkpoints(maze, rng, puzzle.checkpoints, entrance, exit);
+    let stops = [&[entrance], &checkpoints[..], &[exit]].concat();
+    for lock in locks(maze, rng, puzzle.locks, &stops) {
+        maze.overlay_mut().add_lock(lock);
+    }
+    (find_route(maze, &stops), checkpoints)
+}
+
+/// Connect up to `count` pairs of cells, each from one of `starts` to one of
+/// `finishes`, by ways that share no cells with `taken` or each other. Each
+/// way is about as long as it can get, much like the entrance and exit: it
+/// finishes as far as possible from some start, and then starts as far as
+/// possible from that finish. Returns the ways, from start to finish, ending
+/// on the finish twice like `find_path`.
+pub fn pairs<M: Maze>(
+    maze: &M,
+    rng: &mut Arengee,
+    count: u8,
+    taken: &[M::Idx],
+    starts: &[M::Idx],
+    finishes: &[M::Idx],
+) -> Vec<Vec<M::Idx>> {
+    let mut ways = vec![];
+    if count == 0 {
+        return ways;
+    }
+    let mut blocked = vec![false; maze.get_index_bound()];
+    for cell in taken {
+        blocked[maze.get_index(*cell)] = true;
+    }
+    let mut starts = starts.to_vec();
+    rng.shuffle(&mut starts);
+    while ways.len() < count as usize {
+        let free = |cells: Vec<M::Idx>, blocked: &[bool]| {
+            cells
+                .into_iter()
+                .filter(|n| !blocked[maze.get_index(*n)])
+                .collect::<Vec<_>>()
+        };
+        // one search from each part of the maze that's still free is enough
+        // to find the finish furthest from any start
+        let mut seen = blocked.clone();
+        let mut best: Option<(usize, M::Idx)> = None;
+        for start in starts.iter() {
+            if seen[maze.get_index(*start)] {
+                continue;
+            }
+            let costs = cheapest(maze, *start, |maze, cell| {
+                free(maze.get_exits(cell), &blocked)
+            });
+            for other in starts.iter() {
+                seen[maze.get_index(*other)] |= costs[maze.get_index(*other)] > 0;
+            }
+            let furthest = finishes
+                .iter()
+                .filter(|n| **n != *start && costs[maze.get_index(**n)] > 0)
+                .max_by_key(|n| costs[maze.get_index(**n)]);
+            if let Some(finish) = furthest {
+                let cost = costs[maze.get_index(*finish)];
+                if best.is_none_or(|(most, _)| cost > most) {
+                    best = Some((cost, *finish));
+                }
+            }
+        }
+        let Some((_, finish)) = best else {
+            break;
+        };
+        let topo = cheapest(maze, finish, |maze, cell| {
+            free(maze.get_entries(cell), &blocked)
+        });
+        let Some(start) = starts
+            .iter()
+            .filter(|n| **n != finish && topo[maze.get_index(**n)] > 0)
+            .max_by_key(|n| topo[maze.get_index(**n)])
+        else {
+            break;
+        };
+        let way = find_path(maze, &topo, *start, finish);
+        for cell in way.iter() {
+            blocked[maze.get_index(*cell)] = true;
+        }
+        ways.push(way);
+    }
+    ways
+}
+
+/// Lock up to `count` doors that the way through each of the `stops` can't
+/// get around, and hide each key as far off that way as possible, so that
+/// it has to make a detour to fetch it. Only keeps locks that leave a way
+/// through the maze, see `find_route`, and never more than
+/// `Puzzle::MAX_LOCKS`.
+pub fn locks<M: Maze>(
+    maze: &M,
+    rng: &mut Arengee,
+    count: u8,
+    stops: &[M::Idx],
+) -> Vec<Lock<M::Idx>> {
+    let count = count.min(Puzzle::MAX_LOCKS);
+    let mut locks: Vec<Lock<M::Idx>> = vec![];
+    if count == 0 {
+        return locks;
+    }
+    let mut route = find_route(maze, stops);
+    let mut doors = route
+        .windows(2)
+        .filter(|step| step[0] != step[1] && maze.get_paths(step[0]).contains(&step[1]))
+        .map(|step| (step[0], step[1]))
+        .collect::<Vec<_>>();
+    rng.shuffle(&mut doors);
+    // cells that can't hold a key
+    let mut taken = stops.to_vec();
+    for door in doors {
+        if locks.len() == count as usize {
+            break;
+        }
+        if locks.iter().any(|lock| lock.blocks(door.0, door.1)) {
+            continue;
+        }
+        // with its key right behind it, a door only opens if there's a way
+        // around it, and then it isn't worth locking
+        let behind = Lock { key: door.1, door };
+        if unlock_route(maze, &[&locks[..], &[behind]].concat(), stops).is_some() {
+            continue;
+        }
+        let detours = distances_from(maze, &route, &[]);
+        let before = distances_from(maze, &stops[..1], &[door]);
+        let mut keys = maze
+            .get_all_nodes()
+            .into_iter()
+            .filter(|n| {
+                detours[maze.get_index(*n)] > 1
+                    && before[maze.get_index(*n)] > 0
+                    && !taken.contains(n)
+                    && maze.overlay().get_portal(*n).is_none()
+            })
+            .collect::<Vec<_>>();
+        rng.shuffle(&mut keys);
+        keys.sort_by_key(|n| Reverse(detours[maze.get_index(*n)]));
+        // other doors may still be in the way
+        for key in keys.into_iter().take(8) {
+            let lock = Lock { key, door };
+            let candidate = [&locks[..], &[lock]].concat();
+            if let Some(unlocked) = unlock_route(maze, &candidate, stops) {
+                locks = candidate;
+                taken.push(key);
+                route = unlocked;
+                break;
+            }
+        }
+    }
+    locks
+}
+
+/// How many steps it takes to get to each cell from the nearest of the
+/// `origins`, which are 1 step away from themselves, like in `dijkstra`,
+/// without going through any of the `shut` doors. Cells it can't get to are 0.
+fn distances_from<M: Maze>(maze: &M, origins: &[M::Idx], shut: &[(M::Idx, M::Idx)]) -> Vec<usize> {
+    let mut distances = vec![0; maze.get_index_bound()];
+    let mut frontier = VecDeque::new();
+    for origin in origins {
+        if distances[maze.get_index(*origin)] == 0 {
+            distances[maze.get_index(*origin)] = 1;
+            frontier.push_back(*origin);
+        }
+    }
+    while let Some(cell) = frontier.pop_front() {
+        let distance = distances[maze.get_index(cell)];
+        for new in maze.get_paths(cell) {
+            let through = |door: &(M::Idx, M::Idx)| *door == (cell, new) || *door == (new, cell);
+            if distances[maze.get_index(new)] == 0 && !shut.iter().any(through) {
+                distances[maze.get_index(new)] = distance + 1;
+                frontier.push_back(new);
+            }
+        }
+    }
+    distances
+}
+
+/// Walk through each of the `stops` in turn along the cheapest passages, see
+/// `find_path`. Like there, the last stop appears twice at the end. If the
+/// maze has locked doors, this picks up keys on the way wherever it needs
+/// them.
+pub fn find_route<M: Maze>(maze: &M, stops: &[M::Idx]) -> Vec<M::Idx> {
+    let locks = maze.overlay().get_locks();
+    if !locks.is_empty() {
+        return unlock_route(maze, locks, stops)
+            .expect("Couldn't find the keys to the doors on the way from entrance to exit.");
+    }
+    let mut route: Vec<M::Idx> = vec![];
+    for leg in stops.windows(2) {
+        let topo = cheapest(maze, leg[1], M::get_entries);
+        let mut path = find_path(maze, &topo, leg[0], leg[1]);
+        path.pop();
+        if !route.is_empty() {
+            path.remove(0);
+        }
+        route.extend(path);
+    }
+    route.extend(stops.last());
+    route
+}
+
+/// Like `find_route`, but only through the doors of `locks` whose keys have
+/// been picked up on the way so far. `None` if some stop is out of reach.
+fn unlock_route<M: Maze>(
+    maze: &M,
+    locks: &[Lock<M::Idx>],
+    stops: &[M::Idx],
+) -> Option<Vec<M::Idx>> {
+    let mut route = vec![stops[0]];
+    let mut held = 0;
+    for leg in stops.windows(2) {
+        let (path, keys) = unlock(maze, locks, leg[0], leg[1], held)?;
+        route.extend(path.into_iter().skip(1));
+        held = keys;
+    }
+    route.extend(stops.last());
+    Some(route)
+}
+
+/// The cheapest way from `from` to `to`, through the doors of `locks` whose
+/// keys are `held`, one bit per lock, or get picked up on the way. Searches
+/// through every cell with every set of keys that can be held there, so that
+/// it can go back for a key. Returns the way and the keys held at its end.
+fn unlock<M: Maze>(
+    maze: &M,
+    locks: &[Lock<M::Idx>],
+    from: M::Idx,
+    to: M::Idx,
+    held: u32,
+) -> Option<(Vec<M::Idx>, u32)> {
+    let pick_up = |cell: M::Idx, held: u32| {
+        locks
+            .iter()
+            .enumerate()
+            .filter(|(_, lock)| lock.key == cell)
+            .fold(held, |held, (i, _)| held | 1 << i)
+    };
+    // like in `cheapest`, the queue refers to states by their position in
+    // `states`, and each state remembers where it came from
+    let steps = step_costs(maze);
+    let mut states = vec![(from, pick_up(from, held), 0)];
+    let start = steps[maze.get_index(from)];
+    let mut costs = HashMap::from([((maze.get_index(from), states[0].1), start)]);
+    let mut queue = BinaryHeap::from([Reverse((start, 0))]);
+
+    while let Some(Reverse((cost, i))) = queue.pop() {
+        let (cell, keys, _) = states[i];
+        if cost > costs[&(maze.get_index(cell), keys)] {
+            continue;
+        }
+        if cell == to {
+            let mut path = vec![cell];
+            let mut j = i;
+            while j != 0 {
+                j = states[j].2;
+                path.push(states[j].0);
+            }
+            path.reverse();
+            return Some((path, keys));
+        }
+        for new in maze.get_exits(cell) {
+            let locked = locks
+                .iter()
+                .enumerate()
+                .any(|(k, lock)| keys & 1 << k == 0 && lock.blocks(cell, new));
+            if locked {
+                continue;
+            }
+            let new_keys = pick_up(new, keys);
+            let new_cost = cost + steps[maze.get_index(new)];
+            let state = (maze.get_index(new), new_keys);
+            if costs.get(&state).is_none_or(|c| new_cost < *c) {
+                costs.insert(state, new_cost);
+                states.push((new, new_keys, i));
+                queue.push(Reverse((new_cost, states.len() - 1)));
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use super::{
+        aldous_broder,
+        binary_tree::{binary_tree, sidewinder},
+        braid, dijkstra, dijkstra_to,
+        division::{blobby_division, recursive_division},
+        dungeon::dungeon,
+        eller::eller,
+        find_path, find_route, hunt_and_kill, jarník, kruskal, one_way, portals, prim, symmetrise,
+        terrain, unlock_route,
+        weave::weave,
+        wilson, Bias, DisjointSets, Frontier, Pick, Selection, SelectionError, Weights,
+    };
+    use crate::maze::{
+        arengee::Arengee,
+        interface::{Axis, Lock, Maze, Puzzle, Symmetry, Terrain},
+        picture::Picture,
+        shape::{
+            delta::DeltaMaze,
+            layered::LayeredMaze,
+            mask::Mask,
+            regular::{self, RectilinearMaze},
+            sigma::{self, SigmaMaze},
+            theta::{RingMaze, RingNode},
+            wrap::Wrap,
+        },
+    };
+
+    /// A perfect maze has exactly one path between any two cells, i.e. it's
+    /// connected and has one passage fewer than it has cells.
+    fn assert_perfect<M: Maze>(maze: &M)
+    where
+        M::Idx: std::fmt::Debug,
+    {
+        let nodes = maze.get_all_nodes();
+        let passages = nodes
+            .iter()
+            .map(|n| maze.get_paths(*n).len())
+            .sum::<usize>();
+        assert_eq!(passages, 2 * (nodes.len() - 1), "Maze has loops or gaps");
+        let distances = dijkstra(maze, nodes[0]);
+        assert!(
+            nodes.iter().all(|n| distances[maze.get_index(*n)] > 0),
+            "Not every cell is reachable"
+        );
+    }
+
+    #[test]
+    fn aldous_broder_creates_perfect_mazes() {
+        let mut rng = Arengee::new(1);
+        assert_perfect(&aldous_broder(RectilinearMaze::new((12, 9)), &mut rng));
+        assert_perfect(&aldous_broder(RingMaze::new(6, 8), &mut rng));
+        assert_perfect(&aldous_broder(SigmaMaze::new(9), &mut rng));
+        assert_perfect(&aldous_broder(DeltaMaze::new(9), &mut rng));
+    }
+
+    #[test]
+    fn masked_mazes_are_perfect() {
+        let mask = Mask::from_ascii("..X..\n.....\nX...X\n.....\n..X..").unwrap();
+        let rect = || {
+            let mut maze = RectilinearMaze::new((10, 10));
+            maze.apply_mask(&mask).unwrap();
+            maze
+        };
+        let sigma = || {
+            let mut maze = SigmaMaze::new(10);
+            maze.apply_mask(&mask).unwrap();
+            maze
+        };
+        let delta = || {
+            let mut maze = DeltaMaze::new(10);
+            maze.apply_mask(&mask).unwrap();
+            maze
+        };
+        assert_eq!(rect().get_all_nodes().len(), 100 - 4 * 4);
+        assert!(!rect().get_all_nodes().contains(&(4, 0)));
+
+        let mut rng = Arengee::new(1);
+        assert_perfect(&kruskal(rect(), &mut rng, Weights::default()));
+        assert_perfect(&jarník(
+            rect(),
+            &mut rng,
+            &Selection::default(),
+            Weights::default(),
+        ));
+        assert_perfect(&prim(rect(), &mut rng, Frontier::Weighted));
+        assert_perfect(&wilson(rect(), &mut rng, 0));
+        assert_perfect(&aldous_broder(rect(), &mut rng));
+        assert_perfect(&hunt_and_kill(rect(), &mut rng));
+        assert_perfect(&blobby_division(rect(), &mut rng));
+        assert_perfect(&weave(rect(), &mut rng, 100));
+        assert_perfect(&kruskal(sigma(), &mut rng, Weights::default()));
+        assert_perfect(&wilson(sigma(), &mut rng, 0));
+        assert_perfect(&blobby_division(sigma(), &mut rng));
+        assert_perfect(&kruskal(delta(), &mut rng, Weights::default()));
+        assert_perfect(&hunt_and_kill(delta(), &mut rng));
+        assert_perfect(&aldous_broder(delta(), &mut rng));
+    }
+
+    fn count_passages<M: Maze>(maze: &M, axis: Axis) -> usize {
+        let ends = maze
+            .get_all_nodes()
+            .into_iter()
+            .flat_map(|a| maze.get_paths(a).into_iter().map(move |b| (a, b)))
+            .filter(|(a, b)| maze.get_axis(*a, *b) == Some(axis))
+            .count();
+        ends / 2
+    }
+
+    #[test]
+    fn biased_mazes_prefer_passages_along_the_axis() {
+        let mut rng = Arengee::new(1);
+        let horizontal = Weights {
+            bias: Some(Bias {
+                axis: Axis::Horizontal,
+                strength: 100,
+            }),
+            picture: None,
+        };
+        let maze = kruskal(RectilinearMaze::new((10, 10)), &mut rng, horizontal);
+        assert_perfect(&maze);
+        // every row is a single corridor, so rows are only connected once
+        assert_eq!(count_passages(&maze, Axis::Vertical), 9);
+
+        let vertical = Weights {
+            bias: Some(Bias {
+                axis: Axis::Vertical,
+                strength: 80,
+            }),
+            picture: None,
+        };
+        let unbiased = jarník(
+            RingMaze::new(8, 8),
+            &mut Arengee::new(2),
+            &Selection::default(),
+            Weights::default(),
+        );
+        let biased = jarník(
+            RingMaze::new(8, 8),
+            &mut Arengee::new(2),
+            &Selection::default(),
+            vertical,
+        );
+        assert_perfect(&biased);
+        assert!(
+            count_passages(&biased, Axis::Vertical) > count_passages(&unbiased, Axis::Vertical)
+        );
+        assert_perfect(&kruskal(SigmaMaze::new(9), &mut rng, vertical));
+        assert_perfect(&jarník(
+            DeltaMaze::new(9),
+            &mut rng,
+            &Selection::default(),
+            horizontal,
+        ));
+        assert_perfect(&kruskal(LayeredMaze::new((5, 5), 3), &mut rng, horizontal));
+    }
+
+    #[test]
+    fn pictures_turn_bright_areas_into_corridors() {
+        // black on the left, white on the right
+        let picture = Picture::new(2, 1, vec![0, 255]).unwrap();
+        let weights = Weights {
+            bias: None,
+            picture: Some(&picture),
+        };
+        let vertical_passages = |maze: &RectilinearMaze| {
+            let (left, right): (Vec<_>, Vec<_>) = maze
+                .get_all_nodes()
+                .into_iter()
+                .filter(|&(x, y)| maze.get_paths((x, y)).contains(&(x, y + 1)))
+                .partition(|&(x, _)| x < 10);
+            (left.len(), right.len())
+        };
+        let mut rng = Arengee::new(1);
+        let maze = kruskal(RectilinearMaze::new((20, 20)), &mut rng, weights);
+        assert_perfect(&maze);
+        let (left, right) = vertical_passages(&maze);
+        assert!(left > 3 * right, "{} vs {} vertical passages", left, right);
+        let maze = jarník(
+            RectilinearMaze::new((20, 20)),
+            &mut rng,
+            &Selection::default(),
+            weights,
+        );
+        assert_perfect(&maze);
+        let (left, right) = vertical_passages(&maze);
+        assert!(left > 3 * right, "{} vs {} vertical passages", left, right);
+        assert_perfect(&kruskal(RingMaze::new(6, 8), &mut rng, weights));
+        assert_perfect(&kruskal(SigmaMaze::new(9), &mut rng, weights));
+        assert_perfect(&jarník(
+            DeltaMaze::new(9),
+            &mut rng,
+            &Selection::default(),
+            weights,
+        ));
+    }
+
+    #[test]
+    fn recursive_division_creates_perfect_mazes() {
+        let mut rng = Arengee::new(1);
+        assert_perfect(&recursive_division(RectilinearMaze::new((12, 9)), &mut rng));
+        assert_perfect(&recursive_division(RectilinearMaze::new((2, 40)), &mut rng));
+    }
+
+    #[test]
+    fn blobby_division_creates_perfect_mazes() {
+        let mut rng = Arengee::new(1);
+        assert_perfect(&blobby_division(RectilinearMaze::new((12, 9)), &mut rng));
+        assert_perfect(&blobby_division(RingMaze::new(6, 8), &mut rng));
+        assert_perfect(&blobby_division(SigmaMaze::new(9), &mut rng));
+        assert_perfect(&blobby_division(DeltaMaze::new(9), &mut rng));
+    }
+
+    #[test]
+    fn hunt_and_kill_creates_perfect_mazes() {
+        let mut rng = Arengee::new(1);
+        assert_perfect(&hunt_and_kill(RectilinearMaze::new((12, 9)), &mut rng));
+        assert_perfect(&hunt_and_kill(RingMaze::new(6, 8), &mut rng));
+        assert_perfect(&hunt_and_kill(SigmaMaze::new(9), &mut rng));
+        assert_perfect(&hunt_and_kill(DeltaMaze::new(9), &mut rng));
+    }
+
+    #[test]
+    fn generic_algorithms_create_perfect_layered_mazes() {
+        let mut rng = Arengee::new(1);
+        let template = || LayeredMaze::new((6, 5), 3);
+        assert_perfect(&kruskal(template(), &mut rng, Weights::default()));
+        assert_perfect(&jarník(
+            template(),
+            &mut rng,
+            &Selection::default(),
+            Weights::default(),
+        ));
+        assert_perfect(&prim(template(), &mut rng, Frontier::Random));
+        assert_perfect(&wilson(template(), &mut rng, 0));
+        assert_perfect(&aldous_broder(template(), &mut rng));
+        assert_perfect(&hunt_and_kill(template(), &mut rng));
+        assert_perfect(&blobby_division(template(), &mut rng));
+    }
+
+    #[test]
+    fn kruskal_creates_perfect_mazes() {
+        let mut rng = Arengee::new(1);
+        assert_perfect(&kruskal(
+            RectilinearMaze::new((12, 9)),
+            &mut rng,
+            Weights::default(),
+        ));
+        assert_perfect(&kruskal(RingMaze::new(6, 8), &mut rng, Weights::default()));
+        assert_perfect(&kruskal(SigmaMaze::new(9), &mut rng, Weights::default()));
+        assert_perfect(&kruskal(DeltaMaze::new(9), &mut rng, Weights::default()));
+    }
+
+    #[test]
+    fn disjoint_sets_merge_and_compress() {
+        let mut sets = DisjointSets::new(6);
+        assert!(sets.union(0, 1));
+        assert!(sets.union(2, 3));
+        assert!(sets.union(1, 3));
+        assert!(!sets.union(0, 2));
+        assert_eq!(sets.find(0), sets.find(3));
+        assert_ne!(sets.find(0), sets.find(4));
+        // after a find, every node on the way hangs directly off the root
+        let root = sets.find(3);
+        assert!((0..4).all(|n| sets.parents[n] == root));
+        assert!(sets.ranks.iter().all(|rank| *rank <= 2));
+    }
+
+    #[test]
+    fn prim_creates_perfect_mazes() {
+        for frontier in [Frontier::Random, Frontier::Weighted] {
+            let mut rng = Arengee::new(1);
+            assert_perfect(&prim(RectilinearMaze::new((12, 9)), &mut rng, frontier));
+            assert_perfect(&prim(RingMaze::new(6, 8), &mut rng, frontier));
+            assert_perfect(&prim(SigmaMaze::new(9), &mut rng, frontier));
+            assert_perfect(&prim(DeltaMaze::new(9), &mut rng, frontier));
+        }
+    }
+
+    #[test]
+    fn binary_tree_creates_perfect_mazes() {
+        let mut rng = Arengee::new(1);
+        assert_perfect(&binary_tree(RectilinearMaze::new((12, 9)), &mut rng));
+        assert_perfect(&binary_tree(RectilinearMaze::new((2, 40)), &mut rng));
+    }
+
+    #[test]
+    fn sidewinder_creates_perfect_mazes() {
+        let mut rng = Arengee::new(1);
+        assert_perfect(&sidewinder(RectilinearMaze::new((12, 9)), &mut rng));
+        assert_perfect(&sidewinder(RectilinearMaze::new((2, 40)), &mut rng));
+    }
+
+    #[test]
+    fn weave_creates_perfect_mazes_with_crossings() {
+        let mut rng = Arengee::new(1);
+        let maze = weave(RectilinearMaze::new((12, 9)), &mut rng, 50);
+        assert_perfect(&maze);
+        let crossings = maze
+            .get_all_nodes()
+            .into_iter()
+            .filter(|n| maze.is_crossing(*n))
+            .collect::<Vec<_>>();
+        assert!(!crossings.is_empty());
+        for (x, y) in crossings {
+            // the passage through the crossing and the tunnel beneath it
+            let paths = maze.get_paths((x, y));
+            assert_eq!(paths.len(), 2);
+            let tunnel = if paths.contains(&(x - 1, y)) {
+                ((x, y - 1), (x, y + 1))
+            } else {
+                ((x - 1, y), (x + 1, y))
+            };
+            assert!(maze.get_paths(tunnel.0).contains(&tunnel.1));
+            assert!(maze.get_paths(tunnel.1).contains(&tunnel.0));
+        }
+        assert_perfect(&weave(RectilinearMaze::new((2, 40)), &mut rng, 50));
+    }
+
+    /// How often a passage's image under `symmetry` is a wall, counting each
+    /// end of the passage.
+    fn asymmetries<M: Maze>(maze: &M, symmetry: Symmetry) -> usize {
+        let image = |node| maze.get_symmetric(node, symmetry).unwrap();
+        maze.get_all_nodes()
+            .into_iter()
+            .flat_map(|a| maze.get_paths(a).into_iter().map(move |b| (a, b)))
+            .filter(|(a, b)| !maze.get_paths(image(*a)).contains(&image(*b)))
+            .count()
+    }
+
+    #[test]
+    fn symmetric_mazes_are_perfect() {
+        let mut rng = Arengee::new(1);
+        for (extents, symmetry) in [
+            ((10, 8), Symmetry::Mirror),
+            ((11, 8), Symmetry::Mirror),
+            ((11, 9), Symmetry::Rotation(2)),
+            ((10, 9), Symmetry::Rotation(2)),
+            ((9, 9), Symmetry::Rotation(4)),
+        ] {
+            let maze = jarník(
+                RectilinearMaze::new(extents),
+                &mut rng,
+                &Selection::default(),
+                Weights::default(),
+            );
+            let maze = symmetrise(maze, &mut rng, symmetry);
+            assert_perfect(&maze);
+            assert_eq!(asymmetries(&maze, symmetry), 0, "{:?}", extents);
+        }
+        for symmetry in [
+            Symmetry::Mirror,
+            Symmetry::Rotation(4),
+            Symmetry::Rotation(8),
+        ] {
+            let maze = kruskal(RingMaze::new(8, 8), &mut rng, Weights::default());
+            let maze = symmetrise(maze, &mut rng, symmetry);
+            assert_perfect(&maze);
+            assert_eq!(asymmetries(&maze, symmetry), 0);
+        }
+    }
+
+    #[test]
+    fn wrapping_mazes_are_perfect() {
+        let mut rng = Arengee::new(1);
+        let wrap = |horizontal, vertical, twist| Wrap {
+            horizontal,
+            vertical,
+            twist,
+        };
+        for wrap in [
+            wrap(true, false, false),
+            wrap(true, true, false),
+            wrap(true, false, true),
+            wrap(true, true, true),
+            wrap(false, true, true),
+        ] {
+            let mut maze = RectilinearMaze::new((9, 7));
+            maze.set_wrap(wrap).unwrap();
+            let maze = kruskal(maze, &mut rng, Weights::default());
+            assert_perfect(&maze);
+            let wrapping = maze.get_all_nodes().into_iter().any(|node| {
+                regular::Direction::iterator()
+                    .any(|d| maze.wraps(node, d) && !maze.has_wall(node, d))
+            });
+            assert!(wrapping, "{:?}", wrap);
+        }
+        for (size, wrap) in [
+            (8, wrap(true, false, false)),
+            (8, wrap(false, true, false)),
+            (8, wrap(true, true, false)),
+            (9, wrap(false, true, true)),
+        ] {
+            let mut maze = SigmaMaze::new(size);
+            maze.set_wrap(wrap).unwrap();
+            let maze = jarník(maze, &mut rng, &Selection::default(), Weights::default());
+            assert_perfect(&maze);
+            let wrapping = maze.get_all_nodes().into_iter().any(|node| {
+                sigma::Direction::all()
+                    .into_iter()
+                    .any(|d| maze.wraps(&node, d) && maze.has_path(&node, d))
+            });
+            assert!(wrapping, "{:?}", wrap);
+        }
+    }
+
+    #[test]
+    fn symmetry_breaks_where_it_has_to() {
+        let mut rng = Arengee::new(1);
+        // turned around, no cell or passage stays where it is, so the parts
+        // can only be joined by passages without an image
+        for (extents, symmetry) in [
+            ((10, 8), Symmetry::Rotation(2)),
+            ((10, 10), Symmetry::Rotation(4)),
+        ] {
+            let maze = kruskal(RectilinearMaze::new(extents), &mut rng, Weights::default());
+            let maze = symmetrise(maze, &mut rng, symmetry);
+            assert_perfect(&maze);
+            assert!((1..=6).contains(&asymmetries(&maze, symmetry)));
+        }
+        let maze = RectilinearMaze::new((10, 8));
+        assert_eq!(maze.get_symmetric((0, 0), Symmetry::Rotation(4)), None);
+        assert_eq!(maze.get_symmetric((0, 0), Symmetry::Rotation(3)), None);
+        let maze = RingMaze::new(8, 8);
+        assert_eq!(
+            maze.get_symmetric(RingNode { row: 3, column: 1 }, Symmetry::Rotation(3)),
+            None
+        );
+    }
+
+    #[test]
+    fn dungeon_connects_open_rooms() {
+        let mut rng = Arengee::new(1);
+        let maze = dungeon(RectilinearMaze::new((16, 12)), &mut rng, 6);
+        let rooms = maze.get_rooms();
+        assert!(rooms.len() > 1);
+        let mut loops = 0;
+        for (i, room) in rooms.iter().enumerate() {
+            for (x, y) in room.cells() {
+                for neighbour in [(x + 1, y), (x, y + 1)] {
+                    if room.contains(neighbour) {
+                        assert!(maze.get_paths((x, y)).contains(&neighbour));
+                    }
+                }
+            }
+            let (w, h) = room.extents;
+            loops += (w - 1) * (h - 1);
+            assert!(rooms[i + 1..].iter().all(|other| !room.is_near(other, 1)));
+        }
+        // apart from the rooms, the maze is perfect
+        let nodes = maze.get_all_nodes();
+        let passages = nodes
+            .iter()
+            .map(|n| maze.get_paths(*n).len())
+            .sum::<usize>();
+        assert_eq!(passages, 2 * (nodes.len() - 1 + loops));
+        let distances = dijkstra(&maze, nodes[0]);
+        assert!(nodes.iter().all(|n| distances[maze.get_index(*n)] > 0));
+    }
+
+    #[test]
+    fn weave_solutions_pass_through_tunnels() {
+        let mut rng = Arengee::new(3);
+        let mut maze = weave(RectilinearMaze::new((10, 10)), &mut rng, 100);
+        let solution = maze.make_solution(&mut rng, Puzzle::default());
+        let (entrance, exit) = (solution.path[0], *solution.path.last().unwrap());
+        assert_eq!(entrance.1, 0);
+        assert_eq!(exit.1, 9);
+        for step in solution.path.windows(2) {
+            assert!(step[0] == step[1] || maze.get_paths(step[0]).contains(&step[1]));
+        }
+        assert!(
+            solution
+                .path
+                .windows(2)
+                .any(|step| { step[0].0.abs_diff(step[1].0) + step[0].1.abs_diff(step[1].1) == 2 }),
+            "Solution doesn't go through a tunnel"
+        );
+    }
+
+    #[test]
+    fn eller_creates_perfect_mazes() {
+        let mut rng = Arengee::new(1);
+        assert_perfect(&eller(RectilinearMaze::new((12, 9)), &mut rng));
+        assert_perfect(&eller(RectilinearMaze::new((2, 40)), &mut rng));
+    }
+
+    #[test]
+    fn wilson_creates_perfect_mazes() {
+        for aldous_broder in [0, 50] {
+            let mut rng = Arengee::new(1);
+            assert_perfect(&wilson(
+                RectilinearMaze::new((12, 9)),
+                &mut rng,
+                aldous_broder,
+            ));
+            assert_perfect(&wilson(RingMaze::new(6, 8), &mut rng, aldous_broder));
+            assert_perfect(&wilson(SigmaMaze::new(9), &mut rng, aldous_broder));
+            assert_perfect(&wilson(DeltaMaze::new(9), &mut rng, aldous_broder));
+        }
+    }
+
+    fn count_dead_ends<M: Maze>(maze: &M) -> usize {
+        maze.get_all_nodes()
+            .into_iter()
+            .filter(|n| maze.get_paths(*n).len() == 1)
+            .count()
+    }
+
+    /// Some cells, like the corners of a delta maze, only have one neighbour
+    /// and stay dead ends no matter what.
+    fn assert_braided<M: Maze>(maze: &M) {
+        assert!(maze
+            .get_all_nodes()
+            .into_iter()
+            .filter(|n| maze.get_paths(*n).len() == 1)
+            .all(|n| maze.get_walls(n).is_empty()));
+    }
+
+    #[test]
+    fn braid_removes_all_dead_ends() {
+        let mut rng = Arengee::new(1);
+        assert_braided(&braid(
+            kruskal(RectilinearMaze::new((12, 9)), &mut rng, Weights::default()),
+            &mut rng,
+            100,
+        ));
+        assert_braided(&braid(
+            kruskal(RingMaze::new(6, 8), &mut rng, Weights::default()),
+            &mut rng,
+            100,
+        ));
+        assert_braided(&braid(
+            kruskal(SigmaMaze::new(9), &mut rng, Weights::default()),
+            &mut rng,
+            100,
+        ));
+        assert_braided(&braid(
+            kruskal(DeltaMaze::new(9), &mut rng, Weights::default()),
+            &mut rng,
+            100,
+        ));
+    }
+
+    #[test]
+    fn braid_removes_some_dead_ends() {
+        let mut rng = Arengee::new(1);
+        let maze = kruskal(RectilinearMaze::new((20, 20)), &mut rng, Weights::default());
+        let before = count_dead_ends(&maze);
+        let mut maze = braid(maze, &mut rng, 50);
+        let after = count_dead_ends(&maze);
+        assert!(after > 0 && after <= before / 2, "{} -> {}", before, after);
+        // loops don't break solving the maze
+        let solution = maze.make_solution(&mut rng, Puzzle::default());
+        assert!(solution.distances.iter().all(|d| *d > 0));
+        for step in solution.path.windows(2) {
+            assert!(step[0] == step[1] || maze.get_paths(step[0]).contains(&step[1]));
+        }
+    }
+
+    fn count_one_way<M: Maze>(maze: &M) -> usize {
+        maze.get_all_nodes()
+            .into_iter()
+            .map(|n| {
+                maze.get_paths(n)
+                    .into_iter()
+                    .filter(|m| maze.overlay().is_one_way(n, *m))
+                    .count()
+            })
+            .sum()
+    }
+
+    /// Every cell can be reached from `origin`, and `origin` from every cell.
+    fn assert_strongly_connected<M: Maze>(maze: &M, origin: M::Idx)
+    where
+        M::Idx: std::fmt::Debug,
+    {
+        let from = dijkstra(maze, origin);
+        let to = dijkstra_to(maze, origin);
+        for node in maze.get_all_nodes() {
+            assert!(from[maze.get_index(node)] > 0, "{:?} is unreachable", node);
+            assert!(to[maze.get_index(node)] > 0, "{:?} is a trap", node);
+        }
+    }
+
+    fn assert_one_way_solvable<M: Maze>(template: M, rng: &mut Arengee)
+    where
+        M::Idx: std::fmt::Debug,
+    {
+        let maze = kruskal(template, rng, Weights::default());
+        let mut maze = one_way(braid(maze, rng, 100), rng, 50);
+        assert!(count_one_way(&maze) > 0);
+        assert_strongly_connected(&maze, maze.get_all_nodes()[0]);
+        let solution = maze.make_solution(rng, Puzzle::default());
+        for step in solution.path.windows(2) {
+            assert!(step[0] == step[1] || maze.get_exits(step[0]).contains(&step[1]));
+        }
+    }
+
+    #[test]
+    fn one_way_passages_keep_mazes_solvable() {
+        let mut rng = Arengee::new(1);
+        assert_one_way_solvable(RectilinearMaze::new((12, 12)), &mut rng);
+        assert_one_way_solvable(SigmaMaze::new(10), &mut rng);
+        assert_one_way_solvable(DeltaMaze::new(10), &mut rng);
+        assert_one_way_solvable(LayeredMaze::new((6, 6), 3), &mut rng);
+        assert_one_way_solvable(RingMaze::new(8, 8), &mut rng);
+    }
+
+    #[test]
+    fn perfect_mazes_get_no_one_way_passages() {
+        let mut rng = Arengee::new(1);
+        let maze = kruskal(RectilinearMaze::new((12, 12)), &mut rng, Weights::default());
+        let maze = one_way(maze, &mut rng, 100);
+        assert_eq!(count_one_way(&maze), 0);
+        assert_perfect(&maze);
+    }
+
+    #[test]
+    fn dijkstra_follows_one_way_passages() {
+        let mut maze = RectilinearMaze::new((3, 2));
+        maze.carve((0, 0), (1, 0));
+        maze.carve((1, 0), (2, 0));
+        maze.overlay_mut().set_one_way((1, 0), (2, 0));
+        assert_eq!(maze.get_exits((2, 0)), vec![]);
+        assert_eq!(maze.get_entries((2, 0)), vec![(1, 0)]);
+        assert_eq!(dijkstra(&maze, (0, 0))[..3], [1, 2, 3]);
+        assert_eq!(dijkstra(&maze, (2, 0))[..3], [0, 0, 1]);
+        assert_eq!(dijkstra_to(&maze, (2, 0))[..3], [3, 2, 1]);
+        // carving it again makes it an ordinary passage
+        maze.carve((2, 0), (1, 0));
+        assert!(!maze.overlay().is_one_way((1, 0), (2, 0)));
+        assert_eq!(dijkstra(&maze, (2, 0))[..3], [3, 2, 1]);
+    }
+
+    #[test]
+    fn dijkstra_takes_the_cheapest_way_around_rough_terrain() {
+        let mut maze = RectilinearMaze::new((3, 2));
+        maze.carve_all();
+        maze.overlay_mut().set_terrain((1, 0), Terrain::Water);
+        let distances = dijkstra(&maze, (0, 0));
+        // through the water costs 1 + 6 + 1, around it 5 cells of ground
+        assert_eq!(distances[maze.get_index((2, 0))], 5);
+        assert_eq!(distances[maze.get_index((1, 0))], 7);
+        let topo = dijkstra_to(&maze, (2, 0));
+        assert_eq!(
+            find_path(&maze, &topo, (0, 0), (2, 0)),
+            vec![(0, 0), (0, 1), (1, 1), (2, 1), (2, 0), (2, 0)]
+        );
+    }
+
+    #[test]
+    fn terrain_covers_a_share_of_the_cells() {
+        let mut rng = Arengee::new(1);
+        let maze = terrain(RingMaze::new(8, 8), &mut rng, 30);
+        let nodes = maze.get_all_nodes();
+        let rough = nodes
+            .iter()
+            .filter(|n| maze.overlay().get_terrain(**n) != Terrain::Ground)
+            .count();
+        assert_eq!(rough, nodes.len() * 30 / 100);
+        assert!(nodes
+            .iter()
+            .any(|n| maze.overlay().get_terrain(*n) == Terrain::Mud));
+        assert!(nodes
+            .iter()
+            .any(|n| maze.overlay().get_terrain(*n) == Terrain::Water));
+    }
+
+    #[test]
+    fn portals_link_cells_far_apart() {
+        let mut rng = Arengee::new(1);
+        let maze = kruskal(RectilinearMaze::new((8, 8)), &mut rng, Weights::default());
+        let maze = portals(maze, &mut rng, 5);
+        let pairs = maze.overlay().get_portals();
+        assert_eq!(pairs.len(), 5);
+        let mut ends = pairs.iter().flat_map(|(a, b)| [*a, *b]).collect::<Vec<_>>();
+        ends.sort();
+        ends.dedup();
+        assert_eq!(ends.len(), 10, "Cells have at most one portal");
+        for &(a, b) in pairs {
+            assert!(!maze.get_neighbours(a).contains(&b));
+            assert_eq!(maze.overlay().get_portal(a), Some(b));
+            assert_eq!(maze.overlay().get_portal(b), Some(a));
+        }
+        // portals are no passages, so the maze itself stays perfect
+        assert_perfect(&maze);
+    }
+
+    #[test]
+    fn solutions_jump_through_portals() {
+        let mut maze = RectilinearMaze::new((5, 1));
+        for x in 0..4 {
+            maze.carve((x, 0), (x + 1, 0));
+        }
+        maze.overlay_mut().add_portal((0, 0), (4, 0));
+        assert_eq!(maze.get_exits((0, 0)), vec![(1, 0), (4, 0)]);
+        assert_eq!(maze.get_paths((0, 0)), vec![(1, 0)]);
+        assert_eq!(dijkstra(&maze, (0, 0))[..5], [1, 2, 3, 3, 2]);
+        let topo = dijkstra_to(&maze, (4, 0));
+        assert_eq!(
+            find_path(&maze, &topo, (0, 0), (4, 0)),
+            vec![(0, 0), (4, 0), (4, 0)]
+        );
+    }
+
+    #[test]
+    fn solutions_visit_checkpoints_in_order() {
+        let mut rng = Arengee::new(1);
+        let maze = kruskal(RectilinearMaze::new((10, 10)), &mut rng, Weights::default());
+        let mut maze = braid(maze, &mut rng, 50);
+        let solution = maze.make_solution(
+            &mut rng,
+            Puzzle {
+                checkpoints: 4,
+                locks: 0,
+                pairs: 0,
+            },
+        );
+        assert_eq!(solution.checkpoints.len(), 4);
+        let segments = solution.segments();
+        assert_eq!(segments.len(), 5);
+        assert_eq!(segments[0][0], solution.path[0]);
+        for (segment, checkpoint) in segments.iter().zip(solution.checkpoints.iter()) {
+            assert_eq!(segment.last(), Some(checkpoint));
+        }
+        for step in solution.path.windows(2) {
+            assert!(step[0] == step[1] || maze.get_exits(step[0]).contains(&step[1]));
+        }
+    }
+
+    #[test]
+    fn doors_stay_shut_until_their_key_is_held() {
+        let mut maze = RectilinearMaze::new((4, 2));
+        for x in 0..3 {
+            maze.carve((x, 0), (x + 1, 0));
+        }
+        maze.carve((1, 0), (1, 1));
+        maze.carve((1, 1), (0, 1));
+        let door = ((2, 0), (3, 0));
+        maze.overlay_mut().add_lock(Lock { key: (0, 1), door });
+        assert_eq!(
+            find_route(&maze, &[(0, 0), (3, 0)]),
+            vec![
+                (0, 0),
+                (1, 0),
+                (1, 1),
+                (0, 1),
+                (1, 1),
+                (1, 0),
+                (2, 0),
+                (3, 0),
+                (3, 0)
+            ]
+        );
+        let behind = Lock { key: (3, 0), door };
+        assert_eq!(unlock_route(&maze, &[behind], &[(0, 0), (3, 0)]), None);
+    }
+
+    #[test]
+    fn solutions_fetch_keys_before_their_doors() {
+        let mut rng = Arengee::new(1);
+        let mut maze = kruskal(RectilinearMaze::new((12, 12)), &mut rng, Weights::default());
+        let solution = maze.make_solution(
+            &mut rng,
+            Puzzle {
+                checkpoints: 0,
+                locks: 3,
+                pairs: 0,
+            },
+        );
+        let locks = maze.overlay().get_locks();
+        assert_eq!(locks.len(), 3);
+        for lock in locks {
+            let key = solution.path.iter().position(|n| *n == lock.key).unwrap();
+            let door = solution
+                .path
+                .windows(2)
+                .position(|step| lock.blocks(step[0], step[1]))
+                .unwrap();
+            assert!(key < door);
+        }
+        for step in solution.path.windows(2) {
+            assert!(step[0] == step[1] || maze.get_exits(step[0]).contains(&step[1]));
+        }
+    }
+
+    #[test]
+    fn pairs_connect_the_edges_without_sharing_cells() {
+        let mut rng = Arengee::new(1);
+        let maze = kruskal(RectilinearMaze::new((12, 12)), &mut rng, Weights::default());
+        let mut maze = braid(maze, &mut rng, 50);
+        let solution = maze.make_solution(
+            &mut rng,
+            Puzzle {
+                checkpoints: 0,
+                locks: 0,
+                pairs: 4,
+            },
+        );
+        assert_eq!(solution.pairs.len(), 3);
+        let mut taken = HashSet::new();
+        for way in [&solution.path].into_iter().chain(solution.pairs.iter()) {
+            let (start, finish) = (way[0], way[way.len() - 1]);
+            assert_ne!(start, finish);
+            assert!(maze.opening(start).is_some() && maze.opening(finish).is_some());
+            for step in way.windows(2) {
+                assert!(step[0] == step[1] || maze.get_exits(step[0]).contains(&step[1]));
+            }
+            let cells = way.iter().copied().collect::<HashSet<_>>();
+            assert!(taken.is_disjoint(&cells));
+            taken.extend(cells);
+        }
+    }
+
+    #[test]
+    fn routes_pass_through_every_stop() {
+        let mut maze = RectilinearMaze::new((4, 1));
+        for x in 0..3 {
+            maze.carve((x, 0), (x + 1, 0));
+        }
+        assert_eq!(
+            find_route(&maze, &[(1, 0), (3, 0), (0, 0)]),
+            vec![(1, 0), (2, 0), (3, 0), (2, 0), (1, 0), (0, 0), (0, 0)]
+        );
+        let topo = dijkstra_to(&maze, (3, 0));
+        assert_eq!(
+            find_route(&maze, &[(0, 0), (3, 0)]),
+            find_path(&maze, &topo, (0, 0), (3, 0))
+        );
+    }
+
+    #[test]
+    fn selection_parses_weighted_picks() {
+        assert_eq!(
+            "newest:75,random:25".parse::<Selection>(),
+            Ok(Selection(vec![(Pick::Newest, 75), (Pick::Random, 25)]))
+        );
+        assert_eq!(
+            "oldest".parse::<Selection>(),
+            Ok(Selection(vec![(Pick::Oldest, 1)]))
+        );
+    }
+
+    #[test]
+    fn selection_round_trips_through_display() {
+        let selection = Selection(vec![(Pick::Middle, 2), (Pick::Oldest, 3)]);
+        assert_eq!(selection.to_string().parse::<Selection>(), Ok(selection));
+    }
+
+    #[test]
+    fn selection_rejects_nonsense() {
+        assert_eq!("".parse::<Selection>(), Err(SelectionError::Empty));
+        assert_eq!(
+            "newest:0".parse::<Selection>(),
+            Err(SelectionError::IllegalWeight("0".into()))
+        );
+        assert_eq!(
+            "sideways".parse::<Selection>(),
+            Err(SelectionError::UnknownPick("sideways".into()))
+        );
+        assert_eq!(
+            "newest:4294967295,random:1".parse::<Selection>(),
+            Err(SelectionError::TooHeavy)
+        );
+        assert!("newest:4294967294,random:1".parse::<Selection>().is_ok());
+    }
+}
@@ -0,0 +1,49 @@
+use crate::maze::{
+    arengee::Arengee,
+    shape::regular::{Direction, RectilinearMaze},
+};
+
+/// Carves either north or east from every cell. Heavily biased: the top row
+/// and the eastern column are single long corridors, and all passages run
+/// diagonally towards the north-eastern corner.
+pub fn binary_tree(mut maze: RectilinearMaze, rng: &mut Arengee) -> RectilinearMaze {
+    let (width, height) = maze.get_extents();
+    for y in 0..height {
+        for x in 0..width {
+            let targets = [Direction::Up, Direction::Right]
+                .into_iter()
+                .filter_map(|direction| maze.translate((x, y), direction))
+                .collect::<Vec<_>>();
+            if !targets.is_empty() {
+                let target = targets[rng.get_portable_usize(0..targets.len())];
+                maze.move_from_to((x, y), target);
+            }
+        }
+    }
+    maze
+}
+
+/// Works through each row from west to east, carving runs of cells towards
+/// the east and closing each run with a passage north from one of its cells.
+/// Like the binary tree, the top row is a single corridor, but the bias
+/// towards the north-east is gone.
+pub fn sidewinder(mut maze: RectilinearMaze, rng: &mut Arengee) -> RectilinearMaze {
+    let (width, height) = maze.get_extents();
+    for y in 0..height {
+        let mut run_start = 0;
+        for x in 0..width {
+            let at_eastern_edge = maze.translate((x, y), Direction::Right).is_none();
+            let at_northern_edge = maze.translate((x, y), Direction::Up).is_none();
+            if at_eastern_edge || (!at_northern_edge && rng.u32(0..2) == 0) {
+                if !at_northern_edge {
+                    let member = rng.get_portable_usize(run_start..x + 1);
+                    maze.move_from_to((member, y), (member, y - 1));
+                }
+                run_start = x + 1;
+            } else {
+                maze.move_from_to((x, y), (x + 1, y));
+            }
+        }
+    }
+    maze
+}
@@ -0,0 +1,113 @@
+use crate::maze::{arengee::Arengee, interface::Maze, shape::regular::RectilinearMaze};
+
+/// Recursive division starts out with an open area and splits it in two by
+/// adding a wall with a single gap in it, until the areas are only a
+/// corridor wide. Creates boxy, room-like mazes with long straight walls.
+pub fn recursive_division(mut maze: RectilinearMaze, rng: &mut Arengee) -> RectilinearMaze {
+    maze.carve_all();
+    let (width, height) = maze.get_extents();
+    // (x, y, width, height) of the areas that still need dividing
+    let mut areas = vec![(0, 0, width, height)];
+
+    while let Some((x, y, w, h)) = areas.pop() {
+        if w < 2 || h < 2 {
+            continue;
+        }
+        let horizontal = match w.cmp(&h) {
+            std::cmp::Ordering::Less => true,
+            std::cmp::Ordering::Greater => false,
+            std::cmp::Ordering::Equal => rng.u32(0..2) == 0,
+        };
+        if horizontal {
+            let split = rng.get_portable_usize(1..h);
+            let gap = rng.get_portable_usize(0..w);
+            for i in (0..w).filter(|i| *i != gap) {
+                maze.wall_off((x + i, y + split - 1), (x + i, y + split));
+            }
+            areas.push((x, y, w, split));
+            areas.push((x, y + split, w, h - split));
+        } else {
+            let split = rng.get_portable_usize(1..w);
+            let gap = rng.get_portable_usize(0..h);
+            for i in (0..h).filter(|i| *i != gap) {
+                maze.wall_off((x + split - 1, y + i), (x + split, y + i));
+            }
+            areas.push((x, y, split, h));
+            areas.push((x + split, y, w - split, h));
+        }
+    }
+
+    maze
+}
+
+/// The "blobby" variant of recursive division works for any maze: instead of
+/// splitting an area along a straight line, it grows two blobs from random
+/// seeds until they fill the area, and walls off the border between them
+/// except for a single gap.
+pub fn blobby_division<M: Maze>(mut maze: M, rng: &mut Arengee) -> M {
+    maze.carve_all();
+    // which blob a cell belongs to while its area is being divided
+    let mut blob: Vec<Option<bool>> = vec![None; maze.get_index_bound()];
+    let mut in_area = vec![false; blob.len()];
+    let mut areas = vec![maze.get_all_nodes()];
+
+    while let Some(area) = areas.pop() {
+        if area.len() < 2 {
+            continue;
+        }
+        let a = rng.get_portable_usize(0..area.len());
+        let b = (a + rng.get_portable_usize(1..area.len())) % area.len();
+        for node in area.iter() {
+            in_area[maze.get_index(*node)] = true;
+        }
+        blob[maze.get_index(area[a])] = Some(false);
+        blob[maze.get_index(area[b])] = Some(true);
+
+        let mut growing = vec![area[a], area[b]];
+        while !growing.is_empty() {
+            let i = rng.get_portable_usize(0..growing.len());
+            let cell = growing[i];
+            let candidates = maze
+                .get_paths(cell)
+                .into_iter()
+                .filter(|n| in_area[maze.get_index(*n)] && blob[maze.get_index(*n)].is_none())
+                .collect::<Vec<_>>();
+            if candidates.is_empty() {
+                growing.swap_remove(i);
+            } else {
+                let claimed = candidates[rng.get_portable_usize(0..candidates.len())];
+                blob[maze.get_index(claimed)] = blob[maze.get_index(cell)];
+                growing.push(claimed);
+            }
+        }
+
+        let (first, second): (Vec<_>, Vec<_>) = area
+            .iter()
+            .partition(|n| blob[maze.get_index(**n)] == Some(false));
+        let border = first
+            .iter()
+            .flat_map(|a| {
+                maze.get_paths(*a)
+                    .into_iter()
+                    .filter(|b| blob[maze.get_index(*b)] == Some(true))
+                    .map(|b| (*a, b))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let gap = rng.get_portable_usize(0..border.len());
+        for (i, (a, b)) in border.into_iter().enumerate() {
+            if i != gap {
+                maze.build_wall(a, b);
+            }
+        }
+
+        for node in area.iter() {
+            blob[maze.get_index(*node)] = None;
+            in_area[maze.get_index(*node)] = false;
+        }
+        areas.push(first);
+        areas.push(second);
+    }
+
+    maze
+}
@@ -0,0 +1,60 @@
+use crate::maze::{
+    arengee::Arengee,
+    interface::Maze,
+    shape::regular::{RectilinearMaze, Room},
+};
+
+use super::DisjointSets;
+
+/// Kruskal's algorithm with rooms placed before it starts. We try to place up
+/// to `rooms` rooms, each between 2 cells and a quarter of the maze wide and
+/// high, and skip those that would leave out cells of a mask or come closer
+/// than one cell to another room, so that there's always a corridor between
+/// them. Kruskal's algorithm then fills in the rest of the maze, treating each
+/// room like one big cell, so that every room is connected to every other.
+pub fn dungeon(mut maze: RectilinearMaze, rng: &mut Arengee, rooms: u8) -> RectilinearMaze {
+    let mut sets = DisjointSets::new(maze.get_index_bound());
+    let (width, height) = maze.get_extents();
+    let largest = (width.min(height) / 4).max(2);
+    let mut placed: Vec<Room> = vec![];
+    for _ in 0..rooms as usize * 10 {
+        if placed.len() == rooms as usize {
+            break;
+        }
+        let extents = (
+            rng.get_portable_usize(2..largest + 1),
+            rng.get_portable_usize(2..largest + 1),
+        );
+        if extents.0 > width || extents.1 > height {
+            continue;
+        }
+        let room = Room {
+            origin: (
+                rng.get_portable_usize(0..width - extents.0 + 1),
+                rng.get_portable_usize(0..height - extents.1 + 1),
+            ),
+            extents,
+        };
+        if placed.iter().any(|other| room.is_near(other, 1))
+            || !room.cells().all(|cell| maze.is_enabled(cell))
+        {
+            continue;
+        }
+        let origin = maze.get_index(room.origin);
+        for cell in room.cells() {
+            sets.union(origin, maze.get_index(cell));
+        }
+        maze.add_room(room);
+        placed.push(room);
+    }
+
+    let mut edges = maze.get_all_edges();
+    rng.shuffle(&mut edges);
+    for (a, b) in edges {
+        if sets.union(maze.get_index(a), maze.get_index(b)) {
+            maze.carve(a, b);
+        }
+    }
+
+    maze
+}
@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+
+use crate::maze::{
+    arengee::Arengee,
+    shape::regular::{Direction, RectilinearMaze},
+};
+
+/// A finished row of a rectilinear maze, as produced by [`eller_rows`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Row {
+    pub y: usize,
+    passages: Vec<Vec<Direction>>,
+}
+
+impl Row {
+    fn new(y: usize, width: usize) -> Self {
+        Self {
+            y,
+            passages: vec![vec![]; width],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.passages.len()
+    }
+
+    /// Whether the cell in column `x` is closed off towards `direction`.
+    /// The outer walls of the maze are always closed.
+    pub fn has_wall(&self, x: usize, direction: Direction) -> bool {
+        !self.passages[x].contains(&direction)
+    }
+
+    fn open(&mut self, x: usize, direction: Direction) {
+        self.passages[x].push(direction);
+    }
+}
+
+/// Eller's algorithm generates a maze one row at a time and only ever needs
+/// to know about the current row, so it yields the finished rows one by one.
+struct Eller<'a> {
+    rng: &'a mut Arengee,
+    width: usize,
+    height: usize,
+    y: usize,
+    // every cell in the current row belongs to a set of cells that are
+    // already connected, possibly via previous rows
+    sets: Vec<usize>,
+    // which cells of the current row have a passage to the row above
+    up: Vec<bool>,
+    next_set: usize,
+}
+
+impl<'a> Eller<'a> {
+    fn new(width: usize, height: usize, rng: &'a mut Arengee) -> Self {
+        Self {
+            rng,
+            width,
+            height,
+            y: 0,
+            sets: (0..width).collect(),
+            up: vec![false; width],
+            next_set: width,
+        }
+    }
+
+    fn join(&mut self, keep: usize, replace: usize) {
+        self.sets
+            .iter_mut()
+            .filter(|set| **set == replace)
+            .for_each(|set| *set = keep);
+    }
+
+    fn carve_east(&mut self, row: &mut Row, last: bool) {
+        for x in 0..self.width.saturating_sub(1) {
+            if self.sets[x] != self.sets[x + 1] && (last || self.rng.u32(0..2) == 0) {
+                row.open(x, Direction::Right);
+                row.open(x + 1, Direction::Left);
+                self.join(self.sets[x], self.sets[x + 1]);
+            }
+        }
+    }
+
+    /// Every set needs at least one passage down, or it'd be cut off from the rest of the maze.
+    fn carve_south(&mut self, row: &mut Row) {
+        // the members of each set, with the sets in order of their leftmost member
+        let mut members: Vec<Vec<usize>> = vec![];
+        let mut buckets: HashMap<usize, usize> = HashMap::new();
+        for (x, set) in self.sets.iter().enumerate() {
+            let bucket = *buckets.entry(*set).or_insert_with(|| {
+                members.push(vec![]);
+                members.len() - 1
+            });
+            members[bucket].push(x);
+        }
+
+        let mut down = vec![false; self.width];
+        for members in members {
+            down[members[self.rng.get_portable_usize(0..members.len())]] = true;
+            for member in members {
+                if self.rng.u32(0..2) == 0 {
+                    down[member] = true;
+                }
+            }
+        }
+
+        for (x, down) in down.iter().enumerate() {
+            if *down {
+                row.open(x, Direction::Down);
+            } else {
+                self.sets[x] = self.next_set;
+                self.next_set += 1;
+            }
+        }
+        self.up = down;
+    }
+}
+
+impl Iterator for Eller<'_> {
+    type Item = Row;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.y >= self.height {
+            return None;
+        }
+        let last = self.y + 1 == self.height;
+        let mut row = Row::new(self.y, self.width);
+        for x in (0..self.width).filter(|x| self.up[*x]) {
+            row.open(x, Direction::Up);
+        }
+
+        self.carve_east(&mut row, last);
+        if !last {
+            self.carve_south(&mut row);
+        }
+        self.y += 1;
+        Some(row)
+    }
+}
+
+/// Stream the rows of a rectilinear maze `width` cells wide and `height`
+/// cells high, top to bottom. Memory use is proportional to the width only,
+/// so this can produce mazes that are too tall to hold in memory. Use
+/// [`eller`] to fill a [`RectilinearMaze`] instead.
+pub fn eller_rows(
+    width: usize,
+    height: usize,
+    rng: &mut Arengee,
+) -> impl Iterator<Item = Row> + '_ {
+    Eller::new(width, height, rng)
+}
+
+/// Eller's algorithm, which carves the maze row by row while only keeping
+/// track of which cells of the current row are connected.
+pub fn eller(mut maze: RectilinearMaze, rng: &mut Arengee) -> RectilinearMaze {
+    let (width, height) = maze.get_extents();
+    for row in eller_rows(width, height, rng) {
+        for x in 0..width {
+            if !row.has_wall(x, Direction::Right) {
+                maze.move_from_to((x, row.y), (x + 1, row.y));
+            }
+            if !row.has_wall(x, Direction::Down) {
+                maze.move_from_to((x, row.y), (x, row.y + 1));
+            }
+        }
+    }
+    maze
+}
+
+#[cfg(test)]
+mod test {
+    use super::{eller_rows, Row};
+    use crate::maze::{arengee::Arengee, shape::regular::Direction};
+
+    #[test]
+    fn rows_agree_with_their_neighbours() {
+        let mut rng = Arengee::new(1);
+        let rows = eller_rows(7, 30, &mut rng).collect::<Vec<_>>();
+        assert_eq!(rows.len(), 30);
+        for (above, below) in rows.iter().zip(rows.iter().skip(1)) {
+            for x in 0..7 {
+                assert_eq!(
+                    above.has_wall(x, Direction::Down),
+                    below.has_wall(x, Direction::Up)
+                );
+            }
+        }
+        assert!((0..7).all(|x| rows[0].has_wall(x, Direction::Up)));
+        assert!((0..7).all(|x| rows[29].has_wall(x, Direction::Down)));
+    }
+
+    #[test]
+    fn streams_beyond_what_fits_in_a_maze() {
+        let mut rng = Arengee::new(1);
+        // only ever holds on to the row above, like a printer would
+        let mut above: Option<Row> = None;
+        let mut count = 0;
+        for row in eller_rows(3, 100_000, &mut rng) {
+            assert_eq!((row.y, row.width()), (count, 3));
+            if let Some(above) = &above {
+                for x in 0..3 {
+                    assert_eq!(
+                        above.has_wall(x, Direction::Down),
+                        row.has_wall(x, Direction::Up)
+                    );
+                }
+            }
+            above = Some(row);
+            count += 1;
+        }
+        assert_eq!(count, 100_000);
+    }
+}
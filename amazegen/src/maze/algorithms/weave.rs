@@ -0,0 +1,102 @@
+use crate::maze::{
+    arengee::Arengee,
+    interface::Maze,
+    shape::regular::{Direction, RectilinearMaze},
+};
+
+use super::DisjointSets;
+
+/// Kruskal's algorithm with crossings placed before it starts. A crossing is
+/// a cell with a straight passage through it and a tunnel beneath that
+/// connects the cells on its other two sides. We try to place a crossing in
+/// `density` percent of the cells, and skip those where it would either
+/// touch another crossing or close a loop. Kruskal's algorithm then fills in
+/// the rest of the maze around the crossings.
+pub fn weave(mut maze: RectilinearMaze, rng: &mut Arengee, density: u8) -> RectilinearMaze {
+    let mut sets = DisjointSets::new(maze.get_index_bound());
+    let (width, height) = maze.get_extents();
+    let mut candidates = (1..height - 1)
+        .flat_map(|y| (1..width - 1).map(move |x| (x, y)))
+        .collect::<Vec<_>>();
+    rng.shuffle(&mut candidates);
+    let count = candidates.len() * density.min(100) as usize / 100;
+
+    for cell in candidates.into_iter().take(count) {
+        let over = if rng.u32(0..2) == 0 {
+            Direction::Left
+        } else {
+            Direction::Up
+        };
+        let under = if over == Direction::Left {
+            Direction::Up
+        } else {
+            Direction::Left
+        };
+        let side = |direction: Direction| maze.translate(cell, direction);
+        // masks may leave out cells around the crossing
+        let (Some(a), Some(b), Some(c), Some(d)) = (
+            side(over),
+            side(over.reciprocal()),
+            side(under),
+            side(under.reciprocal()),
+        ) else {
+            continue;
+        };
+        if !maze.is_enabled(cell) {
+            continue;
+        }
+        // crossings next to each other would need tunnels of their own
+        if [cell, a, b, c, d].iter().any(|n| maze.is_crossing(*n)) {
+            continue;
+        }
+        if !can_cross(&mut sets, &maze, cell, (a, b), (c, d)) {
+            continue;
+        }
+        maze.add_crossing(cell, over);
+        sets.union(maze.get_index(a), maze.get_index(cell));
+        sets.union(maze.get_index(cell), maze.get_index(b));
+        sets.union(maze.get_index(c), maze.get_index(d));
+    }
+
+    // every side of a crossing is already taken by either its passage or
+    // its tunnel
+    let mut edges = maze
+        .get_all_edges()
+        .into_iter()
+        .filter(|(a, b)| !maze.is_crossing(*a) && !maze.is_crossing(*b))
+        .collect::<Vec<_>>();
+    rng.shuffle(&mut edges);
+    for (a, b) in edges {
+        if sets.union(maze.get_index(a), maze.get_index(b)) {
+            maze.carve(a, b);
+        }
+    }
+
+    maze
+}
+
+/// Whether connecting `over` through `cell`, and `under` beneath it, keeps
+/// the maze free of loops.
+fn can_cross(
+    sets: &mut DisjointSets,
+    maze: &RectilinearMaze,
+    cell: (usize, usize),
+    over: ((usize, usize), (usize, usize)),
+    under: ((usize, usize), (usize, usize)),
+) -> bool {
+    let mut root = |node| sets.find(maze.get_index(node));
+    let passage = [root(over.0), root(cell), root(over.1)];
+    let (c, d) = (root(under.0), root(under.1));
+    if passage[0] == passage[1] || passage[1] == passage[2] || passage[0] == passage[2] {
+        return false;
+    }
+    // after carving the passage, its three cells are all in the same set
+    let merged = |r: usize| {
+        if passage.contains(&r) {
+            passage[1]
+        } else {
+            r
+        }
+    };
+    merged(c) != merged(d)
+}
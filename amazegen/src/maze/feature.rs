@@ -1,4 +1,5 @@
 use itertools::Itertools;
+use thiserror::Error;
 
 use crate::maze::interface::MazeRenderer;
 use crate::maze::paint::theta::RingMazeRenderer;
@@ -6,15 +7,30 @@ use crate::maze::paint::*;
 use crate::maze::shape::regular::RectilinearMaze;
 use crate::WebResponse;
 
-use super::algorithms::{jarník, kruskal};
+use super::algorithms::{
+    aldous_broder,
+    binary_tree::{binary_tree, sidewinder},
+    braid,
+    division::{blobby_division, recursive_division},
+    dungeon::dungeon,
+    eller::eller,
+    hunt_and_kill, jarník, kruskal, one_way, portals, prim, symmetrise, terrain,
+    weave::weave,
+    wilson, Bias, Frontier, Selection, Weights, MAX_PORTALS,
+};
 use super::arengee::Arengee;
-use super::interface::{Maze, Solution};
+use super::interface::{Axis, Maze, Puzzle, Solution, Symmetry};
 use super::paint::delta::DeltaMazeRenderer;
+use super::paint::layered::LayeredRenderer;
 use super::paint::rect::RectilinearRenderer;
 use super::paint::sigma::SigmaMazeRenderer;
+use super::picture::Picture;
 use super::shape::delta::DeltaMaze;
+use super::shape::layered::LayeredMaze;
+use super::shape::mask::{Mask, MaskError};
 use super::shape::sigma::SigmaMaze;
 use super::shape::theta::RingMaze;
+use super::shape::wrap::{Wrap, WrapError};
 
 const STAIN_A: &str = "FFDC80";
 const STAIN_B: &str = "B9327D";
@@ -26,6 +42,8 @@ pub enum Shape {
     Theta(usize),
     Sigma(usize),
     Delta(usize),
+    /// A stack of square mazes connected by stairs: size and number of levels.
+    Layered(usize, usize),
 }
 
 #[derive(Debug, Copy, Clone, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
@@ -69,14 +87,161 @@ impl From<Feature> for DrawingInstructions {
 #[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
 pub enum Algorithm {
     Kruskal,
-    GrowingTree,
+    GrowingTree(Selection),
+    /// Randomised Prim's algorithm, which creates lots of short dead ends.
+    Prim(Frontier),
+    /// Wilson's algorithm, which is unbiased. The parameter is the percentage
+    /// of cells that an Aldous–Broder random walk visits first, which speeds
+    /// things up without introducing a bias.
+    Wilson(u8),
+    /// Unbiased, like Wilson's, but very slow on big mazes.
+    AldousBroder,
+    /// Only works for rectilinear mazes.
+    Eller,
+    /// Adds walls to an open area instead of carving passages. Only works for
+    /// rectilinear mazes.
+    RecursiveDivision,
+    /// Like recursive division, but splits areas of any shape into blobs.
+    BlobbyDivision,
+    /// Long passages like GrowingTree's, but without keeping a stack.
+    HuntAndKill,
+    /// Biased towards the north-east. Only works for rectilinear mazes.
+    BinaryTree,
+    /// Biased towards the north. Only works for rectilinear mazes.
+    Sidewinder,
+    /// Kruskal's algorithm with passages that cross over each other. The
+    /// parameter is the percentage of cells it tries to place crossings in.
+    /// Only works for rectilinear mazes.
+    Weave(u8),
+    /// Kruskal's algorithm connecting open rooms without walls inside. The
+    /// parameter is the number of rooms it tries to place. Only works for
+    /// rectilinear mazes.
+    Dungeon(u8),
+}
+
+#[derive(Error, Debug)]
+pub enum ConfigurationError {
+    #[error("{0} can only generate rectilinear mazes")]
+    RectilinearOnly(Algorithm),
+    #[error("{0} can't generate masked mazes")]
+    Unmaskable(Algorithm),
+    #[error("only rectilinear, sigma and delta mazes can be masked")]
+    UnmaskableShape,
+    #[error("{0} can't generate symmetric mazes")]
+    Asymmetric(Algorithm),
+    #[error("this maze can't have {0} symmetry")]
+    UnsupportedSymmetry(Symmetry),
+    #[error("{0} can't generate mazes that wrap around")]
+    Unwrappable(Algorithm),
+    #[error("only rectilinear and sigma mazes can wrap around")]
+    UnwrappableShape,
+    #[error("layered mazes only have one start and finish")]
+    UnpairableShape,
+    #[error("a maze can't have more than {1} {0}")]
+    TooMany(&'static str, u8),
+    #[error(transparent)]
+    Mask(#[from] MaskError),
+    #[error(transparent)]
+    Wrap(#[from] WrapError),
 }
 
 impl Algorithm {
-    pub fn execute<M: Maze>(&self, maze: M, rng: &mut Arengee) -> M {
+    /// Run an algorithm that works for any shape. Algorithms that need to know
+    /// the shape are run through [`Generate`]. Only Kruskal and GrowingTree
+    /// honour the weights.
+    pub fn execute<M: Maze>(
+        &self,
+        maze: M,
+        rng: &mut Arengee,
+        weights: Weights,
+    ) -> Result<M, ConfigurationError> {
+        match self {
+            Algorithm::Kruskal => Ok(kruskal(maze, rng, weights)),
+            Algorithm::GrowingTree(selection) => Ok(jarník(maze, rng, selection, weights)),
+            Algorithm::Prim(frontier) => Ok(prim(maze, rng, *frontier)),
+            Algorithm::Wilson(aldous_broder) => Ok(wilson(maze, rng, *aldous_broder)),
+            Algorithm::AldousBroder => Ok(aldous_broder(maze, rng)),
+            Algorithm::BlobbyDivision => Ok(blobby_division(maze, rng)),
+            Algorithm::HuntAndKill => Ok(hunt_and_kill(maze, rng)),
+            Algorithm::Eller
+            | Algorithm::RecursiveDivision
+            | Algorithm::BinaryTree
+            | Algorithm::Sidewinder
+            | Algorithm::Weave(_)
+            | Algorithm::Dungeon(_) => Err(ConfigurationError::RectilinearOnly(self.clone())),
+        }
+    }
+}
+
+/// Mazes pick the algorithm to generate them with here, so that shapes can
+/// offer algorithms that only work for them.
+pub trait Generate: Maze + Sized {
+    fn generate(
+        self,
+        algorithm: &Algorithm,
+        rng: &mut Arengee,
+        weights: Weights,
+    ) -> Result<Self, ConfigurationError> {
+        algorithm.execute(self, rng, weights)
+    }
+}
+
+impl Generate for RectilinearMaze {
+    fn generate(
+        self,
+        algorithm: &Algorithm,
+        rng: &mut Arengee,
+        weights: Weights,
+    ) -> Result<Self, ConfigurationError> {
+        match algorithm {
+            Algorithm::Eller
+            | Algorithm::RecursiveDivision
+            | Algorithm::BinaryTree
+            | Algorithm::Sidewinder
+                if self.is_masked() =>
+            {
+                Err(ConfigurationError::Unmaskable(algorithm.clone()))
+            }
+            Algorithm::Eller => Ok(eller(self, rng)),
+            Algorithm::RecursiveDivision => Ok(recursive_division(self, rng)),
+            Algorithm::BinaryTree => Ok(binary_tree(self, rng)),
+            Algorithm::Sidewinder => Ok(sidewinder(self, rng)),
+            Algorithm::Weave(density) => Ok(weave(self, rng, *density)),
+            Algorithm::Dungeon(rooms) => Ok(dungeon(self, rng, *rooms)),
+            _ => algorithm.execute(self, rng, weights),
+        }
+    }
+}
+
+impl Generate for RingMaze {}
+impl Generate for SigmaMaze {}
+impl Generate for DeltaMaze {}
+impl Generate for LayeredMaze {}
+
+/// This is what goes into the location hash, so keep it free of `|`.
+/// GrowingTree with the default selection is just `GrowingTree`, so that
+/// links created before the selection was adjustable keep working.
+impl std::fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Algorithm::Kruskal => kruskal(maze, rng),
-            Algorithm::GrowingTree => jarník(maze, rng),
+            Algorithm::Kruskal => write!(f, "Kruskal"),
+            Algorithm::GrowingTree(selection) if *selection == Selection::default() => {
+                write!(f, "GrowingTree")
+            }
+            Algorithm::GrowingTree(selection) => write!(f, "GrowingTree[{}]", selection),
+            Algorithm::Prim(Frontier::Random) => write!(f, "Prim"),
+            Algorithm::Prim(Frontier::Weighted) => write!(f, "Prim[weighted]"),
+            Algorithm::Wilson(0) => write!(f, "Wilson"),
+            Algorithm::Wilson(aldous_broder) => write!(f, "Wilson[{}]", aldous_broder),
+            Algorithm::AldousBroder => write!(f, "AldousBroder"),
+            Algorithm::Eller => write!(f, "Eller"),
+            Algorithm::RecursiveDivision => write!(f, "RecursiveDivision"),
+            Algorithm::BlobbyDivision => write!(f, "BlobbyDivision"),
+            Algorithm::HuntAndKill => write!(f, "HuntAndKill"),
+            Algorithm::BinaryTree => write!(f, "BinaryTree"),
+            Algorithm::Sidewinder => write!(f, "Sidewinder"),
+            Algorithm::Weave(density) => write!(f, "Weave[{}]", density),
+            Algorithm::Dungeon(rooms) => write!(f, "Dungeon[{}]", rooms),
         }
     }
 }
@@ -89,6 +254,83 @@ pub struct Configuration {
     pub features: Vec<Feature>,
     pub algorithm: Algorithm,
     pub stroke_width: f64,
+    /// Percentage of dead ends to remove after generating the maze, which
+    /// adds loops. At 100, we get a braid maze.
+    #[serde(default)]
+    pub braid: u8,
+    /// Leaves out the cells of rectilinear, sigma and delta mazes that the
+    /// mask doesn't cover. Masks aren't part of the location hash.
+    #[serde(default)]
+    pub mask: Option<Mask>,
+    /// Makes Kruskal and GrowingTree prefer passages along one axis.
+    #[serde(default)]
+    pub bias: Option<Bias>,
+    /// A picture for Kruskal and GrowingTree to trace. Like masks, pictures
+    /// aren't part of the location hash.
+    #[serde(default)]
+    pub picture: Option<Picture>,
+    /// Percentage of passages to turn into one way passages, where that
+    /// doesn't cut off any part of the maze. Only passages on loops qualify,
+    /// so this needs `braid`.
+    #[serde(default)]
+    pub one_way: u8,
+    /// Percentage of cells to cover with mud and water, which cost more to
+    /// walk through. The solution is the cheapest way through the maze, and
+    /// the stain shows how much it costs to get to each cell.
+    #[serde(default)]
+    pub terrain: u8,
+    /// Number of pairs of portals that link cells far apart from each other.
+    /// The solution jumps through them where that's quicker.
+    #[serde(default)]
+    pub portals: u8,
+    /// Number of checkpoints the solution has to visit in order on its way
+    /// from the entrance to the exit.
+    #[serde(default)]
+    pub checkpoints: u8,
+    /// Number of locked doors on the way from the entrance to the exit. The
+    /// solution has to fetch the key to each of them first.
+    #[serde(default)]
+    pub locks: u8,
+    /// Make the maze look the same when it's mirrored or turned around its
+    /// centre. Only rectilinear and theta mazes can be symmetric.
+    #[serde(default)]
+    pub symmetry: Option<Symmetry>,
+    /// Let the edges of the maze lead around to the opposite edge. Only
+    /// rectilinear and sigma mazes can wrap around.
+    #[serde(default)]
+    pub wrap: Wrap,
+    /// Number of labelled start and finish pairs, A to A, B to B and so on,
+    /// connected by ways that don't share any cells. The entrance and exit
+    /// are the first pair, so fewer than two leave just those. Layered mazes
+    /// only have the one pair.
+    #[serde(default)]
+    pub pairs: u8,
+}
+
+/// A small square maze grown depth first, without any of the extras.
+impl Default for Configuration {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            shape: Shape::Rectilinear(10, 10),
+            colour: "000000".into(),
+            features: vec![],
+            algorithm: Algorithm::GrowingTree(Selection::default()),
+            stroke_width: 8.0,
+            braid: 0,
+            mask: None,
+            bias: None,
+            picture: None,
+            one_way: 0,
+            terrain: 0,
+            portals: 0,
+            checkpoints: 0,
+            locks: 0,
+            symmetry: None,
+            wrap: Wrap::default(),
+            pairs: 0,
+        }
+    }
 }
 
 pub struct Svg {
@@ -97,24 +339,67 @@ pub struct Svg {
 }
 
 impl Configuration {
-    pub fn execute_for_web(&self) -> WebResponse {
+    pub fn execute_for_web(&self) -> Result<WebResponse, ConfigurationError> {
         let mut rng = Arengee::new(self.seed);
-        WebResponse {
-            svg: self.display_maze(&mut rng).to_string(),
+        Ok(WebResponse {
+            svg: self.display_maze(&mut rng)?.to_string(),
             hash: self.get_location_hash(),
-        }
+        })
     }
 
-    pub fn execute_for_svg(&self) -> (RenderedMaze, u64) {
+    pub fn execute_for_svg(&self) -> Result<(RenderedMaze, u64), ConfigurationError> {
         let mut rng = Arengee::new(self.seed);
-        let rendered = self.display_maze(&mut rng);
-        (rendered, rng.get_current_seed())
+        let rendered = self.display_maze(&mut rng)?;
+        Ok((rendered, rng.get_current_seed()))
     }
 
-    fn create_maze<M: Maze>(&self, template: M, rng: &mut Arengee) -> (M, Solution<M::Idx>) {
-        let mut maze = self.algorithm.execute(template, rng);
-        let solution = maze.make_solution(rng);
-        (maze, solution)
+    fn create_maze<M: Generate>(
+        &self,
+        template: M,
+        rng: &mut Arengee,
+    ) -> Result<(M, Solution<M::Idx>), ConfigurationError> {
+        let weights = Weights {
+            bias: self.bias,
+            picture: self.picture.as_ref(),
+        };
+        if let Some(symmetry) = self.symmetry {
+            if matches!(self.algorithm, Algorithm::Weave(_) | Algorithm::Dungeon(_)) {
+                return Err(ConfigurationError::Asymmetric(self.algorithm.clone()));
+            }
+            let nodes = template.get_all_nodes();
+            if nodes
+                .iter()
+                .any(|node| template.get_symmetric(*node, symmetry).is_none())
+            {
+                return Err(ConfigurationError::UnsupportedSymmetry(symmetry));
+            }
+        }
+        let mut maze = template.generate(&self.algorithm, rng, weights)?;
+        if let Some(symmetry) = self.symmetry {
+            maze = symmetrise(maze, rng, symmetry);
+        }
+        if self.braid > 0 {
+            maze = braid(maze, rng, self.braid);
+        }
+        if self.one_way > 0 {
+            maze = one_way(maze, rng, self.one_way);
+        }
+        if self.terrain > 0 {
+            maze = terrain(maze, rng, self.terrain);
+        }
+        if self.portals > 0 {
+            maze = portals(maze, rng, self.portals);
+        }
+        let puzzle = Puzzle {
+            checkpoints: self.checkpoints,
+            locks: self.locks,
+            pairs: self.pairs,
+        };
+        let mut solution = maze.make_solution(rng, puzzle);
+        if let Some(picture) = self.picture.as_ref().filter(|p| p.stain) {
+            solution.distances = picture.shades(&maze);
+        }
+        Ok((maze, solution))
     }
 
     fn render<M: Maze, R: MazeRenderer<M>>(&self, mut renderer: R) -> RenderedMaze {
@@ -135,18 +420,99 @@ impl Configuration {
             Shape::Sigma(size) => format!("S{}", size),
             Shape::Theta(size) => format!("T{}", size),
             Shape::Delta(size) => format!("D{}", size),
+            Shape::Layered(size, levels) => format!("L{}x{}", size, levels),
         };
-        let algorithm = match self.algorithm {
-            Algorithm::Kruskal => "Kruskal",
-            Algorithm::GrowingTree => "GrowingTree",
-        };
-        format!("{}|{}|{}", shape, algorithm, self.seed)
+        let mut hash = format!("{}|{}|{}", shape, self.algorithm, self.seed);
+        // optional parts, so that older links keep working
+        if self.braid > 0 {
+            hash.push_str(&format!("|B{}", self.braid));
+        }
+        match self.bias {
+            Some(Bias {
+                axis: Axis::Horizontal,
+                strength,
+            }) => hash.push_str(&format!("|H{}", strength)),
+            Some(Bias {
+                axis: Axis::Vertical,
+                strength,
+            }) => hash.push_str(&format!("|V{}", strength)),
+            None => (),
+        }
+        if self.one_way > 0 {
+            hash.push_str(&format!("|O{}", self.one_way));
+        }
+        if self.terrain > 0 {
+            hash.push_str(&format!("|C{}", self.terrain));
+        }
+        if self.portals > 0 {
+            hash.push_str(&format!("|P{}", self.portals));
+        }
+        if self.checkpoints > 0 {
+            hash.push_str(&format!("|W{}", self.checkpoints));
+        }
+        if self.locks > 0 {
+            hash.push_str(&format!("|K{}", self.locks));
+        }
+        match self.symmetry {
+            Some(Symmetry::Mirror) => hash.push_str("|YM"),
+            Some(Symmetry::Rotation(k)) => hash.push_str(&format!("|Y{}", k)),
+            None => (),
+        }
+        if !self.wrap.is_flat() {
+            hash.push_str("|X");
+            for (letter, on) in [
+                ('H', self.wrap.horizontal),
+                ('V', self.wrap.vertical),
+                ('T', self.wrap.twist),
+            ] {
+                if on {
+                    hash.push(letter);
+                }
+            }
+        }
+        if self.pairs > 1 {
+            hash.push_str(&format!("|A{}", self.pairs));
+        }
+        hash
     }
 
-    fn display_maze(&self, rng: &mut Arengee) -> RenderedMaze {
-        match self.shape {
+    /// The puzzles get slow to solve, or crowd the maze, with too many of
+    /// their parts.
+    fn check_limits(&self) -> Result<(), ConfigurationError> {
+        for (what, count, max) in [
+            ("pairs of portals", self.portals, MAX_PORTALS),
+            ("checkpoints", self.checkpoints, Puzzle::MAX_CHECKPOINTS),
+            ("locks", self.locks, Puzzle::MAX_LOCKS),
+            ("start and finish pairs", self.pairs, Puzzle::MAX_PAIRS),
+        ] {
+            if count > max {
+                return Err(ConfigurationError::TooMany(what, max));
+            }
+        }
+        Ok(())
+    }
+
+    fn display_maze(&self, rng: &mut Arengee) -> Result<RenderedMaze, ConfigurationError> {
+        self.check_limits()?;
+        Ok(match self.shape {
             Shape::Rectilinear(x, y) => {
-                let (maze, solution) = self.create_maze(RectilinearMaze::new((x, y)), rng);
+                let mut template = RectilinearMaze::new((x, y));
+                if !self.wrap.is_flat() {
+                    if matches!(
+                        self.algorithm,
+                        Algorithm::Eller
+                            | Algorithm::RecursiveDivision
+                            | Algorithm::BinaryTree
+                            | Algorithm::Sidewinder
+                    ) {
+                        return Err(ConfigurationError::Unwrappable(self.algorithm.clone()));
+                    }
+                    template.set_wrap(self.wrap)?;
+                }
+                if let Some(mask) = &self.mask {
+                    template.apply_mask(mask)?;
+                }
+                let (maze, solution) = self.create_maze(template, rng)?;
                 self.render(RectilinearRenderer::new(
                     &maze,
                     &solution,
@@ -154,8 +520,17 @@ impl Configuration {
                     40,
                 ))
             }
+            Shape::Theta(_) | Shape::Delta(_) | Shape::Layered(_, _) if !self.wrap.is_flat() => {
+                return Err(ConfigurationError::UnwrappableShape)
+            }
+            Shape::Layered(_, _) if self.pairs > 1 => {
+                return Err(ConfigurationError::UnpairableShape)
+            }
+            Shape::Theta(_) | Shape::Layered(_, _) if self.mask.is_some() => {
+                return Err(ConfigurationError::UnmaskableShape)
+            }
             Shape::Theta(size) => {
-                let (maze, solution) = self.create_maze(RingMaze::new(size, 8), rng);
+                let (maze, solution) = self.create_maze(RingMaze::new(size, 8), rng)?;
                 self.render(RingMazeRenderer::new(
                     &maze,
                     &solution,
@@ -164,7 +539,14 @@ impl Configuration {
                 ))
             }
             Shape::Sigma(size) => {
-                let (maze, solution) = self.create_maze(SigmaMaze::new(size), rng);
+                let mut template = SigmaMaze::new(size);
+                if !self.wrap.is_flat() {
+                    template.set_wrap(self.wrap)?;
+                }
+                if let Some(mask) = &self.mask {
+                    template.apply_mask(mask)?;
+                }
+                let (maze, solution) = self.create_maze(template, rng)?;
                 self.render(SigmaMazeRenderer::new(
                     &maze,
                     &solution,
@@ -173,7 +555,11 @@ impl Configuration {
                 ))
             }
             Shape::Delta(size) => {
-                let (maze, solution) = self.create_maze(DeltaMaze::new(size as u32), rng);
+                let mut template = DeltaMaze::new(size as u32);
+                if let Some(mask) = &self.mask {
+                    template.apply_mask(mask)?;
+                }
+                let (maze, solution) = self.create_maze(template, rng)?;
                 self.render(DeltaMazeRenderer::new(
                     &maze,
                     &solution,
@@ -181,6 +567,16 @@ impl Configuration {
                     40.0,
                 ))
             }
-        }
+            Shape::Layered(size, levels) => {
+                let (maze, solution) =
+                    self.create_maze(LayeredMaze::new((size, size), levels), rng)?;
+                self.render(LayeredRenderer::new(
+                    &maze,
+                    &solution,
+                    self.stroke_width / 2.0,
+                    40,
+                ))
+            }
+        })
     }
 }
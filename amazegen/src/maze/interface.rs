@@ -1,20 +1,146 @@
+use std::hash::Hash;
+
 use super::{
     arengee::Arengee,
     paint::{RenderedMaze, WebColour},
+    shape::overlay::Overlay,
 };
 
 #[derive(Debug)]
 pub struct Solution<T> {
     pub path: Vec<T>,
     pub distances: Vec<usize>,
+    /// The cells the path has to visit on its way, in order.
+    pub checkpoints: Vec<T>,
+    /// Ways between further starts and finishes on the edge of the maze, from
+    /// start to finish. They share no cells with the path or each other.
+    /// Together with the entrance and exit, they're labelled A, B, C and so on.
+    pub pairs: Vec<Vec<T>>,
+}
+
+impl<T: PartialEq> Solution<T> {
+    /// Split the path at each checkpoint, so that every segment leads to the
+    /// next checkpoint, and the last one to the exit. Neighbouring segments
+    /// share the checkpoint between them.
+    pub fn segments(&self) -> Vec<&[T]> {
+        let mut segments = vec![];
+        let mut start = 0;
+        for checkpoint in self.checkpoints.iter() {
+            if let Some(end) = self.path[start..].iter().position(|n| n == checkpoint) {
+                segments.push(&self.path[start..=start + end]);
+                start += end;
+            }
+        }
+        segments.push(&self.path[start..]);
+        segments
+    }
+}
+
+/// What a solution has to do on its way from the entrance to the exit,
+/// besides finding its way through the maze.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Puzzle {
+    /// How many cells to visit in order, see `Solution::checkpoints`.
+    pub checkpoints: u8,
+    /// How many doors to lock, see `Lock`.
+    pub locks: u8,
+    /// How many starts and finishes to connect, counting the entrance and
+    /// exit, see `Solution::pairs`.
+    pub pairs: u8,
+}
+
+impl Puzzle {
+    pub const MAX_CHECKPOINTS: u8 = 20;
+    /// The solution keeps track of the keys it holds in a bit set, and has to
+    /// search the maze once for every combination of them.
+    pub const MAX_LOCKS: u8 = 8;
+    pub const MAX_PAIRS: u8 = 8;
+}
+
+/// A locked door in the passage between two cells, which only opens once
+/// its key has been picked up by walking into the cell it lies in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lock<T> {
+    pub key: T,
+    pub door: (T, T),
+}
+
+impl<T: PartialEq> Lock<T> {
+    /// Whether the door is in the passage between `a` and `b`.
+    pub fn blocks(&self, a: T, b: T) -> bool {
+        (self.door.0 == a && self.door.1 == b) || (self.door.0 == b && self.door.1 == a)
+    }
+}
+
+/// The direction a passage between two neighbouring cells runs in. For
+/// circular mazes, horizontal means around the centre, and vertical away from
+/// it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum Axis {
+    Horizontal,
+    Vertical,
+}
+
+/// How a maze can look the same after being mirrored or turned around its
+/// centre.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum Symmetry {
+    /// The right half is the mirror image of the left half.
+    Mirror,
+    /// The maze looks the same after a `k`th of a full turn, so 2 means
+    /// turning it upside down.
+    Rotation(u8),
+}
+
+impl std::fmt::Display for Symmetry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Symmetry::Mirror => write!(f, "mirror"),
+            Symmetry::Rotation(k) => write!(f, "{}-fold rotational", k),
+        }
+    }
+}
+
+/// What the ground in a cell is like. The rougher it is, the more it costs
+/// to walk through the cell, so the shortest way through the maze isn't
+/// necessarily the cheapest one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum Terrain {
+    #[default]
+    Ground,
+    Mud,
+    Water,
+}
+
+impl Terrain {
+    /// How much it costs to walk through a cell, compared to plain ground.
+    pub fn cost(&self) -> usize {
+        match self {
+            Terrain::Ground => 1,
+            Terrain::Mud => 3,
+            Terrain::Water => 6,
+        }
+    }
 }
 
 pub trait Maze {
-    type Idx: Eq + PartialEq + Copy + Clone;
+    type Idx: Eq + PartialEq + Copy + Clone + Hash;
 
     /// Connect two neighbouring cells. May panic if the cells aren't actually neighbours.
     fn carve(&mut self, node: Self::Idx, neighbour: Self::Idx);
 
+    /// Separate two neighbouring cells again, i.e. undo `carve`. May panic if
+    /// the cells aren't actually neighbours.
+    fn build_wall(&mut self, node: Self::Idx, neighbour: Self::Idx);
+
+    /// Connect all neighbouring cells, for algorithms that start out with an
+    /// open area and add walls using `build_wall`.
+    fn carve_all(&mut self) {
+        for (a, b) in self.get_all_edges() {
+            self.carve(a, b);
+        }
+    }
+
     /// Get the inaccessible neighbours of `node`, i.e. all neighbouring
     /// cells for which no connection has yet been carved using `carve`.
     fn get_walls(&self, node: Self::Idx) -> Vec<Self::Idx>;
@@ -23,6 +149,57 @@ pub trait Maze {
     /// cells for which a connection has been carved using `carve`.
     fn get_paths(&self, node: Self::Idx) -> Vec<Self::Idx>;
 
+    /// The one way passages, terrain, portals and locks laid over the maze,
+    /// see `Overlay`.
+    /// Carving or walling off a passage again makes it a regular passage.
+    fn overlay(&self) -> &Overlay<Self::Idx>;
+
+    fn overlay_mut(&mut self) -> &mut Overlay<Self::Idx>;
+
+    /// Get the cells that can be reached from `node` in one step, i.e. its
+    /// paths except for the one way passages that only lead into `node`, and
+    /// where its portal leads.
+    fn get_exits(&self, node: Self::Idx) -> Vec<Self::Idx> {
+        let mut paths = self.get_paths(node);
+        paths.retain(|n| !self.overlay().is_one_way(*n, node));
+        paths.extend(self.overlay().get_portal(node));
+        paths
+    }
+
+    /// Get the cells that `node` can be reached from in one step, i.e. its
+    /// paths except for the one way passages that only lead out of `node`,
+    /// and where its portal leads.
+    fn get_entries(&self, node: Self::Idx) -> Vec<Self::Idx> {
+        let mut paths = self.get_paths(node);
+        paths.retain(|n| !self.overlay().is_one_way(node, *n));
+        paths.extend(self.overlay().get_portal(node));
+        paths
+    }
+
+    /// Get all neighbours of `node`, no matter whether a connection has been
+    /// carved to them or not.
+    fn get_neighbours(&self, node: Self::Idx) -> Vec<Self::Idx> {
+        let mut neighbours = self.get_walls(node);
+        neighbours.extend(self.get_paths(node));
+        neighbours
+    }
+
+    /// Which way the passage between two neighbouring cells runs, if it's
+    /// clearly one or the other.
+    fn get_axis(&self, _node: Self::Idx, _neighbour: Self::Idx) -> Option<Axis> {
+        None
+    }
+
+    /// The cell that `node` ends up on when mirroring or turning the maze once
+    /// as in `symmetry`. `None` if the maze can't be symmetric like that.
+    fn get_symmetric(&self, _node: Self::Idx, _symmetry: Symmetry) -> Option<Self::Idx> {
+        None
+    }
+
+    /// Where the centre of a cell is when the maze gets drawn, as a fraction of
+    /// the maze's width and height.
+    fn get_position(&self, node: Self::Idx) -> (f64, f64);
+
     /// Get any random node inside the maze without constraints.
     fn get_random_node(&self, rng: &mut Arengee) -> Self::Idx;
 
@@ -37,10 +214,17 @@ pub trait Maze {
     /// to save a maze in a one-dimensional data structure, such as a `Vec`.
     fn get_index(&self, node: Self::Idx) -> usize;
 
-    /// Trace a path through a maze. Takes an `Arengee` because it needs
-    /// to find a start, or could possibly try to find a random solution
-    /// if there were more than one.
-    fn make_solution(&mut self, rng: &mut Arengee) -> Solution<Self::Idx>;
+    /// The size of a `Vec` that can hold every index from `get_index`. That's
+    /// the number of cells, unless some of them have been masked out.
+    fn get_index_bound(&self) -> usize {
+        self.get_all_nodes().len()
+    }
+
+    /// Trace a path through a maze, from the entrance to the exit. Takes an
+    /// `Arengee` because it needs to find a start, or could possibly try to
+    /// find a random solution if there were more than one. The path solves
+    /// the `puzzle` on its way, see `solve_puzzle`.
+    fn make_solution(&mut self, rng: &mut Arengee, puzzle: Puzzle) -> Solution<Self::Idx>;
 }
 
 pub trait MazeRenderer<M: Maze> {
@@ -1,4 +1,5 @@
 pub mod delta;
+pub mod layered;
 pub mod rect;
 pub mod sigma;
 pub mod theta;
@@ -6,7 +7,12 @@ pub mod theta;
 use std::cmp::max;
 use std::fmt::Display;
 
-use super::interface::{Maze, MazeRenderer, Solution};
+use super::interface::{Maze, MazeRenderer, Solution, Terrain};
+use ::svg::node::element::{
+    path::{Command, Data, Position::Absolute},
+    Circle, Definitions, Group, Path, Pattern, Text,
+};
+use ::svg::{Document, Node};
 
 use thiserror::Error;
 
@@ -73,6 +79,37 @@ impl WebColour {
             a: max(self.a, other.a),
         }
     }
+
+    /// Turn the colour's hue by `degrees` around the colour wheel, keeping its
+    /// saturation and brightness.
+    pub fn rotate_hue(&self, degrees: f64) -> Self {
+        let channels = [self.r, self.g, self.b].map(|c| c as f64 / 255.0);
+        let [r, g, b] = channels;
+        let max = channels.iter().copied().fold(0.0, f64::max);
+        let min = channels.iter().copied().fold(1.0, f64::min);
+        let chroma = max - min;
+        let hue = if chroma == 0.0 {
+            0.0
+        } else if max == r {
+            60.0 * ((g - b) / chroma).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * ((b - r) / chroma + 2.0)
+        } else {
+            60.0 * ((r - g) / chroma + 4.0)
+        };
+        let hue = (hue + degrees).rem_euclid(360.0);
+        let channel = |n: f64| {
+            let k = (n + hue / 60.0) % 6.0;
+            let c = max - chroma * k.min(4.0 - k).clamp(0.0, 1.0);
+            (c * 255.0).round() as u8
+        };
+        WebColour {
+            r: channel(5.0),
+            g: channel(3.0),
+            b: channel(1.0),
+            a: self.a,
+        }
+    }
 }
 
 pub struct CellSize(usize);
@@ -153,3 +190,508 @@ impl Display for RenderedMaze {
 pub fn midpoint(a: (f64, f64), b: (f64, f64)) -> (f64, f64) {
     ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0)
 }
+
+/// All one way passages of `maze`, from the cell they may be entered from to
+/// the cell they lead to.
+fn one_way_passages<M: Maze>(maze: &M) -> Vec<(M::Idx, M::Idx)> {
+    let mut passages = vec![];
+    for node in maze.get_all_nodes() {
+        for neighbour in maze.get_paths(node) {
+            if maze.overlay().is_one_way(node, neighbour) {
+                passages.push((node, neighbour));
+            }
+        }
+    }
+    passages
+}
+
+/// Fill in the arrows from `arrow`, if there are any, so that mazes without
+/// one way passages stay the same.
+fn fill_arrows(document: &mut Document, arrows: Data, colour: WebColour) {
+    if !arrows.is_empty() {
+        document.append(
+            Path::new()
+                .set("fill", colour.to_web_string())
+                .set("stroke", "none")
+                .set("d", arrows),
+        );
+    }
+}
+
+/// An arrow head of length `size` centred on `at`, that points in
+/// `direction`. It marks a one way passage, so it gets filled rather than
+/// stroked.
+fn arrow(data: &mut Data, at: (f64, f64), direction: (f64, f64), size: f64) {
+    let length = direction.0.hypot(direction.1);
+    let (dx, dy) = (
+        direction.0 / length * size / 2.0,
+        direction.1 / length * size / 2.0,
+    );
+    data.append(Command::Move(Absolute, (at.0 + dx, at.1 + dy).into()));
+    data.append(Command::Line(
+        Absolute,
+        (at.0 - dx - dy, at.1 - dy + dx).into(),
+    ));
+    data.append(Command::Line(
+        Absolute,
+        (at.0 - dx + dy, at.1 - dy - dx).into(),
+    ));
+    data.append(Command::Close);
+}
+
+/// Cover the cells with rough terrain in fill patterns of `colour`: mud gets
+/// dots, water gets waves. `size` is about the width of a cell, and `outline`
+/// traces a cell's outline. `None` if there's only plain ground.
+fn paint_terrain<M: Maze>(
+    maze: &M,
+    colour: WebColour,
+    size: f64,
+    outline: impl Fn(&mut Data, M::Idx),
+) -> Option<Group> {
+    let mut mud = Data::new();
+    let mut water = Data::new();
+    for node in maze.get_all_nodes() {
+        match maze.overlay().get_terrain(node) {
+            Terrain::Ground => (),
+            Terrain::Mud => outline(&mut mud, node),
+            Terrain::Water => outline(&mut water, node),
+        }
+    }
+    if mud.is_empty() && water.is_empty() {
+        return None;
+    }
+
+    let d = size / 4.0;
+    let colour = colour.to_web_string();
+    let pattern = |id: &str, width: f64| {
+        Pattern::new()
+            .set("id", id)
+            .set("width", width)
+            .set("height", d)
+            .set("patternUnits", "userSpaceOnUse")
+    };
+    let dot = Circle::new()
+        .set("cx", d / 2.0)
+        .set("cy", d / 2.0)
+        .set("r", d / 6.0)
+        .set("fill", colour.clone());
+    let wave = Path::new()
+        .set("fill", "none")
+        .set("stroke", colour)
+        .set("stroke-width", d / 8.0)
+        .set(
+            "d",
+            Data::new()
+                .move_to((0.0, d / 2.0))
+                .quadratic_curve_to((d / 2.0, 0.0, d, d / 2.0))
+                .smooth_quadratic_curve_to((2.0 * d, d / 2.0)),
+        );
+    let mut group = Group::new().add(
+        Definitions::new()
+            .add(pattern("mud", d).add(dot))
+            .add(pattern("water", 2.0 * d).add(wave)),
+    );
+    for (id, data) in [("mud", mud), ("water", water)] {
+        if !data.is_empty() {
+            group.append(
+                Path::new()
+                    .set("fill", format!("url(#{})", id))
+                    .set("stroke", "none")
+                    .set("opacity", 0.5)
+                    .set("d", data),
+            );
+        }
+    }
+    Some(group)
+}
+
+/// Whether a step along the solution jumps through a portal.
+fn is_jump<M: Maze>(maze: &M, from: M::Idx, to: M::Idx) -> bool {
+    maze.overlay().get_portal(from) == Some(to)
+}
+
+/// Mark both ends of each pair of portals with the same symbol, centred on
+/// the cell. `size` is about the width of a cell. There are twelve different
+/// symbols, so more portals than that start repeating them.
+fn paint_portals<M: Maze>(
+    maze: &M,
+    colour: WebColour,
+    size: f64,
+    centre: impl Fn(M::Idx) -> (f64, f64),
+) -> Option<Group> {
+    let portals = maze.overlay().get_portals();
+    if portals.is_empty() {
+        return None;
+    }
+    let mut filled = Data::new();
+    let mut outlined = Data::new();
+    for (i, (a, b)) in portals.iter().enumerate() {
+        let data = if (i / 6) % 2 == 0 {
+            &mut filled
+        } else {
+            &mut outlined
+        };
+        for node in [*a, *b] {
+            symbol(data, i % 6, centre(node), size / 4.0);
+        }
+    }
+    let colour = colour.to_web_string();
+    let mut group = Group::new().add(
+        Path::new()
+            .set("fill", colour.clone())
+            .set("stroke", "none")
+            .set("d", filled),
+    );
+    if portals.len() > 6 {
+        group.append(
+            Path::new()
+                .set("fill", "none")
+                .set("stroke", colour)
+                .set("stroke-width", size / 20.0)
+                .set("stroke-linejoin", "round")
+                .set("d", outlined),
+        );
+    }
+    Some(group)
+}
+
+/// One of six simple shapes that fit into a circle of radius `r` around
+/// `(x, y)`: a circle, a square, a triangle, a diamond, a star and a cross.
+fn symbol(data: &mut Data, kind: usize, (x, y): (f64, f64), r: f64) {
+    let polygon = |data: &mut Data, points: &[(f64, f64)]| {
+        data.append(Command::Move(
+            Absolute,
+            (x + points[0].0 * r, y + points[0].1 * r).into(),
+        ));
+        for (px, py) in &points[1..] {
+            data.append(Command::Line(Absolute, (x + px * r, y + py * r).into()));
+        }
+        data.append(Command::Close);
+    };
+    // corners around the centre, starting at the top
+    let star = |corners: usize, inner: f64| {
+        (0..2 * corners)
+            .map(|i| {
+                let θ = std::f64::consts::PI * (i as f64 / corners as f64 - 0.5);
+                let radius = if i % 2 == 0 { 1.0 } else { inner };
+                (radius * θ.cos(), radius * θ.sin())
+            })
+            .collect::<Vec<_>>()
+    };
+    match kind {
+        0 => {
+            data.append(Command::Move(Absolute, (x - r, y).into()));
+            for end in [x + r, x - r] {
+                data.append(Command::EllipticalArc(
+                    Absolute,
+                    (r, r, 0, 0, 0, end, y).into(),
+                ));
+            }
+            data.append(Command::Close);
+        }
+        1 => polygon(data, &[(-0.8, -0.8), (0.8, -0.8), (0.8, 0.8), (-0.8, 0.8)]),
+        2 => polygon(data, &[(0.0, -1.0), (0.87, 0.5), (-0.87, 0.5)]),
+        3 => polygon(data, &[(0.0, -1.0), (1.0, 0.0), (0.0, 1.0), (-1.0, 0.0)]),
+        4 => polygon(data, &star(5, 0.45)),
+        _ => polygon(
+            data,
+            &[
+                (-0.3, -1.0),
+                (0.3, -1.0),
+                (0.3, -0.3),
+                (1.0, -0.3),
+                (1.0, 0.3),
+                (0.3, 0.3),
+                (0.3, 1.0),
+                (-0.3, 1.0),
+                (-0.3, 0.3),
+                (-1.0, 0.3),
+                (-1.0, -0.3),
+                (-0.3, -0.3),
+            ],
+        ),
+    }
+}
+
+/// The jumps the solution takes through portals, as dashed arcs from one
+/// portal's centre to the other's. `None` if it doesn't take any.
+fn portal_leaps<M: Maze>(
+    maze: &M,
+    path: &[M::Idx],
+    colour: WebColour,
+    stroke_width: f64,
+    centre: impl Fn(M::Idx) -> (f64, f64),
+) -> Option<Path> {
+    let mut data = Data::new();
+    for step in path.windows(2) {
+        if is_jump(maze, step[0], step[1]) {
+            let (from, to) = (centre(step[0]), centre(step[1]));
+            // bulge out to the side by a quarter of the distance
+            let (dx, dy) = ((to.0 - from.0) / 4.0, (to.1 - from.1) / 4.0);
+            let (mx, my) = midpoint(from, to);
+            data.append(Command::Move(Absolute, from.into()));
+            data.append(Command::QuadraticCurve(
+                Absolute,
+                (mx + dy, my - dx, to.0, to.1).into(),
+            ));
+        }
+    }
+    (!data.is_empty()).then(|| {
+        Path::new()
+            .set("fill", "none")
+            .set("stroke", colour.to_web_string())
+            .set("stroke-width", stroke_width)
+            .set("stroke-linecap", "round")
+            .set(
+                "stroke-dasharray",
+                format!("{} {}", stroke_width, stroke_width * 2.0),
+            )
+            .set("d", data)
+    })
+}
+
+/// The colour of the `i`th of `count` segments of a solution that visits
+/// checkpoints. The first one keeps the solution's colour, the others are
+/// spread around the colour wheel.
+fn segment_colour(colour: WebColour, i: usize, count: usize) -> WebColour {
+    if i == 0 {
+        colour
+    } else {
+        colour.rotate_hue(360.0 * i as f64 / count as f64)
+    }
+}
+
+/// Ring each checkpoint and number it in the order the solution visits them.
+/// `size` is about the width of a cell.
+fn paint_checkpoints<T: Copy>(
+    checkpoints: &[T],
+    colour: WebColour,
+    size: f64,
+    centre: impl Fn(T) -> (f64, f64),
+) -> Option<Group> {
+    if checkpoints.is_empty() {
+        return None;
+    }
+    let colour = colour.to_web_string();
+    let mut rings = Data::new();
+    let mut numbers = Group::new()
+        .set("fill", colour.clone())
+        .set("font-family", "sans-serif")
+        .set("font-weight", "bold")
+        .set("font-size", size / 2.5)
+        .set("text-anchor", "middle")
+        .set("dominant-baseline", "central");
+    let r = size * 0.35;
+    for (i, checkpoint) in checkpoints.iter().enumerate() {
+        let (x, y) = centre(*checkpoint);
+        rings.append(Command::Move(Absolute, (x - r, y).into()));
+        for end in [x + r, x - r] {
+            rings.append(Command::EllipticalArc(
+                Absolute,
+                (r, r, 0, 0, 1, end, y).into(),
+            ));
+        }
+        numbers.append(Text::new((i + 1).to_string()).set("x", x).set("y", y));
+    }
+    Some(
+        Group::new()
+            .add(
+                Path::new()
+                    .set("fill", "none")
+                    .set("stroke", colour)
+                    .set("stroke-width", size / 20.0)
+                    .set("d", rings),
+            )
+            .add(numbers),
+    )
+}
+
+/// Put a coloured plate with a letter on each locked door, and a key with the
+/// same colour and letter on the cell that holds its key. Doors between cells
+/// that aren't drawn next to each other, like stairs, get a plate on both
+/// cells. `size` is about the width of a cell.
+fn paint_locks<M: Maze>(
+    maze: &M,
+    size: f64,
+    centre: impl Fn(M::Idx) -> (f64, f64),
+) -> Option<Group> {
+    let locks = maze.overlay().get_locks();
+    if locks.is_empty() {
+        return None;
+    }
+    let red = WebColour {
+        r: 0xcc,
+        g: 0x22,
+        b: 0x22,
+        a: 0xff,
+    };
+    let mut group = Group::new();
+    let mut letters = Group::new()
+        .set("fill", "white")
+        .set("font-family", "sans-serif")
+        .set("font-weight", "bold")
+        .set("font-size", size / 4.0)
+        .set("text-anchor", "middle")
+        .set("dominant-baseline", "central");
+    let half = size / 5.0;
+    for (i, lock) in locks.iter().enumerate() {
+        let letter = ((b'A' + i as u8) as char).to_string();
+        let (a, b) = (centre(lock.door.0), centre(lock.door.1));
+        let plates = if (a.0 - b.0).hypot(a.1 - b.1) > 1.5 * size {
+            vec![a, b]
+        } else {
+            vec![midpoint(a, b)]
+        };
+        let mut data = Data::new();
+        for (x, y) in plates {
+            data.append(Command::Move(Absolute, (x - half, y - half).into()));
+            data.append(Command::HorizontalLine(Absolute, (x + half).into()));
+            data.append(Command::VerticalLine(Absolute, (y + half).into()));
+            data.append(Command::HorizontalLine(Absolute, (x - half).into()));
+            data.append(Command::Close);
+            letters.append(Text::new(letter.clone()).set("x", x).set("y", y));
+        }
+        // the bow of the key holds the letter, and its bit points right
+        let (x, y) = centre(lock.key);
+        let (bow, r) = (x - size / 8.0, size / 6.0);
+        data.append(Command::Move(Absolute, (bow - r, y).into()));
+        for end in [bow + r, bow - r] {
+            data.append(Command::EllipticalArc(
+                Absolute,
+                (r, r, 0, 0, 1, end, y).into(),
+            ));
+        }
+        let (shaft, tip) = (size / 24.0, x + size * 0.35);
+        data.append(Command::Move(Absolute, (bow + r * 0.9, y - shaft).into()));
+        data.append(Command::HorizontalLine(Absolute, tip.into()));
+        data.append(Command::VerticalLine(Absolute, (y + size / 6.0).into()));
+        data.append(Command::HorizontalLine(
+            Absolute,
+            (tip - size / 12.0).into(),
+        ));
+        data.append(Command::VerticalLine(Absolute, (y + shaft).into()));
+        data.append(Command::HorizontalLine(Absolute, (bow + r * 0.9).into()));
+        data.append(Command::Close);
+        letters.append(Text::new(letter).set("x", bow).set("y", y));
+        let colour = red.rotate_hue(360.0 * i as f64 / locks.len() as f64);
+        group.append(
+            Path::new()
+                .set("fill", colour.to_web_string())
+                .set("stroke", "none")
+                .set("d", data),
+        );
+    }
+    Some(group.add(letters))
+}
+
+/// Both ends of a passage that wraps around the edges of a maze, or of a way
+/// between a start and finish on them. Each end is the midpoint of the side on
+/// the edge and the centre of the cell it belongs to.
+type Ends = [((f64, f64), (f64, f64)); 2];
+
+/// Number the passages that wrap around the edges of the maze, with the same
+/// number on a badge at both ends, just inside the cells. `size` is about the
+/// width of a cell.
+fn paint_wraps(passages: &[Ends], colour: WebColour, size: f64) -> Option<Group> {
+    if passages.is_empty() {
+        return None;
+    }
+    let mut badges = Group::new().set("fill", colour.to_web_string());
+    let mut numbers = Group::new()
+        .set("fill", "white")
+        .set("font-family", "sans-serif")
+        .set("font-weight", "bold")
+        .set("font-size", size / 5.0)
+        .set("text-anchor", "middle")
+        .set("dominant-baseline", "central");
+    let r = size / 7.0;
+    for (i, ends) in passages.iter().enumerate() {
+        for ((sx, sy), (cx, cy)) in ends {
+            let inset = (r * 1.2 / (cx - sx).hypot(cy - sy)).min(1.0);
+            let (x, y) = (sx + (cx - sx) * inset, sy + (cy - sy) * inset);
+            badges.append(Circle::new().set("cx", x).set("cy", y).set("r", r));
+            numbers.append(Text::new((i + 1).to_string()).set("x", x).set("y", y));
+        }
+    }
+    Some(Group::new().add(badges).add(numbers))
+}
+
+/// Mark the cells the solution starts and finishes in with an S and an F, for
+/// mazes that can't be entered and left through their edges. `size` is about
+/// the width of a cell.
+fn paint_ends(start: (f64, f64), finish: (f64, f64), colour: WebColour, size: f64) -> Group {
+    let mut group = Group::new()
+        .set("fill", colour.to_web_string())
+        .set("font-family", "sans-serif")
+        .set("font-weight", "bold")
+        .set("font-size", size / 2.0)
+        .set("text-anchor", "middle")
+        .set("dominant-baseline", "central");
+    for (letter, (x, y)) in [("S", start), ("F", finish)] {
+        group.append(Text::new(letter).set("x", x).set("y", y));
+    }
+    group
+}
+
+/// The colour of the `i`th of `count` start and finish pairs, spread around
+/// the colour wheel from a blue that doesn't look like a lock.
+fn pair_colour(i: usize, count: usize) -> WebColour {
+    let blue = WebColour {
+        r: 0x22,
+        g: 0x66,
+        b: 0xcc,
+        a: 0xff,
+    };
+    blue.rotate_hue(360.0 * i as f64 / count as f64)
+}
+
+/// The colour to draw the way between the `i`th start and finish pair of
+/// `solution` in, where the entrance and exit are the first pair. Without
+/// further pairs, that's the solution's own colour.
+fn way_colour<T>(solution: &Solution<T>, colour: WebColour, i: usize) -> WebColour {
+    if solution.pairs.is_empty() {
+        colour
+    } else {
+        pair_colour(i, solution.pairs.len() + 1)
+    }
+}
+
+/// Put the same letter just inside both ends of each start and finish pair,
+/// beside where the way between them comes in: A for the entrance and exit, B
+/// for the next pair and so on, in the pair's colour. A pair is `None` if its
+/// ends aren't on the edge of the maze. Only labels anything if there's more
+/// than one pair. `size` is about the width of a cell.
+fn paint_pairs(pairs: &[Option<Ends>], size: f64) -> Option<Group> {
+    if pairs.len() < 2 {
+        return None;
+    }
+    let mut group = Group::new()
+        .set("font-family", "sans-serif")
+        .set("font-weight", "bold")
+        .set("font-size", size / 2.5)
+        .set("text-anchor", "middle")
+        .set("dominant-baseline", "central");
+    for (i, ends) in pairs.iter().enumerate() {
+        let Some(ends) = ends else {
+            continue;
+        };
+        let letter = ((b'A' + i as u8) as char).to_string();
+        let colour = pair_colour(i, pairs.len()).to_web_string();
+        for ((sx, sy), (cx, cy)) in ends {
+            // inside the cell, beside the way coming in
+            let length = (cx - sx).hypot(cy - sy).max(f64::EPSILON);
+            let (ux, uy) = ((cx - sx) / length, (cy - sy) / length);
+            let (x, y) = (
+                sx + (ux * 0.3 - uy * 0.25) * size,
+                sy + (uy * 0.3 + ux * 0.25) * size,
+            );
+            group.append(
+                Text::new(letter.clone())
+                    .set("x", x)
+                    .set("y", y)
+                    .set("fill", colour.clone()),
+            );
+        }
+    }
+    Some(group)
+}
@@ -15,7 +15,11 @@ use crate::maze::{
     },
 };
 
-use super::{midpoint, RenderedMaze};
+use super::{
+    arrow, fill_arrows, is_jump, midpoint, one_way_passages, paint_checkpoints, paint_locks,
+    paint_pairs, paint_portals, paint_terrain, portal_leaps, segment_colour, way_colour, Ends,
+    RenderedMaze, WebColour,
+};
 
 pub struct DeltaMazeRenderer<'a> {
     maze: &'a DeltaMaze,
@@ -58,38 +62,40 @@ impl MazeRenderer<DeltaMaze> for DeltaMazeRenderer<'_> {
     }
 
     fn solve(&mut self, stroke_colour: super::WebColour) {
-        let mut data = Data::new();
-        let entrance = {
-            let (x, _) = self.compute_centre(&self.solution.path[0]);
-            (x, 0)
-        };
-        let exit = {
-            let (x, y) = self.compute_centre(self.solution.path.last().unwrap());
-            (x, y + (self.cell_height / 2.0))
-        };
-        data.append(Command::Move(Position::Absolute, entrance.into()));
-        for i in 1..self.solution.path.len() - 2 {
-            let prev = self.solution.path[i - 1];
-            let cur = self.solution.path[i];
-            let next = self.solution.path[i + 1];
-
-            let inbound = midpoint(self.compute_centre(&prev), self.compute_centre(&cur));
-            let outbound = midpoint(self.compute_centre(&cur), self.compute_centre(&next));
-            data.append(Command::Line(Position::Absolute, inbound.into()));
-            data.append(Command::Line(Position::Absolute, outbound.into()));
+        let segments = self.solution.segments();
+        let main_colour = way_colour(self.solution, stroke_colour, 0);
+        for (i, segment) in segments.iter().enumerate() {
+            let colour = segment_colour(main_colour, i, segments.len());
+            // in at the top, and out at the bottom
+            let start = (i == 0).then(|| {
+                let (x, _) = self.compute_centre(&segment[0]);
+                (x, segment[0].y() as f64 * self.cell_height)
+            });
+            let finish = (i == segments.len() - 1).then(|| {
+                let (x, y) = self.compute_centre(segment.last().unwrap());
+                (x, y + (self.cell_height / 2.0))
+            });
+            self.trace(segment, colour, start, finish);
+        }
+        for (i, way) in self.solution.pairs.iter().enumerate() {
+            let colour = way_colour(self.solution, stroke_colour, i + 1);
+            let [start, finish] = [way[0], way[way.len() - 1]]
+                .map(|cell| self.maze.opening(cell).map(|d| self.side(&cell, d)));
+            self.trace(way, colour, start, finish);
         }
-        data.append(Command::Line(Position::Absolute, exit.into()));
-        let path = Path::new()
-            .set("fill", "none")
-            .set("stroke", stroke_colour.to_web_string())
-            .set("stroke-width", self.stroke_width)
-            .set("stroke-linecap", "round")
-            .set("stroke-linejoin", "round")
-            .set("d", data);
-        self.document.append(path);
     }
 
     fn paint(&mut self, border: super::WebColour) {
+        if let Some(terrain) = paint_terrain(self.maze, border, self.edge_length, |data, cell| {
+            let Geometry { start, movements } = self.get_geometry(&cell);
+            data.append(Command::Move(Position::Absolute, start.into()));
+            for (_, x, y) in movements.into_iter().take(2) {
+                data.append(Command::Line(Position::Absolute, (x, y).into()));
+            }
+            data.append(Command::Close);
+        }) {
+            self.document.append(terrain);
+        }
         let mut data = Data::new();
         self.maze
             .get_all_nodes()
@@ -103,6 +109,40 @@ impl MazeRenderer<DeltaMaze> for DeltaMazeRenderer<'_> {
             .set("stroke-linejoin", "round")
             .set("d", data);
         self.document.append(path);
+
+        let mut arrows = Data::new();
+        for (node, neighbour) in one_way_passages(self.maze) {
+            let (from, to) = (self.compute_centre(&node), self.compute_centre(&neighbour));
+            let direction = (to.0 - from.0, to.1 - from.1);
+            arrow(
+                &mut arrows,
+                midpoint(from, to),
+                direction,
+                self.cell_height / 3.0,
+            );
+        }
+        fill_arrows(&mut self.document, arrows, border);
+        if let Some(portals) = paint_portals(self.maze, border, self.edge_length, |cell| {
+            self.compute_centre(&cell)
+        }) {
+            self.document.append(portals);
+        }
+        if let Some(checkpoints) = paint_checkpoints(
+            &self.solution.checkpoints,
+            border,
+            self.edge_length,
+            |cell| self.compute_centre(&cell),
+        ) {
+            self.document.append(checkpoints);
+        }
+        if let Some(locks) = paint_locks(self.maze, self.edge_length, |cell| {
+            self.compute_centre(&cell)
+        }) {
+            self.document.append(locks);
+        }
+        if let Some(pairs) = paint_pairs(&self.pairs(), self.edge_length) {
+            self.document.append(pairs);
+        }
     }
 
     fn render(self) -> super::RenderedMaze {
@@ -127,11 +167,101 @@ impl<'a> DeltaMazeRenderer<'a> {
             solution,
             stroke_width,
             edge_length,
-            cell_height: (3.0 as f64).sqrt() / 2.0 * edge_length,
+            cell_height: 3.0_f64.sqrt() / 2.0 * edge_length,
             document: Document::new(),
         }
     }
 
+    /// Both ends of every start and finish pair, see `paint_pairs`.
+    fn pairs(&self) -> Vec<Option<Ends>> {
+        [&self.solution.path[..]]
+            .into_iter()
+            .chain(self.solution.pairs.iter().map(|way| &way[..]))
+            .map(|way| {
+                let (start, finish) = (way[0], way[way.len() - 1]);
+                let (a, b) = (self.maze.opening(start)?, self.maze.opening(finish)?);
+                Some([
+                    (self.side(&start, a), self.compute_centre(&start)),
+                    (self.side(&finish, b), self.compute_centre(&finish)),
+                ])
+            })
+            .collect()
+    }
+
+    /// The midpoint of the side of `cell` in `direction`.
+    fn side(&self, cell: &Cartesian<u32>, direction: Direction) -> (f64, f64) {
+        let Geometry { start, movements } = self.get_geometry(cell);
+        let mut from = start;
+        for (d, x, y) in movements {
+            if d == direction {
+                return midpoint(from, (x, y));
+            }
+            from = (x, y);
+        }
+        self.compute_centre(cell)
+    }
+
+    /// Draw part of a way through the maze, coming in from `start` on the
+    /// edge of the maze and going out again to `finish`, if they're given.
+    fn trace(
+        &mut self,
+        path: &[Cartesian<u32>],
+        colour: WebColour,
+        start: Option<(f64, f64)>,
+        finish: Option<(f64, f64)>,
+    ) {
+        let mut data = Data::new();
+        // a way out ends on its finish twice
+        let cells = if finish.is_some() {
+            &path[..path.len() - 1]
+        } else {
+            path
+        };
+        let entrance = start.unwrap_or_else(|| self.compute_centre(&cells[0]));
+        let exit = finish.unwrap_or_else(|| self.compute_centre(cells.last().unwrap()));
+        data.append(Command::Move(Position::Absolute, entrance.into()));
+        for i in 1..cells.len() - 1 {
+            let prev = cells[i - 1];
+            let cur = cells[i];
+            let next = cells[i + 1];
+
+            // portal jumps go from one cell's centre to the other's
+            if is_jump(self.maze, prev, cur) {
+                let centre = self.compute_centre(&prev);
+                data.append(Command::Line(Position::Absolute, centre.into()));
+                let centre = self.compute_centre(&cur);
+                data.append(Command::Move(Position::Absolute, centre.into()));
+            } else {
+                let inbound = midpoint(self.compute_centre(&prev), self.compute_centre(&cur));
+                data.append(Command::Line(Position::Absolute, inbound.into()));
+            }
+            let outbound = if is_jump(self.maze, cur, next) {
+                self.compute_centre(&cur)
+            } else {
+                midpoint(self.compute_centre(&cur), self.compute_centre(&next))
+            };
+            data.append(Command::Line(Position::Absolute, outbound.into()));
+        }
+        if cells.len() > 1 && is_jump(self.maze, cells[cells.len() - 2], cells[cells.len() - 1]) {
+            let centre = self.compute_centre(&cells[cells.len() - 1]);
+            data.append(Command::Move(Position::Absolute, centre.into()));
+        }
+        data.append(Command::Line(Position::Absolute, exit.into()));
+        let path_element = Path::new()
+            .set("fill", "none")
+            .set("stroke", colour.to_web_string())
+            .set("stroke-width", self.stroke_width)
+            .set("stroke-linecap", "round")
+            .set("stroke-linejoin", "round")
+            .set("d", data);
+        self.document.append(path_element);
+        if let Some(leaps) = portal_leaps(self.maze, path, colour, self.stroke_width, |cell| {
+            self.compute_centre(&cell)
+        }) {
+            self.document.append(leaps);
+        }
+    }
+
     fn render_cell(&self, data: &mut Data, cell: Cartesian<u32>) {
         let Geometry { start, movements } = self.get_geometry(&cell);
         data.append(Command::Move(Position::Absolute, start.into()));
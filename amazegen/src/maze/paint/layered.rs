@@ -0,0 +1,227 @@
+use svg::{
+    node::element::{
+        path::{Command, Data, Position::Absolute},
+        Path,
+    },
+    Node,
+};
+
+use crate::maze::{
+    interface::{Maze, MazeRenderer, Solution},
+    paint::Gradient,
+    shape::layered::LayeredMaze,
+};
+
+use super::{
+    is_jump, one_way_passages, paint_checkpoints, paint_locks, paint_portals, paint_terrain,
+    portal_leaps,
+    rect::{outline_cell, render_cell, render_one_way},
+    segment_colour, CellSize, RenderedMaze, WebColour,
+};
+
+/// Draws the levels of a `LayeredMaze` next to each other, from the lowest
+/// on the left to the highest on the right.
+pub struct LayeredRenderer<'a> {
+    maze: &'a LayeredMaze,
+    solution: &'a Solution<(usize, usize, usize)>,
+    stroke_width: f64,
+    document: svg::Document,
+    cell_size: CellSize,
+}
+
+impl MazeRenderer<LayeredMaze> for LayeredRenderer<'_> {
+    fn stain(&mut self, gradient: (WebColour, WebColour)) {
+        let gradient = Gradient::new(gradient, self.maze, self.solution);
+        let s = self.cell_size.0 as f64;
+        let fudge = self.stroke_width.floor().min(3.0);
+        for (x, y, z) in self.maze.get_all_nodes() {
+            let (ox, oy) = self.origin(z);
+            let rect = svg::node::element::Rectangle::new()
+                .set("x", ox + x as f64 * s - fudge)
+                .set("y", oy + y as f64 * s - fudge)
+                .set("width", s + fudge)
+                .set("height", s + fudge)
+                .set("fill", gradient.compute(&(x, y, z)).to_web_string());
+            self.document.append(rect);
+        }
+    }
+
+    fn solve(&mut self, stroke_colour: WebColour) {
+        let segments = self.solution.segments();
+        for (i, segment) in segments.iter().enumerate() {
+            let colour = segment_colour(stroke_colour, i, segments.len());
+            self.trace(segment, colour, i == 0, i == segments.len() - 1);
+        }
+    }
+
+    fn paint(&mut self, border: WebColour) {
+        let s = self.cell_size.0;
+        if let Some(terrain) = paint_terrain(self.maze, border, s as f64, |data, (x, y, z)| {
+            outline_cell(data, (x, y), s, self.origin(z))
+        }) {
+            self.document.append(terrain);
+        }
+        let mut walls = Data::new();
+        let mut glyphs = Data::new();
+        for (x, y, z) in self.maze.get_all_nodes() {
+            render_cell(
+                &mut walls,
+                self.maze.level(z),
+                (x, y),
+                self.cell_size.0,
+                self.origin(z),
+            );
+            self.render_stairs(&mut glyphs, (x, y, z));
+        }
+        // one way stairs only get a glyph at the end they can be taken from,
+        // so only the passages on each level need arrows
+        for ((x, y, z), (nx, ny, nz)) in one_way_passages(self.maze) {
+            if z == nz {
+                render_one_way(
+                    &mut glyphs,
+                    self.maze.level(z),
+                    (x, y),
+                    (nx, ny),
+                    self.cell_size.0,
+                    self.origin(z),
+                );
+            }
+        }
+        let path = Path::new()
+            .set("fill", "none")
+            .set("stroke", border.to_web_string())
+            .set("stroke-width", self.stroke_width * 2.0)
+            .set("stroke-linecap", "round")
+            .set("stroke-linejoin", "round")
+            .set("d", walls);
+        self.document.append(path);
+        let glyphs = Path::new()
+            .set("fill", border.to_web_string())
+            .set("stroke", "none")
+            .set("d", glyphs);
+        self.document.append(glyphs);
+        if let Some(portals) = paint_portals(self.maze, border, s as f64, |cell| self.centre(cell))
+        {
+            self.document.append(portals);
+        }
+        if let Some(checkpoints) =
+            paint_checkpoints(&self.solution.checkpoints, border, s as f64, |cell| {
+                self.centre(cell)
+            })
+        {
+            self.document.append(checkpoints);
+        }
+        if let Some(locks) = paint_locks(self.maze, s as f64, |cell| self.centre(cell)) {
+            self.document.append(locks);
+        }
+    }
+
+    fn render(self) -> RenderedMaze {
+        let (ox, _) = self.origin(self.maze.level_count());
+        let (x, y) = (
+            ox - self.cell_size.0 as f64 + self.stroke_width,
+            (self.maze.extents.1 * self.cell_size.0) as f64 + 2.0 * self.stroke_width,
+        );
+        RenderedMaze::new(self.document, (x.floor() as u32, y.floor() as u32))
+    }
+}
+
+impl<'a> LayeredRenderer<'a> {
+    pub fn new(
+        maze: &'a LayeredMaze,
+        solution: &'a Solution<(usize, usize, usize)>,
+        stroke_width: f64,
+        cell_width: usize,
+    ) -> Self {
+        Self {
+            maze,
+            solution,
+            stroke_width,
+            document: svg::Document::new(),
+            cell_size: CellSize(cell_width),
+        }
+    }
+
+    /// Draw one segment of the solution, coming in from the edge of the
+    /// lowest level if it's the first one, and going out of the highest one if
+    /// it's the last one. The path is interrupted where it takes the stairs,
+    /// and continues in the same spot on the next level.
+    fn trace(
+        &mut self,
+        path: &[(usize, usize, usize)],
+        colour: WebColour,
+        first: bool,
+        last: bool,
+    ) {
+        let mut data = Data::new();
+        let s = self.cell_size.0 as f64;
+        let start = if first {
+            (self.centre((path[0].0, 0, 0)).0, 0.0)
+        } else {
+            self.centre(path[0])
+        };
+        data.append(Command::Move(Absolute, start.into()));
+        let mut previous = path[0];
+        for node in path.iter() {
+            let command = if node.2 == previous.2 && !is_jump(self.maze, previous, *node) {
+                Command::Line
+            } else {
+                Command::Move
+            };
+            previous = *node;
+            data.append(command(Absolute, self.centre(*node).into()));
+        }
+        if last {
+            let (x, y) = self.centre(*path.last().unwrap());
+            data.append(Command::Line(Absolute, (x, y + s / 2.0).into()));
+        }
+
+        let path_element = Path::new()
+            .set("fill", "none")
+            .set("stroke", colour.to_web_string())
+            .set("stroke-width", self.stroke_width * 2.0)
+            .set("stroke-linecap", "round")
+            .set("stroke-linejoin", "round")
+            .set("d", data);
+        self.document.append(path_element);
+        if let Some(leaps) = portal_leaps(self.maze, path, colour, self.stroke_width, |cell| {
+            self.centre(cell)
+        }) {
+            self.document.append(leaps);
+        }
+    }
+
+    /// The top left corner of a level. Levels are one cell apart.
+    fn origin(&self, level: usize) -> (f64, f64) {
+        let width = ((self.maze.extents.0 + 1) * self.cell_size.0) as f64;
+        (self.stroke_width + level as f64 * width, self.stroke_width)
+    }
+
+    fn centre(&self, (x, y, z): (usize, usize, usize)) -> (f64, f64) {
+        let s = self.cell_size.0 as f64;
+        let (ox, oy) = self.origin(z);
+        (ox + (x as f64 + 0.5) * s, oy + (y as f64 + 0.5) * s)
+    }
+
+    /// Stairs up are a triangle pointing up on the right of the cell, stairs
+    /// down one pointing down on the left, so a cell can have both.
+    fn render_stairs(&self, data: &mut Data, node: (usize, usize, usize)) {
+        let s = self.cell_size.0 as f64;
+        let t = s / 6.0;
+        let (cx, cy) = self.centre(node);
+        if self.maze.has_stairs_up(node) {
+            let x = cx + s / 5.0;
+            data.append(Command::Move(Absolute, (x - t, cy + t / 2.0).into()));
+            data.append(Command::Line(Absolute, (x + t, cy + t / 2.0).into()));
+            data.append(Command::Line(Absolute, (x, cy - t).into()));
+            data.append(Command::Close);
+        }
+        if self.maze.has_stairs_down(node) {
+            let x = cx - s / 5.0;
+            data.append(Command::Move(Absolute, (x - t, cy - t / 2.0).into()));
+            data.append(Command::Line(Absolute, (x + t, cy - t / 2.0).into()));
+            data.append(Command::Line(Absolute, (x, cy + t).into()));
+            data.append(Command::Close);
+        }
+    }
+}
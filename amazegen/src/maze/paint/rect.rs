@@ -15,7 +15,11 @@ use crate::maze::{
     shape::regular::{Direction, RectilinearMaze},
 };
 
-use super::{CellSize, RenderedMaze};
+use super::{
+    arrow, fill_arrows, is_jump, one_way_passages, paint_checkpoints, paint_ends, paint_locks,
+    paint_pairs, paint_portals, paint_terrain, paint_wraps, portal_leaps, segment_colour,
+    way_colour, CellSize, Ends, RenderedMaze, WebColour,
+};
 
 pub struct RectilinearRenderer<'a> {
     maze: &'a RectilinearMaze,
@@ -43,40 +47,55 @@ impl MazeRenderer<RectilinearMaze> for RectilinearRenderer<'_> {
     }
 
     fn solve(&mut self, stroke_colour: super::WebColour) {
-        let mut data = Data::new();
-        let s = self.cell_size.0;
-        let stroke: usize = self.stroke_width.floor() as usize;
-        data.append(Command::Move(
-            Absolute,
-            (
-                self.solution.path.first().unwrap_or(&(0, 0)).0 * s + s / 2 + stroke,
-                0,
-            )
-                .into(),
-        ));
-        self.solution.path.iter().for_each(|(x, y)| {
-            data.append(Command::Line(
-                Absolute,
-                (x * s + s / 2 + stroke, *y * s + s / 2 + stroke).into(),
-            ))
-        });
-        data.append(Command::Line(Relative, (0, s / 2).into()));
-        let path = Path::new()
-            .set("fill", "none")
-            .set("stroke", stroke_colour.to_web_string())
-            .set("stroke-width", self.stroke_width * 2.0)
-            .set("stroke-linecap", "round")
-            .set("stroke-linejoin", "round")
-            .set("d", data);
-        self.document.append(path);
+        let segments = self.solution.segments();
+        let inside = self.maze.get_wrap().vertical;
+        let main_colour = way_colour(self.solution, stroke_colour, 0);
+        for (i, segment) in segments.iter().enumerate() {
+            let colour = segment_colour(main_colour, i, segments.len());
+            let start = (i == 0 && !inside).then_some(Direction::Up);
+            let finish = (i == segments.len() - 1 && !inside).then_some(Direction::Down);
+            self.trace(segment, colour, start, finish);
+        }
+        for (i, way) in self.solution.pairs.iter().enumerate() {
+            let colour = way_colour(self.solution, stroke_colour, i + 1);
+            let (start, finish) = (way[0], way[way.len() - 1]);
+            self.trace(
+                way,
+                colour,
+                self.maze.opening(start),
+                self.maze.opening(finish),
+            );
+        }
     }
 
     fn paint(&mut self, border: super::WebColour) {
+        let (s, origin) = (self.cell_size.0, (self.stroke_width, self.stroke_width));
+        if let Some(terrain) = paint_terrain(self.maze, border, s as f64, |data, cell| {
+            outline_cell(data, cell, s, origin)
+        }) {
+            self.document.append(terrain);
+        }
+        // a faint floor, so that rooms stand out from the corridors
+        let floor = WebColour { a: 0x30, ..border };
+        for room in self.maze.get_rooms() {
+            let rect = svg::node::element::Rectangle::new()
+                .set("x", room.origin.0 as f64 * s as f64 + self.stroke_width)
+                .set("y", room.origin.1 as f64 * s as f64 + self.stroke_width)
+                .set("width", room.extents.0 * s)
+                .set("height", room.extents.1 * s)
+                .set("fill", floor.to_web_string());
+            self.document.append(rect);
+        }
         let mut data = Data::new();
-        self.maze
-            .get_all_nodes()
-            .iter()
-            .for_each(|cell| self.render_cell(&mut data, *cell));
+        self.maze.get_all_nodes().iter().for_each(|cell| {
+            render_cell(
+                &mut data,
+                self.maze,
+                *cell,
+                self.cell_size.0,
+                (self.stroke_width, self.stroke_width),
+            )
+        });
         let path = Path::new()
             .set("fill", "none")
             .set("stroke", border.to_web_string())
@@ -85,6 +104,47 @@ impl MazeRenderer<RectilinearMaze> for RectilinearRenderer<'_> {
             .set("stroke-linejoin", "round")
             .set("d", data);
         self.document.append(path);
+
+        let mut arrows = Data::new();
+        for (node, neighbour) in one_way_passages(self.maze) {
+            render_one_way(
+                &mut arrows,
+                self.maze,
+                node,
+                neighbour,
+                self.cell_size.0,
+                (self.stroke_width, self.stroke_width),
+            );
+        }
+        fill_arrows(&mut self.document, arrows, border);
+        if let Some(portals) = paint_portals(self.maze, border, s as f64, |cell| self.centre(cell))
+        {
+            self.document.append(portals);
+        }
+        if let Some(checkpoints) =
+            paint_checkpoints(&self.solution.checkpoints, border, s as f64, |cell| {
+                self.centre(cell)
+            })
+        {
+            self.document.append(checkpoints);
+        }
+        if let Some(locks) = paint_locks(self.maze, s as f64, |cell| self.centre(cell)) {
+            self.document.append(locks);
+        }
+        if let Some(wraps) = paint_wraps(&self.wrapping_passages(), border, s as f64) {
+            self.document.append(wraps);
+        }
+        if let Some(pairs) = paint_pairs(&self.pairs(), s as f64) {
+            self.document.append(pairs);
+        }
+        if self.maze.get_wrap().vertical {
+            self.document.append(paint_ends(
+                self.centre(self.maze.get_entrance()),
+                self.centre(self.maze.get_exit()),
+                border,
+                s as f64,
+            ));
+        }
     }
 
     fn render(self) -> RenderedMaze {
@@ -97,6 +157,66 @@ impl MazeRenderer<RectilinearMaze> for RectilinearRenderer<'_> {
 }
 
 impl<'a> RectilinearRenderer<'a> {
+    fn centre(&self, (x, y): (usize, usize)) -> (f64, f64) {
+        let s = self.cell_size.0 as f64;
+        (
+            (x as f64 + 0.5) * s + self.stroke_width,
+            (y as f64 + 0.5) * s + self.stroke_width,
+        )
+    }
+
+    /// The midpoint of the side of `cell` in `direction`.
+    fn side(&self, cell: (usize, usize), direction: Direction) -> (f64, f64) {
+        let (x, y) = self.centre(cell);
+        let (dx, dy) = direction.offset();
+        let half = self.cell_size.0 as f64 / 2.0;
+        (x + dx as f64 * half, y + dy as f64 * half)
+    }
+
+    /// Both ends of every passage that wraps around the edges, see
+    /// `paint_wraps`.
+    fn wrapping_passages(&self) -> Vec<Ends> {
+        let maze = self.maze;
+        let mut passages = vec![];
+        for cell in maze.get_all_nodes() {
+            for direction in [Direction::Right, Direction::Down] {
+                let Some(other) = maze
+                    .translate(cell, direction)
+                    .filter(|_| maze.wraps(cell, direction))
+                else {
+                    continue;
+                };
+                let Some(back) = maze.direction_between(other, cell) else {
+                    continue;
+                };
+                // a tunnel may leave through a side with a wall
+                if !maze.has_wall(cell, direction) || !maze.has_wall(other, back) {
+                    passages.push([
+                        (self.side(cell, direction), self.centre(cell)),
+                        (self.side(other, back), self.centre(other)),
+                    ]);
+                }
+            }
+        }
+        passages
+    }
+
+    /// Both ends of every start and finish pair, see `paint_pairs`.
+    fn pairs(&self) -> Vec<Option<Ends>> {
+        let ways = [&self.solution.path[..]]
+            .into_iter()
+            .chain(self.solution.pairs.iter().map(|way| &way[..]));
+        ways.map(|way| {
+            let (start, finish) = (way[0], way[way.len() - 1]);
+            let (a, b) = (self.maze.opening(start)?, self.maze.opening(finish)?);
+            Some([
+                (self.side(start, a), self.centre(start)),
+                (self.side(finish, b), self.centre(finish)),
+            ])
+        })
+        .collect()
+    }
+
     pub fn new(
         maze: &'a RectilinearMaze,
         solution: &'a Solution<(usize, usize)>,
@@ -114,29 +234,194 @@ impl<'a> RectilinearRenderer<'a> {
         }
     }
 
-    fn render_cell(&self, data: &mut Data, (x, y): (usize, usize)) {
-        let s = self.cell_size.0 as i32;
-
-        let c = |d: Direction| {
-            if self.maze.has_wall((x, y), d) {
-                Command::Line
-            } else {
-                Command::Move
-            }
-        };
-
+    /// Draw part of a way through the maze, coming in from the edge through
+    /// the `start` side of its first cell, and going out again through the
+    /// `finish` side of its last one, if they're given.
+    fn trace(
+        &mut self,
+        path: &[(usize, usize)],
+        colour: WebColour,
+        start: Option<Direction>,
+        finish: Option<Direction>,
+    ) {
+        let mut data = Data::new();
+        let s = self.cell_size.0;
+        let stroke: usize = self.stroke_width.floor() as usize;
+        let centre = |(x, y): (usize, usize)| (x * s + s / 2 + stroke, y * s + s / 2 + stroke);
+        let (x, y) = centre(*path.first().unwrap_or(&(0, 0)));
+        let (dx, dy) = start.map_or((0, 0), |direction| direction.offset());
+        // all the way out to the edge of the picture
+        let out = (s / 2 + stroke) as isize;
         data.append(Command::Move(
             Absolute,
-            (
-                x as f64 * s as f64 + self.stroke_width,
-                y as f64 * s as f64 + self.stroke_width,
-            )
-                .into(),
+            (x as isize + dx * out, y as isize + dy * out).into(),
         ));
+        let mut previous = path[0];
+        path.iter().for_each(|(x, y)| {
+            let command = if is_jump(self.maze, previous, (*x, *y)) {
+                Command::Move
+            } else if let Some(direction) = self
+                .maze
+                .direction_between(previous, (*x, *y))
+                .filter(|direction| self.maze.wraps(previous, *direction))
+            {
+                // leave through one edge and come back in through the other
+                let back = self.maze.direction_between((*x, *y), previous).unwrap();
+                let (dx, dy) = direction.offset();
+                let (bx, by) = back.offset();
+                let half = (s / 2) as isize;
+                let (px, py) = centre(previous);
+                let (cx, cy) = centre((*x, *y));
+                data.append(Command::Line(
+                    Absolute,
+                    (px as isize + dx * half, py as isize + dy * half).into(),
+                ));
+                data.append(Command::Move(
+                    Absolute,
+                    (cx as isize + bx * half, cy as isize + by * half).into(),
+                ));
+                Command::Line
+            } else {
+                Command::Line
+            };
+            previous = (*x, *y);
+            data.append(command(Absolute, centre((*x, *y)).into()))
+        });
+        if let Some(direction) = finish {
+            let (dx, dy) = direction.offset();
+            let half = (s / 2) as isize;
+            data.append(Command::Line(Relative, (dx * half, dy * half).into()));
+        }
+        let path_element = Path::new()
+            .set("fill", "none")
+            .set("stroke", colour.to_web_string())
+            .set("stroke-width", self.stroke_width * 2.0)
+            .set("stroke-linecap", "round")
+            .set("stroke-linejoin", "round")
+            .set("d", data);
+        self.document.append(path_element);
+        if let Some(leaps) = portal_leaps(self.maze, path, colour, self.stroke_width, |cell| {
+            self.centre(cell)
+        }) {
+            self.document.append(leaps);
+        }
+    }
+}
+
+/// Draw the walls of a cell of `maze`, whose top left corner is at `origin`.
+pub(super) fn render_cell(
+    data: &mut Data,
+    maze: &RectilinearMaze,
+    (x, y): (usize, usize),
+    cell_size: usize,
+    (ox, oy): (f64, f64),
+) {
+    if maze.is_crossing((x, y)) {
+        return render_crossing(data, maze, (x, y), cell_size, (ox, oy));
+    }
+    let s = cell_size as i32;
+
+    let c = |d: Direction| {
+        if maze.has_wall((x, y), d) {
+            Command::Line
+        } else {
+            Command::Move
+        }
+    };
+
+    data.append(Command::Move(
+        Absolute,
+        (x as f64 * s as f64 + ox, y as f64 * s as f64 + oy).into(),
+    ));
 
-        data.append(c(Direction::Up)(Relative, (s, 0).into()));
-        data.append(c(Direction::Right)(Relative, (0, s).into()));
-        data.append(c(Direction::Down)(Relative, (-s, 0).into()));
-        data.append(c(Direction::Left)(Relative, (0, -s).into()));
+    data.append(c(Direction::Up)(Relative, (s, 0).into()));
+    data.append(c(Direction::Right)(Relative, (0, s).into()));
+    data.append(c(Direction::Down)(Relative, (-s, 0).into()));
+    data.append(c(Direction::Left)(Relative, (0, -s).into()));
+}
+
+/// Trace the outline of a cell, in a maze whose top left corner is at
+/// `origin`.
+pub(super) fn outline_cell(
+    data: &mut Data,
+    (x, y): (usize, usize),
+    cell_size: usize,
+    (ox, oy): (f64, f64),
+) {
+    let s = cell_size as f64;
+    data.append(Command::Move(
+        Absolute,
+        (ox + x as f64 * s, oy + y as f64 * s).into(),
+    ));
+    data.append(Command::Line(Relative, (s, 0).into()));
+    data.append(Command::Line(Relative, (0, s).into()));
+    data.append(Command::Line(Relative, (-s, 0).into()));
+    data.append(Command::Close);
+}
+
+/// Draw an arrow where the one way passage from `node` to `neighbour` of
+/// `maze` leaves `node`, in a maze whose top left corner is at `origin`. That's on the wall
+/// between them, or where the tunnel starts if the passage goes underneath a
+/// crossing.
+pub(super) fn render_one_way(
+    data: &mut Data,
+    maze: &RectilinearMaze,
+    (x, y): (usize, usize),
+    neighbour: (usize, usize),
+    cell_size: usize,
+    (ox, oy): (f64, f64),
+) {
+    let s = cell_size as f64;
+    let (dx, dy) = Direction::iterator()
+        .find(|direction| maze.step((x, y), *direction) == Some(neighbour))
+        .map_or((0, 0), |direction| direction.offset());
+    let direction = (dx as f64, dy as f64);
+    let at = (
+        ox + (x as f64 + 0.5 + direction.0 / 2.0) * s,
+        oy + (y as f64 + 0.5 + direction.1 / 2.0) * s,
+    );
+    arrow(data, at, direction, s / 3.0);
+}
+
+/// The passage across the cell is narrower than the cell, so we can see
+/// the walls of the tunnel end where they go beneath it.
+fn render_crossing(
+    data: &mut Data,
+    maze: &RectilinearMaze,
+    (x, y): (usize, usize),
+    cell_size: usize,
+    (ox, oy): (f64, f64),
+) {
+    let s = cell_size as f64;
+    let q = s / 4.0;
+    let (ox, oy) = (x as f64 * s + ox, y as f64 * s + oy);
+    // `along` follows the passage on top, `across` follows the tunnel
+    let horizontal = !maze.has_wall((x, y), Direction::Left);
+    let point = |along: f64, across: f64| {
+        if horizontal {
+            (ox + along, oy + across)
+        } else {
+            (ox + across, oy + along)
+        }
+    };
+    let relative = |along: f64, across: f64| {
+        if horizontal {
+            (along, across)
+        } else {
+            (across, along)
+        }
+    };
+
+    for edge in [0.0, s] {
+        // the walls of the tunnel, up to the passage
+        data.append(Command::Move(Absolute, point(edge, 0.0).into()));
+        data.append(Command::Line(Relative, relative(0.0, q).into()));
+        data.append(Command::Move(Absolute, point(edge, 3.0 * q).into()));
+        data.append(Command::Line(Relative, relative(0.0, q).into()));
+    }
+    for side in [q, 3.0 * q] {
+        // the walls of the passage
+        data.append(Command::Move(Absolute, point(0.0, side).into()));
+        data.append(Command::Line(Relative, relative(s, 0.0).into()));
     }
 }
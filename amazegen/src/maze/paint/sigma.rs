@@ -15,7 +15,11 @@ use crate::maze::{
     },
 };
 
-use super::{RenderedMaze, WebColour};
+use super::{
+    arrow, fill_arrows, is_jump, midpoint, one_way_passages, paint_checkpoints, paint_ends,
+    paint_locks, paint_pairs, paint_portals, paint_terrain, paint_wraps, portal_leaps,
+    segment_colour, way_colour, Ends, RenderedMaze, WebColour,
+};
 
 pub struct SigmaMazeRenderer<'a> {
     maze: &'a SigmaMaze,
@@ -49,59 +53,42 @@ impl MazeRenderer<SigmaMaze> for SigmaMazeRenderer<'_> {
     }
 
     fn solve(&mut self, stroke_colour: WebColour) {
-        let mut data = Data::new();
-        let entrance = {
-            let (x, y) = self.compute_centre(&self.solution.path[0]);
-            let neighbours = self.maze.cells[self.maze.get_index(self.solution.path[0])]
-                .accessible
-                .clone();
-            let Dimensions { a, b, .. } = self.dimensions;
-            if neighbours[Direction::NorthWest].is_some() && self.solution.path[0].x() % 2 == 0 {
-                (x - (a * 1.5), y - (b / 2.0))
-            } else if neighbours[Direction::NorthEast].is_some()
-                && self.solution.path[0].x() % 2 == 0
-            {
-                (x + (a * 1.5), y - (b / 2.0))
-            } else {
-                (x, y - b + self.stroke_width / 2.0)
-            }
-        };
-
-        let exit = {
-            let last = self.solution.path.last().unwrap();
-            let (x, y) = self.compute_centre(last);
-            let neighbours = self.maze.cells[self.maze.get_index(*last)]
-                .accessible
-                .clone();
-            let Dimensions { a, b, .. } = self.dimensions;
-            if neighbours[Direction::SouthWest].is_some() && last.x() % 2 == 1 {
-                (x - (a * 1.5), y + (b / 2.0))
-            } else if neighbours[Direction::SouthEast].is_some() && last.x() % 2 == 1 {
-                (x + (a * 1.5), y + (b / 2.0))
-            } else {
-                (x, y + b - self.stroke_width / 2.0)
-            }
-        };
-
-        data.append(Command::Move(Absolute, entrance.into()));
-        self.solution
-            .path
-            .iter()
-            .map(|node| self.compute_centre(node))
-            .for_each(|coords| data.append(Command::Line(Absolute, coords.into())));
-        data.append(Command::Line(Absolute, exit.into()));
-
-        let path = Path::new()
-            .set("fill", "none")
-            .set("stroke", stroke_colour.to_web_string())
-            .set("stroke-width", self.stroke_width)
-            .set("stroke-linecap", "round")
-            .set("stroke-linejoin", "round")
-            .set("d", data);
-        self.document.append(path);
+        let segments = self.solution.segments();
+        // mazes that wrap around vertically have no edge to come in through
+        let inside = self.maze.get_wrap().vertical;
+        let main_colour = way_colour(self.solution, stroke_colour, 0);
+        for (i, segment) in segments.iter().enumerate() {
+            let colour = segment_colour(main_colour, i, segments.len());
+            let start = (i == 0 && !inside).then(|| self.entrance(&segment[0]));
+            let finish =
+                (i == segments.len() - 1 && !inside).then(|| self.exit(segment.last().unwrap()));
+            self.trace(segment, colour, start, finish);
+        }
+        for (i, way) in self.solution.pairs.iter().enumerate() {
+            let colour = way_colour(self.solution, stroke_colour, i + 1);
+            let [start, finish] = [way[0], way[way.len() - 1]]
+                .map(|cell| self.maze.opening(cell).map(|d| self.side(&cell, d)));
+            self.trace(way, colour, start, finish);
+        }
     }
 
     fn paint(&mut self, border: WebColour) {
+        if let Some(terrain) = paint_terrain(
+            self.maze,
+            border,
+            self.dimensions.cell_height,
+            |data, cell| {
+                let (x, y) = self.compute_centre(&cell);
+                let Dimensions { a, b, .. } = self.dimensions;
+                data.append(Command::Move(Absolute, (x - 2.0 * a, y).into()));
+                for step in [(a, -b), (2.0 * a, 0.0), (a, b), (-a, b), (-2.0 * a, 0.0)] {
+                    data.append(Command::Line(Relative, step.into()));
+                }
+                data.append(Command::Close);
+            },
+        ) {
+            self.document.append(terrain);
+        }
         let mut data = Data::new();
         self.maze
             .get_all_nodes()
@@ -115,6 +102,61 @@ impl MazeRenderer<SigmaMaze> for SigmaMazeRenderer<'_> {
             .set("stroke-linejoin", "round")
             .set("d", data);
         self.document.append(path);
+
+        let mut arrows = Data::new();
+        for (node, neighbour) in one_way_passages(self.maze) {
+            let from = self.compute_centre(&node);
+            let at = match self.wrapping_side(node, neighbour) {
+                Some(d) => self.side(&node, d),
+                None => midpoint(from, self.compute_centre(&neighbour)),
+            };
+            let direction = (at.0 - from.0, at.1 - from.1);
+            arrow(
+                &mut arrows,
+                at,
+                direction,
+                self.dimensions.cell_height / 3.0,
+            );
+        }
+        fill_arrows(&mut self.document, arrows, border);
+        if let Some(portals) =
+            paint_portals(self.maze, border, self.dimensions.cell_height, |cell| {
+                self.compute_centre(&cell)
+            })
+        {
+            self.document.append(portals);
+        }
+        if let Some(checkpoints) = paint_checkpoints(
+            &self.solution.checkpoints,
+            border,
+            self.dimensions.cell_height,
+            |cell| self.compute_centre(&cell),
+        ) {
+            self.document.append(checkpoints);
+        }
+        if let Some(locks) = paint_locks(self.maze, self.dimensions.cell_height, |cell| {
+            self.compute_centre(&cell)
+        }) {
+            self.document.append(locks);
+        }
+        if let Some(wraps) = paint_wraps(
+            &self.wrapping_passages(),
+            border,
+            self.dimensions.cell_height,
+        ) {
+            self.document.append(wraps);
+        }
+        if let Some(pairs) = paint_pairs(&self.pairs(), self.dimensions.cell_height) {
+            self.document.append(pairs);
+        }
+        if self.maze.get_wrap().vertical {
+            self.document.append(paint_ends(
+                self.compute_centre(&self.solution.path[0]),
+                self.compute_centre(self.solution.path.last().unwrap()),
+                border,
+                self.dimensions.cell_height,
+            ));
+        }
     }
 
     fn render(self) -> RenderedMaze {
@@ -169,6 +211,144 @@ impl<'a> SigmaMazeRenderer<'a> {
         }
     }
 
+    /// The midpoint of the side of `cell` in direction `d`.
+    fn side(&self, cell: &Cartesian<usize>, d: Direction) -> (f64, f64) {
+        let (x, y) = self.compute_centre(cell);
+        let Dimensions { a, b, .. } = self.dimensions;
+        let (dx, dy) = match d {
+            Direction::North => (0.0, -b),
+            Direction::South => (0.0, b),
+            Direction::NorthEast => (1.5 * a, -b / 2.0),
+            Direction::NorthWest => (-1.5 * a, -b / 2.0),
+            Direction::SouthEast => (1.5 * a, b / 2.0),
+            Direction::SouthWest => (-1.5 * a, b / 2.0),
+        };
+        (x + dx, y + dy)
+    }
+
+    /// The side of `from` that leads around the edges of the maze to `to`,
+    /// if that's how they're connected.
+    fn wrapping_side(&self, from: Cartesian<usize>, to: Cartesian<usize>) -> Option<Direction> {
+        self.maze
+            .direction_between(from, to)
+            .filter(|d| self.maze.wraps(&from, *d))
+    }
+
+    /// Both ends of every passage that wraps around the edges, see
+    /// `paint_wraps`.
+    fn wrapping_passages(&self) -> Vec<Ends> {
+        let mut passages = vec![];
+        for cell in self.maze.get_all_nodes() {
+            for d in [Direction::South, Direction::SouthEast, Direction::SouthWest] {
+                if !self.maze.wraps(&cell, d) || !self.maze.has_path(&cell, d) {
+                    continue;
+                }
+                let other = self.maze.cells[self.maze.get_index(cell)].accessible[d].unwrap();
+                if let Some(back) = self.maze.direction_between(other, cell) {
+                    passages.push([
+                        (self.side(&cell, d), self.compute_centre(&cell)),
+                        (self.side(&other, back), self.compute_centre(&other)),
+                    ]);
+                }
+            }
+        }
+        passages
+    }
+
+    /// Both ends of every start and finish pair, see `paint_pairs`.
+    fn pairs(&self) -> Vec<Option<Ends>> {
+        let ways = [&self.solution.path[..]]
+            .into_iter()
+            .chain(self.solution.pairs.iter().map(|way| &way[..]));
+        ways.map(|way| {
+            let (start, finish) = (way[0], way[way.len() - 1]);
+            let (a, b) = (self.maze.opening(start)?, self.maze.opening(finish)?);
+            Some([
+                (self.side(&start, a), self.compute_centre(&start)),
+                (self.side(&finish, b), self.compute_centre(&finish)),
+            ])
+        })
+        .collect()
+    }
+
+    /// Where the solution comes in through the entrance in `cell`.
+    fn entrance(&self, cell: &Cartesian<usize>) -> (f64, f64) {
+        let (x, y) = self.compute_centre(cell);
+        let neighbours = &self.maze.cells[self.maze.get_index(*cell)].accessible;
+        let Dimensions { a, b, .. } = self.dimensions;
+        // the entrance is a path from the cell to itself
+        let entrance = Some(*cell);
+        if neighbours[Direction::NorthWest] == entrance && cell.x().is_multiple_of(2) {
+            (x - (a * 1.5), y - (b / 2.0))
+        } else if neighbours[Direction::NorthEast] == entrance && cell.x().is_multiple_of(2) {
+            (x + (a * 1.5), y - (b / 2.0))
+        } else {
+            (x, y - b + self.stroke_width / 2.0)
+        }
+    }
+
+    /// Where the solution leaves through the exit in `cell`.
+    fn exit(&self, cell: &Cartesian<usize>) -> (f64, f64) {
+        let (x, y) = self.compute_centre(cell);
+        let neighbours = &self.maze.cells[self.maze.get_index(*cell)].accessible;
+        let Dimensions { a, b, .. } = self.dimensions;
+        if neighbours[Direction::SouthWest] == Some(*cell) && cell.x() % 2 == 1 {
+            (x - (a * 1.5), y + (b / 2.0))
+        } else if neighbours[Direction::SouthEast] == Some(*cell) && cell.x() % 2 == 1 {
+            (x + (a * 1.5), y + (b / 2.0))
+        } else {
+            (x, y + b - self.stroke_width / 2.0)
+        }
+    }
+
+    /// Draw part of a way through the maze, coming in from `start` on the
+    /// edge of the maze and going out again to `finish`, if they're given.
+    fn trace(
+        &mut self,
+        path: &[Cartesian<usize>],
+        colour: WebColour,
+        start: Option<(f64, f64)>,
+        finish: Option<(f64, f64)>,
+    ) {
+        let mut data = Data::new();
+        let entrance = start.unwrap_or_else(|| self.compute_centre(&path[0]));
+
+        data.append(Command::Move(Absolute, entrance.into()));
+        let mut previous = path[0];
+        for node in path.iter() {
+            let command = if is_jump(self.maze, previous, *node) {
+                Command::Move
+            } else if let Some(d) = self.wrapping_side(previous, *node) {
+                // leave through one edge and come back in through the other
+                let back = self.maze.direction_between(*node, previous).unwrap();
+                data.append(Command::Line(Absolute, self.side(&previous, d).into()));
+                data.append(Command::Move(Absolute, self.side(node, back).into()));
+                Command::Line
+            } else {
+                Command::Line
+            };
+            previous = *node;
+            data.append(command(Absolute, self.compute_centre(node).into()));
+        }
+        if let Some(exit) = finish {
+            data.append(Command::Line(Absolute, exit.into()));
+        }
+
+        let path_element = Path::new()
+            .set("fill", "none")
+            .set("stroke", colour.to_web_string())
+            .set("stroke-width", self.stroke_width)
+            .set("stroke-linecap", "round")
+            .set("stroke-linejoin", "round")
+            .set("d", data);
+        self.document.append(path_element);
+        if let Some(leaps) = portal_leaps(self.maze, path, colour, self.stroke_width, |cell| {
+            self.compute_centre(&cell)
+        }) {
+            self.document.append(leaps);
+        }
+    }
+
     fn compute_centre(&self, cell: &Cartesian<usize>) -> (f64, f64) {
         let Dimensions {
             a, b, cell_height, ..
@@ -176,7 +356,11 @@ impl<'a> SigmaMazeRenderer<'a> {
 
         let x = 3.0 * a * cell.x() as f64 + 2.0 * a + (self.stroke_width / 2.0);
         let y = cell_height * cell.y() as f64
-            + if cell.x() % 2 == 0 { b } else { 2.0 * b }
+            + if cell.x().is_multiple_of(2) {
+                b
+            } else {
+                2.0 * b
+            }
             + (self.stroke_width / 2.0);
 
         (x, y)
@@ -199,7 +383,12 @@ impl<'a> SigmaMazeRenderer<'a> {
         data.append(c(Direction::NorthWest)(Relative, (a, -b).into()));
         data.append(c(Direction::North)(Relative, (2.0 * a, 0).into()));
         data.append(c(Direction::NorthEast)(Relative, (a, b).into()));
-        if cell.x() == 0 || cell.y() == self.maze.size() - 1 || cell.x() == self.maze.size() - 1 {
+        // the cells below draw the other sides, unless there aren't any or
+        // they're on the opposite edge
+        if [Direction::SouthEast, Direction::South, Direction::SouthWest]
+            .into_iter()
+            .any(|d| self.maze.faces_outside(&cell, d) || self.maze.wraps(&cell, d))
+        {
             data.append(c(Direction::SouthEast)(Relative, (-a, b).into()));
             data.append(c(Direction::South)(Relative, (-2.0 * a, 0).into()));
             data.append(c(Direction::SouthWest)(Relative, (-a, -b).into()));
@@ -11,7 +11,11 @@ use svg::{Document, Node};
 use crate::maze::interface::{MazeRenderer, Solution};
 use crate::maze::shape::theta::{RingCell, RingMaze, RingNode};
 
-use super::{Gradient, RenderedMaze, WebColour};
+use super::{
+    arrow, fill_arrows, is_jump, midpoint, one_way_passages, paint_checkpoints, paint_locks,
+    paint_pairs, paint_portals, paint_terrain, portal_leaps, segment_colour, way_colour, Ends,
+    Gradient, RenderedMaze, WebColour,
+};
 
 #[allow(non_upper_case_globals)]
 const π: f64 = std::f64::consts::PI;
@@ -38,7 +42,7 @@ pub struct PolarGrid<'a> {
 }
 
 impl PolarGrid<'_> {
-    pub fn new(maze: &RingMaze, ring_height: f64, stroke_width: f64) -> PolarGrid {
+    pub fn new(maze: &RingMaze, ring_height: f64, stroke_width: f64) -> PolarGrid<'_> {
         PolarGrid {
             maze,
             ring_height,
@@ -169,6 +173,29 @@ impl<'a> RingMazeRenderer<'a> {
         }
     }
 
+    fn centre(&self, node: RingNode) -> (f64, f64) {
+        let point = self.polar(&node).to_cartesian(self.grid.centre);
+        (point.x, point.y)
+    }
+
+    /// Both ends of every start and finish pair, see `paint_pairs`. They're
+    /// all on the outer ring.
+    fn pairs(&self) -> Vec<Option<Ends>> {
+        let end = |node: RingNode| {
+            let side = PolarPoint {
+                r: self.grid.outer_radius(node.row),
+                θ: self.polar(&node).θ,
+            }
+            .to_cartesian(self.grid.centre);
+            ((side.x, side.y), self.centre(node))
+        };
+        [&self.solution.path[..]]
+            .into_iter()
+            .chain(self.solution.pairs.iter().map(|way| &way[..]))
+            .map(|way| Some([end(way[0]), end(way[way.len() - 1])]))
+            .collect()
+    }
+
     fn render_cell(data: &mut Data, grid: &PolarGrid, cell: &RingCell) {
         let node = cell.coordinates;
         let c = grid.compute_cell(node);
@@ -213,14 +240,18 @@ impl<'a> RingMazeRenderer<'a> {
         }
     }
 
-    fn split_nodes_traversing_north(&self) -> Vec<&RingNode> {
-        self.solution
-            .path
-            .iter()
+    /// Whether the solution walks from `a` to `b` between rings, rather than
+    /// around a ring or jumping through a portal.
+    fn changes_ring(&self, a: RingNode, b: RingNode) -> bool {
+        a.row != b.row && !is_jump(self.grid.maze, a, b)
+    }
+
+    fn split_nodes_traversing_north<'b>(&self, path: &'b [RingNode]) -> Vec<&'b RingNode> {
+        path.iter()
             .enumerate()
             .flat_map(|(i, node)| {
-                if self.solution.path[i.saturating_sub(1)].row != node.row
-                    && self.solution.path[min(self.solution.path.len() - 1, i + 1)].row != node.row
+                if self.changes_ring(path[i.saturating_sub(1)], *node)
+                    && self.changes_ring(*node, path[min(path.len() - 1, i + 1)])
                 {
                     vec![node, node]
                 } else {
@@ -229,47 +260,11 @@ impl<'a> RingMazeRenderer<'a> {
             })
             .collect::<Vec<_>>()
     }
-}
-
-impl MazeRenderer<RingMaze> for RingMazeRenderer<'_> {
-    fn stain(&mut self, gradient: (WebColour, WebColour)) {
-        let gradient = Gradient::new(gradient, self.grid.maze, self.solution);
-        {
-            self.document.append(
-                Circle::new()
-                    .set("cx", self.grid.centre.x)
-                    .set("cy", self.grid.centre.y)
-                    .set("r", self.grid.ring_height + 1.0)
-                    .set("stroke", "none")
-                    .set(
-                        "fill",
-                        gradient
-                            .compute(&RingNode { column: 0, row: 0 })
-                            .to_web_string(),
-                    ),
-            );
-        };
-
-        for node in self.grid.maze.cells.iter().skip(1) {
-            let outer = self.grid.outer_radius(node.coordinates.row);
-            let inner = self.grid.inner_radius(node.coordinates.row);
-            let c = self.grid.compute_cell_with_fudge(node.coordinates);
-            let data = Data::new()
-                .move_to((c.ax, c.ay))
-                .line_to((c.bx, c.by))
-                .elliptical_arc_to((outer, outer, 0, 0, 0, c.dx, c.dy))
-                .line_to((c.cx, c.cy))
-                .elliptical_arc_to((inner, inner, 0, 0, 1, c.ax, c.ay));
-            let path = Path::new()
-                .set("stroke", "none")
-                .set("fill", gradient.compute(&node.coordinates).to_web_string())
-                .set("d", data);
-            self.document.append(path);
-        }
-    }
 
-    fn solve(&mut self, stroke_colour: WebColour) {
-        let nodes = self.split_nodes_traversing_north();
+    /// Draw one segment of the solution, coming in from outside the maze if
+    /// it's the first one, and going out again if it's the last one.
+    fn trace(&mut self, path: &[RingNode], colour: WebColour, first: bool, last: bool) {
+        let nodes = self.split_nodes_traversing_north(path);
 
         let polar_points = nodes
             .iter()
@@ -277,12 +272,12 @@ impl MazeRenderer<RingMaze> for RingMazeRenderer<'_> {
             .map(|(i, node)| {
                 let path_prev = nodes[i.saturating_sub(1)];
                 let path_next = nodes[min(nodes.len() - 1, i + 1)];
-                if node.row < path_prev.row {
+                if node.row < path_prev.row && !is_jump(self.grid.maze, *path_prev, **node) {
                     PolarPoint {
                         r: self.grid.inner_radius(node.row) + self.grid.ring_height / 2.0,
                         θ: self.grid.θ_east(*path_prev) + self.grid.θ(path_prev.row) / 2.0,
                     }
-                } else if node.row < path_next.row {
+                } else if node.row < path_next.row && !is_jump(self.grid.maze, **node, *path_next) {
                     PolarPoint {
                         r: self.grid.inner_radius(node.row) + self.grid.ring_height / 2.0,
                         θ: self.grid.θ_east(*path_next) + self.grid.θ(path_next.row) / 2.0,
@@ -298,16 +293,20 @@ impl MazeRenderer<RingMaze> for RingMazeRenderer<'_> {
             .map(|p| p.to_cartesian(self.grid.centre))
             .collect::<Vec<_>>();
 
-        let r_out = self.grid.outer_radius(self.solution.path[0].row) + self.stroke_width / 2.0;
-        let mut data = Data::new()
-            .move_to(
-                (PolarPoint {
-                    r: r_out - self.stroke_width / 3.0,
-                    θ: polar_points[0].θ,
-                })
-                .to_cartesian(self.grid.centre),
-            )
-            .line_to::<CartesianPoint>(cartesian_points[0]);
+        let r_out = self.grid.outer_radius(path[0].row) + self.stroke_width / 2.0;
+        let mut data = if first {
+            Data::new()
+                .move_to(
+                    (PolarPoint {
+                        r: r_out - self.stroke_width / 3.0,
+                        θ: polar_points[0].θ,
+                    })
+                    .to_cartesian(self.grid.centre),
+                )
+                .line_to::<CartesianPoint>(cartesian_points[0])
+        } else {
+            Data::new().move_to::<CartesianPoint>(cartesian_points[0])
+        };
 
         cartesian_points
             .into_iter()
@@ -316,7 +315,9 @@ impl MazeRenderer<RingMaze> for RingMazeRenderer<'_> {
             .skip(1)
             .for_each(|(i, point)| {
                 let prev_i = i.saturating_sub(1);
-                if nodes[prev_i].row == nodes[i].row {
+                if is_jump(self.grid.maze, *nodes[prev_i], *nodes[i]) {
+                    data.append(Command::Move(Absolute, point.into()));
+                } else if nodes[prev_i].row == nodes[i].row {
                     let sweep = if (polar_points[prev_i].θ > polar_points[i].θ
                         && !(nodes[prev_i].column > 2 && nodes[i].column == 0))
                         || (nodes[prev_i].column == 0 && nodes[i].column > 2)
@@ -342,26 +343,118 @@ impl MazeRenderer<RingMaze> for RingMazeRenderer<'_> {
                     data.append(Command::Line(Absolute, point.into()));
                 }
             });
-        {
+        if last {
+            let r_out = self.grid.outer_radius(path[path.len() - 1].row) + self.stroke_width / 2.0;
             let exit = (PolarPoint {
                 r: r_out - self.stroke_width / 3.0,
                 θ: polar_points.last().unwrap().θ,
             })
             .to_cartesian(self.grid.centre);
             data.append(Command::Line(Absolute, (exit.x, exit.y).into()));
-        };
+        }
 
         let p = Path::new()
-            .set("stroke", stroke_colour.to_web_string())
+            .set("stroke", colour.to_web_string())
             .set("fill", "none")
             .set("stroke-linejoin", "round")
             .set("d", data)
             .set("stroke-width", 1.5 * self.stroke_width);
 
         self.document.append(p);
+        if let Some(leaps) = portal_leaps(
+            self.grid.maze,
+            path,
+            colour,
+            1.5 * self.stroke_width,
+            |node| self.centre(node),
+        ) {
+            self.document.append(leaps);
+        }
+    }
+}
+
+impl MazeRenderer<RingMaze> for RingMazeRenderer<'_> {
+    fn stain(&mut self, gradient: (WebColour, WebColour)) {
+        let gradient = Gradient::new(gradient, self.grid.maze, self.solution);
+        {
+            self.document.append(
+                Circle::new()
+                    .set("cx", self.grid.centre.x)
+                    .set("cy", self.grid.centre.y)
+                    .set("r", self.grid.ring_height + 1.0)
+                    .set("stroke", "none")
+                    .set(
+                        "fill",
+                        gradient
+                            .compute(&RingNode { column: 0, row: 0 })
+                            .to_web_string(),
+                    ),
+            );
+        };
+
+        for node in self.grid.maze.cells.iter().skip(1) {
+            let outer = self.grid.outer_radius(node.coordinates.row);
+            let inner = self.grid.inner_radius(node.coordinates.row);
+            let c = self.grid.compute_cell_with_fudge(node.coordinates);
+            let data = Data::new()
+                .move_to((c.ax, c.ay))
+                .line_to((c.bx, c.by))
+                .elliptical_arc_to((outer, outer, 0, 0, 0, c.dx, c.dy))
+                .line_to((c.cx, c.cy))
+                .elliptical_arc_to((inner, inner, 0, 0, 1, c.ax, c.ay));
+            let path = Path::new()
+                .set("stroke", "none")
+                .set("fill", gradient.compute(&node.coordinates).to_web_string())
+                .set("d", data);
+            self.document.append(path);
+        }
+    }
+
+    fn solve(&mut self, stroke_colour: WebColour) {
+        let segments = self.solution.segments();
+        let main_colour = way_colour(self.solution, stroke_colour, 0);
+        for (i, segment) in segments.iter().enumerate() {
+            let colour = segment_colour(main_colour, i, segments.len());
+            self.trace(segment, colour, i == 0, i == segments.len() - 1);
+        }
+        for (i, way) in self.solution.pairs.iter().enumerate() {
+            let colour = way_colour(self.solution, stroke_colour, i + 1);
+            self.trace(way, colour, true, true);
+        }
     }
 
     fn paint(&mut self, border: WebColour) {
+        let grid = &self.grid;
+        if let Some(terrain) = paint_terrain(grid.maze, border, grid.ring_height, |data, node| {
+            let outer = grid.outer_radius(node.row);
+            if node.row == 0 {
+                // the centre is a whole circle
+                let (x, y) = (grid.centre.x, grid.centre.y);
+                data.append(Command::Move(Absolute, (x - outer, y).into()));
+                for end in [x + outer, x - outer] {
+                    data.append(EllipticalArc(
+                        Absolute,
+                        (outer, outer, 0, 0, 0, end, y).into(),
+                    ));
+                }
+                return;
+            }
+            let inner = grid.inner_radius(node.row);
+            let c = grid.compute_cell(node);
+            data.append(Command::Move(Absolute, (c.ax, c.ay).into()));
+            data.append(Command::Line(Absolute, (c.bx, c.by).into()));
+            data.append(EllipticalArc(
+                Absolute,
+                (outer, outer, 0, 0, 0, c.dx, c.dy).into(),
+            ));
+            data.append(Command::Line(Absolute, (c.cx, c.cy).into()));
+            data.append(EllipticalArc(
+                Absolute,
+                (inner, inner, 0, 0, 1, c.ax, c.ay).into(),
+            ));
+        }) {
+            self.document.append(terrain);
+        }
         let mut data = Data::new();
         for node in self.grid.maze.cells.iter() {
             Self::render_cell(&mut data, &self.grid, node);
@@ -374,6 +467,42 @@ impl MazeRenderer<RingMaze> for RingMazeRenderer<'_> {
             .set("d", data)
             .set("stroke-width", self.stroke_width);
         self.document.append(path);
+
+        let mut arrows = Data::new();
+        for (node, neighbour) in one_way_passages(self.grid.maze) {
+            let from = self.polar(&node).to_cartesian(self.grid.centre);
+            let to = self.polar(&neighbour).to_cartesian(self.grid.centre);
+            arrow(
+                &mut arrows,
+                midpoint((from.x, from.y), (to.x, to.y)),
+                (to.x - from.x, to.y - from.y),
+                self.grid.ring_height / 3.0,
+            );
+        }
+        fill_arrows(&mut self.document, arrows, border);
+        if let Some(portals) =
+            paint_portals(self.grid.maze, border, self.grid.ring_height, |node| {
+                self.centre(node)
+            })
+        {
+            self.document.append(portals);
+        }
+        if let Some(checkpoints) = paint_checkpoints(
+            &self.solution.checkpoints,
+            border,
+            self.grid.ring_height,
+            |node| self.centre(node),
+        ) {
+            self.document.append(checkpoints);
+        }
+        if let Some(locks) = paint_locks(self.grid.maze, self.grid.ring_height, |node| {
+            self.centre(node)
+        }) {
+            self.document.append(locks);
+        }
+        if let Some(pairs) = paint_pairs(&self.pairs(), self.grid.ring_height) {
+            self.document.append(pairs);
+        }
     }
 
     fn render(self) -> RenderedMaze {
@@ -0,0 +1,137 @@
+use thiserror::Error;
+
+use super::interface::Maze;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum PictureError {
+    #[error("the picture is empty")]
+    Empty,
+    #[error("a {0}×{1} picture needs {2} pixels, but it has {3}")]
+    WrongSize(usize, usize, usize, usize),
+}
+
+#[derive(serde::Deserialize)]
+struct RawPicture {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+    #[serde(default)]
+    stain: bool,
+}
+
+/// A grayscale picture for `kruskal` and `jarník` to trace: passages in the
+/// bright parts tend to become long corridors, while the dark parts fill up
+/// with short dead ends. Like masks, pictures get stretched over the maze.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(try_from = "RawPicture")]
+pub struct Picture {
+    width: usize,
+    height: usize,
+    // row by row, 0 is black and 255 white
+    pixels: Vec<u8>,
+    /// Stain the maze with the picture's shades instead of each cell's
+    /// distance from the entrance.
+    pub stain: bool,
+}
+
+impl TryFrom<RawPicture> for Picture {
+    type Error = PictureError;
+
+    fn try_from(raw: RawPicture) -> Result<Self, Self::Error> {
+        Ok(Self {
+            stain: raw.stain,
+            ..Self::new(raw.width, raw.height, raw.pixels)?
+        })
+    }
+}
+
+impl Picture {
+    pub fn new(width: usize, height: usize, pixels: Vec<u8>) -> Result<Self, PictureError> {
+        if width == 0 || height == 0 {
+            return Err(PictureError::Empty);
+        }
+        if pixels.len() != width * height {
+            return Err(PictureError::WrongSize(
+                width,
+                height,
+                width * height,
+                pixels.len(),
+            ));
+        }
+        Ok(Self {
+            width,
+            height,
+            pixels,
+            stain: false,
+        })
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    /// Row by row, 0 is black and 255 white.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// The pixel at a position given as a fraction of the picture's width and
+    /// height, see [`Maze::get_position`].
+    pub fn brightness(&self, (x, y): (f64, f64)) -> u8 {
+        let column = ((x * self.width as f64) as usize).min(self.width - 1);
+        let row = ((y * self.height as f64) as usize).min(self.height - 1);
+        self.pixels[row * self.width + column]
+    }
+
+    /// How dark the picture is at each cell, indexed by `Maze::get_index`
+    /// like `Solution::distances`, so that the stain can use it instead.
+    pub fn shades<M: Maze>(&self, maze: &M) -> Vec<usize> {
+        let mut shades = vec![0; maze.get_index_bound()];
+        for node in maze.get_all_nodes() {
+            // never 0, so that the gradient has something to go on
+            shades[maze.get_index(node)] = 256 - self.brightness(maze.get_position(node)) as usize;
+        }
+        shades
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Picture, PictureError, RawPicture};
+    use crate::maze::{interface::Maze, shape::regular::RectilinearMaze};
+
+    #[test]
+    fn pictures_stretch_over_the_maze() {
+        #[rustfmt::skip]
+        let picture = Picture::new(2, 2, vec![
+            0, 100,
+            200, 255,
+        ])
+        .unwrap();
+        let maze = RectilinearMaze::new((4, 4));
+        assert_eq!(picture.brightness(maze.get_position((1, 1))), 0);
+        assert_eq!(picture.brightness(maze.get_position((2, 0))), 100);
+        assert_eq!(picture.brightness(maze.get_position((0, 3))), 200);
+        assert_eq!(picture.brightness(maze.get_position((3, 3))), 255);
+        let shades = picture.shades(&maze);
+        assert_eq!(shades[maze.get_index((0, 0))], 256);
+        assert_eq!(shades[maze.get_index((3, 2))], 1);
+    }
+
+    #[test]
+    fn pictures_need_as_many_pixels_as_they_are_big() {
+        assert_eq!(Picture::new(0, 3, vec![]), Err(PictureError::Empty));
+        assert_eq!(
+            Picture::new(2, 3, vec![0; 5]),
+            Err(PictureError::WrongSize(2, 3, 6, 5))
+        );
+        let raw = |pixels| RawPicture {
+            width: 1,
+            height: 2,
+            pixels,
+            stain: true,
+        };
+        assert!(Picture::try_from(raw(vec![0, 255])).unwrap().stain);
+        assert!(Picture::try_from(raw(vec![0])).is_err());
+    }
+}
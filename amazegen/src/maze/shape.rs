@@ -1,5 +1,9 @@
 pub mod coordinates;
 pub mod delta;
+pub mod layered;
+pub mod mask;
+pub mod overlay;
 pub mod regular;
 pub mod sigma;
 pub mod theta;
+pub mod wrap;
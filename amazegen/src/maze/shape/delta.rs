@@ -3,14 +3,18 @@ use std::ops::Index;
 use itertools::Itertools;
 
 use crate::maze::{
-    algorithms::{dijkstra, find_path},
+    algorithms::{dijkstra, dijkstra_to, pairs, solve_puzzle},
     arengee::Arengee,
-    interface::{Maze, Solution},
+    interface::{Axis, Maze, Puzzle, Solution},
 };
 
-use super::coordinates::Cartesian;
+use super::{
+    coordinates::Cartesian,
+    mask::{check_mask, openings, Mask, MaskCell, MaskError},
+    overlay::Overlay,
+};
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Direction {
     ALPHA,
     WEST,
@@ -24,6 +28,12 @@ struct Neighbours {
     east: Option<Cartesian<u32>>,
 }
 
+impl Direction {
+    pub fn all() -> [Direction; 3] {
+        [Direction::ALPHA, Direction::WEST, Direction::EAST]
+    }
+}
+
 impl Neighbours {
     fn new() -> Self {
         Self {
@@ -142,6 +152,27 @@ impl DeltaCell {
             }
         });
     }
+
+    fn build_wall(&mut self, neighbour: Cartesian<u32>) {
+        self.accessible.alpha.inspect(|alpha| {
+            if alpha == &neighbour {
+                self.accessible.alpha = None;
+                self.inaccessible.alpha = Some(neighbour);
+            }
+        });
+        self.accessible.west.inspect(|west| {
+            if west == &neighbour {
+                self.accessible.west = None;
+                self.inaccessible.west = Some(neighbour);
+            }
+        });
+        self.accessible.east.inspect(|east| {
+            if east == &neighbour {
+                self.accessible.east = None;
+                self.inaccessible.east = Some(neighbour);
+            }
+        });
+    }
 }
 
 #[derive(Debug)]
@@ -149,6 +180,8 @@ pub struct DeltaMaze {
     width: u32,
     height: u32,
     cells: Vec<DeltaCell>,
+    mask: Vec<MaskCell>,
+    overlay: Overlay<Cartesian<u32>>,
 }
 
 impl DeltaMaze {
@@ -165,10 +198,82 @@ impl DeltaMaze {
         Self {
             width,
             height,
+            mask: vec![MaskCell::Cell; cells.len()],
             cells,
+            overlay: Overlay::default(),
         }
     }
 
+    /// Remove the cells that `mask` leaves out. Must be called before
+    /// generating the maze. Fails if there is no cell left that the entrance
+    /// or exit could lead through.
+    pub fn apply_mask(&mut self, mask: &Mask) -> Result<(), MaskError> {
+        let width = self.width;
+        self.mask = mask.sample(self.width as usize, self.height as usize);
+        for (cell, kind) in self.cells.iter_mut().zip(self.mask.iter()) {
+            let neighbours = &mut cell.inaccessible;
+            for neighbour in [
+                &mut neighbours.alpha,
+                &mut neighbours.west,
+                &mut neighbours.east,
+            ] {
+                if !kind.is_cell()
+                    || neighbour
+                        .is_some_and(|n| !self.mask[n.regular_index(width) as usize].is_cell())
+                {
+                    *neighbour = None;
+                }
+            }
+        }
+        check_mask(self)?;
+        if self.get_possible_entrances().is_empty() || self.get_possible_exits().is_empty() {
+            return Err(MaskError::NoEntrance);
+        }
+        Ok(())
+    }
+
+    fn is_enabled(&self, coords: Cartesian<u32>) -> bool {
+        self.mask[coords.regular_index(self.width) as usize].is_cell()
+    }
+
+    /// Top cells that are the topmost cell of their column, so that the
+    /// entrance can lead into them from above.
+    fn get_possible_entrances(&self) -> Vec<Cartesian<u32>> {
+        let candidates = (0..self.width)
+            .filter_map(|x| {
+                (0..self.height)
+                    .map(|y| Cartesian::new(x, y))
+                    .find(|c| self.is_enabled(*c))
+            })
+            .filter(|c| is_top(*c))
+            .collect_vec();
+        openings(
+            &self.mask,
+            candidates,
+            |c| self.get_index(c),
+            MaskCell::Start,
+        )
+    }
+
+    /// Bottom cells that are the bottommost cell of their column.
+    fn get_possible_exits(&self) -> Vec<Cartesian<u32>> {
+        let candidates = (0..self.width)
+            .filter_map(|x| {
+                (0..self.height)
+                    .rev()
+                    .map(|y| Cartesian::new(x, y))
+                    .find(|c| self.is_enabled(*c))
+            })
+            .filter(|c| !is_top(*c))
+            .collect_vec();
+        openings(
+            &self.mask,
+            candidates,
+            |c| self.get_index(c),
+            MaskCell::Finish,
+        )
+    }
+
     /// May only be called on a top cell
     fn set_entrance(&mut self, coords: Cartesian<u32>) {
         let index = coords.regular_index(self.width) as usize;
@@ -180,6 +285,50 @@ impl DeltaMaze {
         self.cells[index].accessible.alpha = Some(coords);
     }
 
+    /// Whether the side of `cell` in `direction` is on the border of the
+    /// maze, i.e. there's no cell on the other side, or it's been masked out.
+    fn faces_outside(&self, cell: Cartesian<u32>, direction: Direction) -> bool {
+        let cell = &self.cells[cell.regular_index(self.width) as usize];
+        cell.accessible[direction].is_none() && cell.inaccessible[direction].is_none()
+    }
+
+    /// Every cell with a side on the border, where a pair of the solution
+    /// can start or finish.
+    fn get_edge_cells(&self) -> Vec<Cartesian<u32>> {
+        self.get_all_nodes()
+            .into_iter()
+            .filter(|c| {
+                Direction::all()
+                    .into_iter()
+                    .any(|d| self.faces_outside(*c, d))
+            })
+            .collect()
+    }
+
+    /// Like the entrance, but through any side on the border.
+    fn open(&mut self, coords: Cartesian<u32>, rng: &mut Arengee) {
+        let ways = Direction::all()
+            .into_iter()
+            .filter(|d| self.faces_outside(coords, *d))
+            .collect_vec();
+        let index = coords.regular_index(self.width) as usize;
+        let accessible = &mut self.cells[index].accessible;
+        match rng.choice(&ways) {
+            Direction::ALPHA => accessible.alpha = Some(coords),
+            Direction::WEST => accessible.west = Some(coords),
+            Direction::EAST => accessible.east = Some(coords),
+        }
+    }
+
+    /// The side of `cell` that leads out of the maze, if it has been opened
+    /// for a way in or out.
+    pub fn opening(&self, cell: Cartesian<u32>) -> Option<Direction> {
+        let accessible = &self.cells[cell.regular_index(self.width) as usize].accessible;
+        Direction::all()
+            .into_iter()
+            .find(|d| accessible[*d] == Some(cell))
+    }
+
     pub fn has_path(&self, a: &Cartesian<u32>, direction: Direction) -> bool {
         let cell = &self.cells[a.regular_index(self.width) as usize];
         cell.accessible[direction].is_some()
@@ -198,6 +347,15 @@ impl Maze for DeltaMaze {
         let b = neighbour.regular_index(self.width);
         self.cells[a as usize].carve(neighbour);
         self.cells[b as usize].carve(node);
+        self.overlay.clear_one_way(node, neighbour);
+    }
+
+    fn build_wall(&mut self, node: Self::Idx, neighbour: Self::Idx) {
+        let a = node.regular_index(self.width);
+        let b = neighbour.regular_index(self.width);
+        self.cells[a as usize].build_wall(neighbour);
+        self.cells[b as usize].build_wall(node);
+        self.overlay.clear_one_way(node, neighbour);
     }
 
     fn get_walls(&self, node: Self::Idx) -> Vec<Self::Idx> {
@@ -224,8 +382,39 @@ impl Maze for DeltaMaze {
         .collect()
     }
 
+    fn overlay(&self) -> &Overlay<Self::Idx> {
+        &self.overlay
+    }
+
+    fn overlay_mut(&mut self) -> &mut Overlay<Self::Idx> {
+        &mut self.overlay
+    }
+
+    /// Neighbouring triangles overlap by half their width.
+    fn get_position(&self, node: Self::Idx) -> (f64, f64) {
+        (
+            (node.x() as f64 + 1.0) / (self.width as f64 + 1.0),
+            (node.y() as f64 + 0.5) / self.height as f64,
+        )
+    }
+
+    /// Triangles share their left and right sides with the cells in the same
+    /// row, and their top or bottom with one in the row above or below.
+    fn get_axis(&self, node: Self::Idx, neighbour: Self::Idx) -> Option<Axis> {
+        Some(if node.y() == neighbour.y() {
+            Axis::Horizontal
+        } else {
+            Axis::Vertical
+        })
+    }
+
     fn get_random_node(&self, rng: &mut Arengee) -> Self::Idx {
-        self.cells[rng.get_portable_usize(0..self.cells.len())].coordinates
+        loop {
+            let node = self.cells[rng.get_portable_usize(0..self.cells.len())].coordinates;
+            if self.is_enabled(node) {
+                return node;
+            }
+        }
     }
 
     fn get_all_edges(&self) -> Vec<(Self::Idx, Self::Idx)> {
@@ -234,39 +423,40 @@ impl Maze for DeltaMaze {
             .flat_map(|c| {
                 let mut edges = Vec::with_capacity(3);
                 if !is_top(c.coordinates) {
-                    c.inaccessible
-                        .alpha
-                        .map(|alpha| edges.push((c.coordinates, alpha)));
+                    if let Some(alpha) = c.inaccessible.alpha {
+                        edges.push((c.coordinates, alpha));
+                    }
+                }
+                if let Some(west) = c.inaccessible.west {
+                    edges.push((c.coordinates, west));
+                }
+                if let Some(east) = c.inaccessible.east {
+                    edges.push((c.coordinates, east));
                 }
-                c.inaccessible
-                    .west
-                    .map(|west| edges.push((c.coordinates, west)));
-                c.inaccessible
-                    .east
-                    .map(|east| edges.push((c.coordinates, east)));
                 edges
             })
             .collect()
     }
 
     fn get_all_nodes(&self) -> Vec<Self::Idx> {
-        self.cells.iter().map(|c| c.coordinates).collect()
+        self.cells
+            .iter()
+            .map(|c| c.coordinates)
+            .filter(|c| self.is_enabled(*c))
+            .collect()
     }
 
     fn get_index(&self, node: Self::Idx) -> usize {
         node.regular_index(self.width) as usize
     }
 
-    fn make_solution(&mut self, rng: &mut Arengee) -> Solution<Self::Idx> {
-        let possible_entrances = (0..self.width)
-            .map(|x| Cartesian::new(x, 0))
-            .filter(|c| is_top(*c))
-            .collect_vec();
+    fn get_index_bound(&self) -> usize {
+        self.cells.len()
+    }
 
-        let possible_exits = (0..self.width)
-            .map(|x| Cartesian::new(x, self.height - 1))
-            .filter(|c| !is_top(*c))
-            .collect_vec();
+    fn make_solution(&mut self, rng: &mut Arengee, puzzle: Puzzle) -> Solution<Self::Idx> {
+        let possible_entrances = self.get_possible_entrances();
+        let possible_exits = self.get_possible_exits();
 
         let seed_topo = dijkstra(self, *rng.choice(&possible_entrances));
         let exit: Cartesian<u32> = *possible_exits
@@ -274,7 +464,7 @@ impl Maze for DeltaMaze {
             .max_by_key(|c| seed_topo.get(self.get_index(**c)))
             .unwrap_or_else(|| rng.choice(&possible_exits));
 
-        let exit_topo = dijkstra(self, exit);
+        let exit_topo = dijkstra_to(self, exit);
         let entrance: Cartesian<u32> = *possible_entrances
             .iter()
             .max_by_key(|c| exit_topo.get(self.get_index(**c)))
@@ -283,11 +473,26 @@ impl Maze for DeltaMaze {
         let entrance_topo = dijkstra(self, entrance);
         self.set_entrance(entrance);
         self.set_exit(exit);
-        let path = find_path(self, &exit_topo, entrance, exit);
+        let (path, checkpoints) = solve_puzzle(self, rng, puzzle, entrance, exit);
+        let edge = self.get_edge_cells();
+        let pairs = pairs(
+            self,
+            rng,
+            puzzle.pairs.saturating_sub(1),
+            &path,
+            &edge,
+            &edge,
+        );
+        for way in pairs.iter() {
+            self.open(way[0], rng);
+            self.open(way[way.len() - 1], rng);
+        }
 
         Solution {
             path,
             distances: entrance_topo,
+            checkpoints,
+            pairs,
         }
     }
 }
@@ -295,14 +500,19 @@ impl Maze for DeltaMaze {
 #[cfg(test)]
 mod test {
     use super::DeltaMaze;
-    use crate::maze::{algorithms::jarník, arengee::Arengee, interface::Maze};
+    use crate::maze::{
+        algorithms::{jarník, Selection, Weights},
+        arengee::Arengee,
+        interface::{Maze, Puzzle},
+    };
 
     #[test]
     fn maze_template_creation_is_correct() {
         let maze = DeltaMaze::new(3);
-        assert_eq!(maze.get_all_nodes().len(), 9);
+        assert_eq!(maze.get_size(), (4, 3));
+        assert_eq!(maze.get_all_nodes().len(), 12);
         [0, 1, 2].iter().for_each(|y| {
-            let westest_cell = &maze.cells[maze.get_index((0, *y).into()) as usize];
+            let westest_cell = &maze.cells[maze.get_index((0, *y).into())];
             assert_eq!(
                 westest_cell.inaccessible.west, None,
                 "{:?} shouldn't have a western neighbour",
@@ -314,24 +524,24 @@ mod test {
                 "{:?} should have an eastern neighbour",
                 westest_cell
             );
-            let eastest_cell = &maze.cells[maze.get_index((2, *y).into()) as usize];
+            let eastest_cell = &maze.cells[maze.get_index((3, *y).into())];
             assert_eq!(
                 eastest_cell.inaccessible.east,
                 None,
                 "{:?}{:?}{:?} shouldn't have an eastern neighbour",
-                (2, *y),
-                maze.get_index((2, *y).into()),
+                (3, *y),
+                maze.get_index((3, *y).into()),
                 eastest_cell
             );
             assert_eq!(
                 eastest_cell.inaccessible.west,
-                Some((1, *y).into()),
+                Some((2, *y).into()),
                 "{:?} should have a western neighbour",
                 eastest_cell
             );
         });
         [(0, 0), (2, 0), (1, 2)].iter().for_each(|&(x, y)| {
-            let cell = &maze.cells[maze.get_index((x, y).into()) as usize];
+            let cell = &maze.cells[maze.get_index((x, y).into())];
             assert_eq!(
                 cell.inaccessible.alpha, None,
                 "{:?} shouldn't have alpha neighbour",
@@ -341,7 +551,7 @@ mod test {
         [(1, 0), (0, 1), (1, 1), (2, 1), (0, 2), (2, 2)]
             .iter()
             .for_each(|&(x, y)| {
-                let cell = &maze.cells[maze.get_index((x, y).into()) as usize];
+                let cell = &maze.cells[maze.get_index((x, y).into())];
                 assert!(
                     cell.inaccessible.alpha.is_some(),
                     "{:?} should have alpha neighbour",
@@ -355,8 +565,13 @@ mod test {
         let mut rng = Arengee::new(1);
         let maze_template = DeltaMaze::new(10);
         println!("{:?}", maze_template);
-        let mut maze = jarník(maze_template, &mut rng);
-        let solution = maze.make_solution(&mut rng);
+        let mut maze = jarník(
+            maze_template,
+            &mut rng,
+            &Selection::default(),
+            Weights::default(),
+        );
+        let solution = maze.make_solution(&mut rng, Puzzle::default());
         let entrance = solution.path[0];
         assert_eq!(entrance.y(), 0);
         let exit = solution.path.last().unwrap();
@@ -0,0 +1,258 @@
+use crate::maze::algorithms::{dijkstra, dijkstra_to, solve_puzzle};
+use crate::maze::arengee::Arengee;
+use crate::maze::interface::{Axis, Maze, Puzzle, Solution};
+
+use super::overlay::Overlay;
+use super::regular::RectilinearMaze;
+
+type Cell = (usize, usize, usize);
+
+/// A stack of rectilinear mazes, one per level, connected by stairs. Cells are
+/// indexed by `(x, y, level)`, where level 0 is at the bottom. Stairs lead
+/// from a cell to the one right above it.
+#[derive(Debug, Clone)]
+pub struct LayeredMaze {
+    levels: Vec<RectilinearMaze>,
+    // indexed by the lower end of the stairs
+    stairs: Vec<bool>,
+    // covers all levels, the levels' own overlays stay empty
+    overlay: Overlay<Cell>,
+    pub extents: (usize, usize),
+}
+
+impl LayeredMaze {
+    pub fn new(extents: (usize, usize), levels: usize) -> Self {
+        let levels = (0..levels.max(1))
+            .map(|_| RectilinearMaze::new(extents))
+            .collect::<Vec<_>>();
+        let extents = levels[0].get_extents();
+        Self {
+            stairs: vec![false; extents.0 * extents.1 * levels.len()],
+            levels,
+            extents,
+            overlay: Overlay::default(),
+        }
+    }
+
+    pub fn level(&self, level: usize) -> &RectilinearMaze {
+        &self.levels[level]
+    }
+
+    pub fn level_count(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// Whether there are stairs that can be walked up from `node`.
+    pub fn has_stairs_up(&self, (x, y, z): (usize, usize, usize)) -> bool {
+        self.stairs[self.get_index((x, y, z))] && !self.overlay.is_one_way((x, y, z + 1), (x, y, z))
+    }
+
+    /// Whether there are stairs that can be walked down from `node`.
+    pub fn has_stairs_down(&self, (x, y, z): (usize, usize, usize)) -> bool {
+        z > 0
+            && self.stairs[self.get_index((x, y, z - 1))]
+            && !self.overlay.is_one_way((x, y, z - 1), (x, y, z))
+    }
+
+    fn vertical_neighbours(&self, (x, y, z): (usize, usize, usize)) -> Vec<(usize, usize, usize)> {
+        let mut neighbours = vec![];
+        if z > 0 {
+            neighbours.push((x, y, z - 1));
+        }
+        if z < self.levels.len() - 1 {
+            neighbours.push((x, y, z + 1));
+        }
+        neighbours
+    }
+
+    /// The lower end of the stairs between two cells, if they're on top of
+    /// each other.
+    fn stairs_between(
+        (ax, ay, az): (usize, usize, usize),
+        (bx, by, bz): (usize, usize, usize),
+    ) -> Option<(usize, usize, usize)> {
+        (ax == bx && ay == by && az.abs_diff(bz) == 1).then_some((ax, ay, az.min(bz)))
+    }
+
+    fn is_connected(&self, a: (usize, usize, usize), b: (usize, usize, usize)) -> bool {
+        Self::stairs_between(a, b).is_some_and(|lower| self.stairs[self.get_index(lower)])
+    }
+}
+
+impl Maze for LayeredMaze {
+    type Idx = (usize, usize, usize);
+
+    fn carve(&mut self, a: Self::Idx, b: Self::Idx) {
+        match Self::stairs_between(a, b) {
+            Some(lower) => {
+                let index = self.get_index(lower);
+                self.stairs[index] = true;
+            }
+            None => self.levels[a.2].carve((a.0, a.1), (b.0, b.1)),
+        }
+        self.overlay.clear_one_way(a, b);
+    }
+
+    fn build_wall(&mut self, a: Self::Idx, b: Self::Idx) {
+        match Self::stairs_between(a, b) {
+            Some(lower) => {
+                let index = self.get_index(lower);
+                self.stairs[index] = false;
+            }
+            None => self.levels[a.2].build_wall((a.0, a.1), (b.0, b.1)),
+        }
+        self.overlay.clear_one_way(a, b);
+    }
+
+    fn get_walls(&self, (x, y, z): Self::Idx) -> Vec<Self::Idx> {
+        let mut walls = self.levels[z]
+            .get_walls((x, y))
+            .into_iter()
+            .map(|(x, y)| (x, y, z))
+            .collect::<Vec<_>>();
+        walls.extend(
+            self.vertical_neighbours((x, y, z))
+                .into_iter()
+                .filter(|n| !self.is_connected((x, y, z), *n)),
+        );
+        walls
+    }
+
+    fn get_paths(&self, (x, y, z): Self::Idx) -> Vec<Self::Idx> {
+        let mut paths = self.levels[z]
+            .get_paths((x, y))
+            .into_iter()
+            .map(|(x, y)| (x, y, z))
+            .collect::<Vec<_>>();
+        paths.extend(
+            self.vertical_neighbours((x, y, z))
+                .into_iter()
+                .filter(|n| self.is_connected((x, y, z), *n)),
+        );
+        paths
+    }
+
+    fn overlay(&self) -> &Overlay<Self::Idx> {
+        &self.overlay
+    }
+
+    fn overlay_mut(&mut self) -> &mut Overlay<Self::Idx> {
+        &mut self.overlay
+    }
+
+    /// Every level is drawn on its own, so they all have the same positions.
+    fn get_position(&self, (x, y, z): Self::Idx) -> (f64, f64) {
+        self.levels[z].get_position((x, y))
+    }
+
+    /// Stairs are neither horizontal nor vertical.
+    fn get_axis(&self, (x, y, z): Self::Idx, (nx, ny, nz): Self::Idx) -> Option<Axis> {
+        (z == nz)
+            .then(|| self.levels[z].get_axis((x, y), (nx, ny)))
+            .flatten()
+    }
+
+    fn get_random_node(&self, rng: &mut Arengee) -> Self::Idx {
+        let (x, y) = self.levels[0].get_random_node(rng);
+        (x, y, rng.get_portable_usize(0..self.levels.len()))
+    }
+
+    fn get_all_edges(&self) -> Vec<(Self::Idx, Self::Idx)> {
+        let mut edges = vec![];
+        for (z, level) in self.levels.iter().enumerate() {
+            edges.extend(
+                level
+                    .get_all_edges()
+                    .into_iter()
+                    .map(|((ax, ay), (bx, by))| ((ax, ay, z), (bx, by, z))),
+            );
+        }
+        for z in 1..self.levels.len() {
+            edges.extend(
+                self.levels[0]
+                    .get_all_nodes()
+                    .into_iter()
+                    .map(|(x, y)| ((x, y, z - 1), (x, y, z))),
+            );
+        }
+        edges
+    }
+
+    fn get_all_nodes(&self) -> Vec<Self::Idx> {
+        (0..self.levels.len())
+            .flat_map(|z| {
+                self.levels[z]
+                    .get_all_nodes()
+                    .into_iter()
+                    .map(move |(x, y)| (x, y, z))
+            })
+            .collect()
+    }
+
+    fn get_index(&self, (x, y, z): Self::Idx) -> usize {
+        let (width, height) = self.extents;
+        (z * height + y) * width + x
+    }
+
+    /// The entrance is at the top of the lowest level, the exit at the
+    /// bottom of the highest one.
+    fn make_solution(&mut self, rng: &mut Arengee, puzzle: Puzzle) -> Solution<Self::Idx> {
+        let (width, height) = self.extents;
+        let top = self.levels.len() - 1;
+        let seed_topo = dijkstra(self, (rng.get_portable_usize(0..width), 0, 0));
+        let exit = (0..width)
+            .map(|x| (x, height - 1, top))
+            .max_by_key(|node| seed_topo[self.get_index(*node)])
+            .unwrap();
+        let exit_topo = dijkstra_to(self, exit);
+        let entrance = (0..width)
+            .map(|x| (x, 0, 0))
+            .max_by_key(|node| exit_topo[self.get_index(*node)])
+            .unwrap();
+        let entrance_topo = dijkstra(self, entrance);
+
+        self.levels[0].set_entrance((entrance.0, entrance.1));
+        self.levels[top].set_exit((exit.0, exit.1));
+
+        let (path, checkpoints) = solve_puzzle(self, rng, puzzle, entrance, exit);
+        Solution {
+            path,
+            distances: entrance_topo,
+            checkpoints,
+            pairs: vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::LayeredMaze;
+    use crate::maze::interface::Maze;
+
+    #[test]
+    fn stairs_connect_cells_on_top_of_each_other() {
+        let mut m = LayeredMaze::new((4, 4), 3);
+        m.carve((1, 2, 1), (1, 2, 2));
+        assert!(m.has_stairs_up((1, 2, 1)));
+        assert!(m.has_stairs_down((1, 2, 2)));
+        assert!(!m.has_stairs_down((1, 2, 1)));
+        assert_eq!(m.get_paths((1, 2, 2)), vec![(1, 2, 1)]);
+        assert!(m.get_walls((1, 2, 1)).contains(&(1, 2, 0)));
+        m.build_wall((1, 2, 2), (1, 2, 1));
+        assert!(!m.has_stairs_up((1, 2, 1)));
+    }
+
+    #[test]
+    fn every_cell_has_a_unique_index() {
+        let m = LayeredMaze::new((4, 3), 3);
+        let mut indices = m
+            .get_all_nodes()
+            .into_iter()
+            .map(|n| m.get_index(n))
+            .collect::<Vec<_>>();
+        indices.sort();
+        assert_eq!(indices, (0..36).collect::<Vec<_>>());
+        // 4x3 grid has 17 edges per level, plus 12 stairs between each level
+        assert_eq!(m.get_all_edges().len(), 3 * 17 + 2 * 12);
+    }
+}
@@ -0,0 +1,540 @@
+use std::collections::VecDeque;
+
+use thiserror::Error;
+
+use crate::maze::interface::Maze;
+
+/// Which cells of a maze exist. A mask gets stretched over the maze, so it
+/// doesn't need to have the maze's size: every cell looks at the spot of the
+/// mask that lies under its centre.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Mask {
+    width: usize,
+    height: usize,
+    // row by row
+    cells: Vec<MaskCell>,
+}
+
+/// If a mask has any `Start` cells, the entrance must lead into one of them,
+/// and likewise for `Finish` cells and the exit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaskCell {
+    Hole,
+    Cell,
+    Start,
+    Finish,
+}
+
+impl MaskCell {
+    /// Whether the cell is part of the maze.
+    pub fn is_cell(self) -> bool {
+        self != MaskCell::Hole
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum MaskError {
+    #[error("the mask doesn't have any cells")]
+    Empty,
+    #[error("row {0} of the mask isn't as long as the first one")]
+    UnevenRows(usize),
+    #[error("the mask contains {0:?}, use '.' for cells, 'X' for holes, 'S' for starts and 'F' for finishes")]
+    UnknownCharacter(char),
+    #[error("not a PBM image: {0}")]
+    InvalidPbm(&'static str),
+    #[error("the mask cuts the maze into pieces")]
+    Disconnected,
+    #[error("the mask leaves no room for an entrance or an exit")]
+    NoEntrance,
+}
+
+impl Mask {
+    /// `cells` goes row by row. Fails unless it has all `width` × `height` of
+    /// them, and at least one isn't a hole.
+    pub fn new(width: usize, height: usize, cells: Vec<MaskCell>) -> Result<Self, MaskError> {
+        if width * height != cells.len() || !cells.iter().any(|c| c.is_cell()) {
+            return Err(MaskError::Empty);
+        }
+        Ok(Self {
+            width,
+            height,
+            cells,
+        })
+    }
+
+    /// One line per row, with `.` for cells, `X` for holes, and `S` and `F`
+    /// for start and finish cells.
+    pub fn from_ascii(text: &str) -> Result<Self, MaskError> {
+        let rows = text
+            .trim_end()
+            .lines()
+            .map(|line| line.trim_end_matches('\r'))
+            .collect::<Vec<_>>();
+        let width = rows.first().map_or(0, |row| row.chars().count());
+        let mut cells = Vec::with_capacity(width * rows.len());
+        for (y, row) in rows.iter().enumerate() {
+            if row.chars().count() != width {
+                return Err(MaskError::UnevenRows(y));
+            }
+            for c in row.chars() {
+                cells.push(match c {
+                    '.' => MaskCell::Cell,
+                    'X' | 'x' => MaskCell::Hole,
+                    'S' => MaskCell::Start,
+                    'F' => MaskCell::Finish,
+                    _ => return Err(MaskError::UnknownCharacter(c)),
+                });
+            }
+        }
+        Self::new(width, rows.len(), cells)
+    }
+
+    /// Reads plain (P1) and raw (P4) PBM images. Black pixels are cells,
+    /// white ones are holes.
+    pub fn from_pbm(data: &[u8]) -> Result<Self, MaskError> {
+        let mut reader = PbmReader { data, position: 0 };
+        let raw = match reader.token()? {
+            b"P1" => false,
+            b"P4" => true,
+            _ => return Err(MaskError::InvalidPbm("unknown magic number")),
+        };
+        let width = reader.number()?;
+        let height = reader.number()?;
+        let cells = if raw {
+            // a single whitespace character separates the header from the bits
+            let start = reader.position + 1;
+            let row_length = width.div_ceil(8);
+            let bits = data
+                .get(start..start + row_length * height)
+                .ok_or(MaskError::InvalidPbm("not enough pixels"))?;
+            (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x, y)))
+                .map(|(x, y)| bits[y * row_length + x / 8] & (0x80 >> (x % 8)) != 0)
+                .map(MaskCell::from)
+                .collect()
+        } else {
+            let mut cells = Vec::with_capacity(width * height);
+            while cells.len() < width * height {
+                reader.skip_whitespace();
+                match data.get(reader.position) {
+                    Some(b'1') => cells.push(MaskCell::Cell),
+                    Some(b'0') => cells.push(MaskCell::Hole),
+                    Some(_) => return Err(MaskError::InvalidPbm("pixels must be 0 or 1")),
+                    None => return Err(MaskError::InvalidPbm("not enough pixels")),
+                }
+                reader.position += 1;
+            }
+            cells
+        };
+        Self::new(width, height, cells)
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    /// Stretch the mask over a grid of `width` × `height` cells. The result
+    /// goes row by row.
+    pub fn sample(&self, width: usize, height: usize) -> Vec<MaskCell> {
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let mx = (2 * x + 1) * self.width / (2 * width);
+                let my = (2 * y + 1) * self.height / (2 * height);
+                self.cells[my * self.width + mx]
+            })
+            .collect()
+    }
+}
+
+impl Mask {
+    /// Connect the pieces of the mask with corridors, one after the other
+    /// from left to right, and make the leftmost piece the start and the
+    /// rightmost one the finish. The pieces form a chain, so the solution
+    /// leads through all of them, e.g. through every letter of a word. A
+    /// piece is a group of cells that are connected horizontally or
+    /// vertically. Small pieces that float above a bigger one, like the dot
+    /// of an i or an accent, get attached to it first.
+    pub fn chain(mut self) -> Self {
+        let mut owner = vec![None; self.cells.len()];
+        let mut pieces = self.pieces(&mut owner);
+        if self.attach_marks(&owner, &pieces) {
+            owner = vec![None; self.cells.len()];
+            pieces = self.pieces(&mut owner);
+        }
+        if pieces.len() < 2 {
+            return self;
+        }
+
+        // the way in is above the first piece and the way out below the last
+        // one, so corridors must keep out of the way
+        let (first, last) = (0, pieces.len() - 1);
+        let mut shadow = vec![false; self.cells.len()];
+        for i in &pieces[first] {
+            self.column_from(*i, true)
+                .for_each(|above| shadow[above] = true);
+        }
+        for i in &pieces[last] {
+            self.column_from(*i, false)
+                .for_each(|below| shadow[below] = true);
+        }
+
+        for (from, to) in (0..pieces.len()).zip(1..pieces.len()) {
+            // the corridor mustn't touch any other piece, or the solution
+            // could take a shortcut past `to`
+            let corridor = self
+                .corridor(&owner, from, to, |i| {
+                    self.cells[i] == MaskCell::Hole
+                        && !shadow[i]
+                        && self
+                            .neighbours(i)
+                            .all(|n| owner[n].is_none_or(|p| p == from || p == to))
+                })
+                .or_else(|| self.corridor(&owner, from, to, |_| true))
+                .expect("Every cell can be reached if the corridor can go anywhere.");
+            for i in corridor {
+                if self.cells[i] == MaskCell::Hole {
+                    self.cells[i] = MaskCell::Cell;
+                    // mark corridors as taken, so that later ones keep away
+                    owner[i] = Some(pieces.len());
+                }
+            }
+        }
+
+        // only cells with nothing above or below them can be opened, and if
+        // a corridor had to go past all of them, any cell will do
+        let starts = pieces[first]
+            .iter()
+            .copied()
+            .filter(|i| {
+                self.column_from(*i, true)
+                    .all(|above| !self.cells[above].is_cell())
+            })
+            .collect::<Vec<_>>();
+        let finishes = pieces[last]
+            .iter()
+            .copied()
+            .filter(|i| {
+                self.column_from(*i, false)
+                    .all(|below| !self.cells[below].is_cell())
+            })
+            .collect::<Vec<_>>();
+        for i in starts {
+            self.cells[i] = MaskCell::Start;
+        }
+        for i in finishes {
+            self.cells[i] = MaskCell::Finish;
+        }
+        self
+    }
+
+    /// Join every piece that floats above a bigger piece to the first one
+    /// below it with a straight corridor. Returns whether it joined any.
+    fn attach_marks(&mut self, owner: &[Option<usize>], pieces: &[Vec<usize>]) -> bool {
+        let mut corridors = vec![];
+        for (mark, cells) in pieces.iter().enumerate() {
+            let below = cells.iter().find_map(|i| {
+                let mut gap = vec![];
+                for below in self.column_from(*i, false) {
+                    match owner[below] {
+                        None => gap.push(below),
+                        Some(piece) if piece == mark => return None,
+                        Some(piece) if pieces[piece].len() > cells.len() => return Some(gap),
+                        Some(_) => return None,
+                    }
+                }
+                None
+            });
+            if let Some(gap) = below {
+                corridors.push(gap);
+            }
+        }
+        for i in corridors.iter().flatten() {
+            self.cells[*i] = MaskCell::Cell;
+        }
+        !corridors.is_empty()
+    }
+
+    /// The cells above or below the cell at `index`, going away from it.
+    fn column_from(&self, index: usize, up: bool) -> impl Iterator<Item = usize> {
+        let (width, x, y) = (self.width, index % self.width, index / self.width);
+        let rows: Box<dyn Iterator<Item = usize>> = if up {
+            Box::new((0..y).rev())
+        } else {
+            Box::new(y + 1..self.height)
+        };
+        rows.map(move |row| row * width + x)
+    }
+
+    /// Find the pieces of the mask, ordered by their leftmost cell, and
+    /// record which piece each cell belongs to in `owner`.
+    fn pieces(&self, owner: &mut [Option<usize>]) -> Vec<Vec<usize>> {
+        let mut pieces = vec![];
+        let by_column = (0..self.width).flat_map(|x| (0..self.height).map(move |y| (x, y)));
+        for start in by_column.map(|(x, y)| y * self.width + x) {
+            if !self.cells[start].is_cell() || owner[start].is_some() {
+                continue;
+            }
+            owner[start] = Some(pieces.len());
+            let mut piece = vec![];
+            let mut stack = vec![start];
+            while let Some(i) = stack.pop() {
+                piece.push(i);
+                for n in self.neighbours(i) {
+                    if self.cells[n].is_cell() && owner[n].is_none() {
+                        owner[n] = Some(pieces.len());
+                        stack.push(n);
+                    }
+                }
+            }
+            pieces.push(piece);
+        }
+        pieces
+    }
+
+    /// The shortest line of `passable` cells from piece `from` to piece `to`.
+    fn corridor(
+        &self,
+        owner: &[Option<usize>],
+        from: usize,
+        to: usize,
+        passable: impl Fn(usize) -> bool,
+    ) -> Option<Vec<usize>> {
+        let mut previous = vec![None; self.cells.len()];
+        let mut queue = (0..self.cells.len())
+            .filter(|i| owner[*i] == Some(from))
+            .collect::<VecDeque<_>>();
+        for i in queue.iter() {
+            previous[*i] = Some(*i);
+        }
+        while let Some(i) = queue.pop_front() {
+            for n in self.neighbours(i) {
+                if owner[n] == Some(to) {
+                    let mut corridor = vec![];
+                    let mut cursor = i;
+                    while owner[cursor] != Some(from) {
+                        corridor.push(cursor);
+                        cursor = previous[cursor].unwrap();
+                    }
+                    return Some(corridor);
+                }
+                if previous[n].is_none() && passable(n) {
+                    previous[n] = Some(i);
+                    queue.push_back(n);
+                }
+            }
+        }
+        None
+    }
+
+    /// The cells above, below, left and right of the cell at `index`.
+    fn neighbours(&self, index: usize) -> impl Iterator<Item = usize> {
+        let (width, height) = (self.width, self.height);
+        let (x, y) = (index % width, index / width);
+        [
+            (y > 0).then(|| index - width),
+            (y + 1 < height).then(|| index + width),
+            (x > 0).then(|| index - 1),
+            (x + 1 < width).then(|| index + 1),
+        ]
+        .into_iter()
+        .flatten()
+    }
+}
+
+impl From<bool> for MaskCell {
+    fn from(is_cell: bool) -> Self {
+        if is_cell {
+            MaskCell::Cell
+        } else {
+            MaskCell::Hole
+        }
+    }
+}
+
+impl TryFrom<String> for Mask {
+    type Error = MaskError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::from_ascii(&value)
+    }
+}
+
+impl From<Mask> for String {
+    fn from(mask: Mask) -> Self {
+        mask.cells
+            .chunks(mask.width)
+            .map(|row| {
+                row.iter()
+                    .map(|c| match c {
+                        MaskCell::Hole => 'X',
+                        MaskCell::Cell => '.',
+                        MaskCell::Start => 'S',
+                        MaskCell::Finish => 'F',
+                    })
+                    .collect()
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+struct PbmReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl PbmReader<'_> {
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.data.get(self.position) {
+            match c {
+                b'#' => {
+                    while self.data.get(self.position).is_some_and(|c| *c != b'\n') {
+                        self.position += 1;
+                    }
+                }
+                c if c.is_ascii_whitespace() => self.position += 1,
+                _ => break,
+            }
+        }
+    }
+
+    fn token(&mut self) -> Result<&[u8], MaskError> {
+        self.skip_whitespace();
+        let start = self.position;
+        while self
+            .data
+            .get(self.position)
+            .is_some_and(|c| !c.is_ascii_whitespace())
+        {
+            self.position += 1;
+        }
+        if start == self.position {
+            return Err(MaskError::InvalidPbm("the header ends early"));
+        }
+        Ok(&self.data[start..self.position])
+    }
+
+    fn number(&mut self) -> Result<usize, MaskError> {
+        std::str::from_utf8(self.token()?)
+            .ok()
+            .and_then(|token| token.parse().ok())
+            .filter(|n| *n > 0)
+            .ok_or(MaskError::InvalidPbm("invalid dimensions"))
+    }
+}
+
+/// Make sure a masked maze still has cells, and that every cell can reach
+/// every other one once all walls are gone. No algorithm could connect the
+/// pieces of a maze that the mask cut apart.
+pub(super) fn check_mask<M: Maze>(maze: &M) -> Result<(), MaskError> {
+    let nodes = maze.get_all_nodes();
+    let Some(first) = nodes.first() else {
+        return Err(MaskError::Empty);
+    };
+    let mut seen = vec![false; maze.get_index_bound()];
+    let mut stack = vec![*first];
+    let mut count = 0;
+    while let Some(node) = stack.pop() {
+        if std::mem::replace(&mut seen[maze.get_index(node)], true) {
+            continue;
+        }
+        count += 1;
+        stack.extend(maze.get_neighbours(node));
+    }
+    if count < nodes.len() {
+        return Err(MaskError::Disconnected);
+    }
+    Ok(())
+}
+
+/// The `candidates` for an entrance or exit that are `opening` cells, or all
+/// of them if the mask doesn't have any such cells.
+pub(super) fn openings<T: Copy>(
+    mask: &[MaskCell],
+    candidates: Vec<T>,
+    index: impl Fn(T) -> usize,
+    opening: MaskCell,
+) -> Vec<T> {
+    if !mask.contains(&opening) {
+        return candidates;
+    }
+    candidates
+        .into_iter()
+        .filter(|c| mask[index(*c)] == opening)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Mask, MaskCell, MaskError};
+    use crate::maze::{
+        algorithms::{kruskal, Weights},
+        arengee::Arengee,
+        interface::{Maze, Puzzle},
+        shape::regular::RectilinearMaze,
+    };
+
+    #[test]
+    fn ascii_masks_round_trip() {
+        let text = "X..X\n....\n.XX.";
+        let mask = Mask::from_ascii(text).unwrap();
+        assert_eq!(String::from(mask.clone()), text);
+        assert_eq!(mask.sample(4, 3), mask.cells);
+        assert!(matches!(
+            Mask::from_ascii("..\n.").unwrap_err(),
+            MaskError::UnevenRows(1)
+        ));
+        assert!(matches!(
+            Mask::from_ascii("XX\nXX").unwrap_err(),
+            MaskError::Empty
+        ));
+    }
+
+    #[test]
+    fn masks_stretch_over_bigger_mazes() {
+        let mask = Mask::from_ascii(".X\nX.").unwrap();
+        let sampled = mask.sample(4, 2);
+        assert_eq!(
+            sampled
+                .into_iter()
+                .map(MaskCell::is_cell)
+                .collect::<Vec<_>>(),
+            vec![true, true, false, false, false, false, true, true]
+        );
+    }
+
+    #[test]
+    fn chained_pieces_lead_from_start_to_finish() {
+        let mask = Mask::from_ascii("..XXX..XXX..\nXXXXXXXXXXXX\nXXXXXXXXXXXX\n..XXX..XXX..")
+            .unwrap()
+            .chain();
+        assert_eq!(
+            String::from(mask.clone()),
+            "SSX....X....\n.X..X.X..X.X\n.X.XX.X.XX.X\n...XX...XXFF"
+        );
+
+        let mut maze = RectilinearMaze::new(mask.size());
+        maze.apply_mask(&mask).unwrap();
+        let mut maze = kruskal(maze, &mut Arengee::new(1), Weights::default());
+        let solution = maze.make_solution(&mut Arengee::new(1), Puzzle::default());
+        for piece in [(0, 0), (0, 3), (5, 0), (5, 3), (10, 0), (10, 3)] {
+            assert!(
+                solution.path.contains(&piece),
+                "The solution should lead through {:?}",
+                piece
+            );
+        }
+    }
+
+    #[test]
+    fn pbm_images_become_masks() {
+        let plain = Mask::from_pbm(b"P1\n# a comment\n3 2\n1 0 1\n111").unwrap();
+        assert_eq!(String::from(plain.clone()), ".X.\n...");
+        let raw = Mask::from_pbm(b"P4 3 2\n\xa0\xe0").unwrap();
+        assert_eq!(raw, plain);
+        assert!(Mask::from_pbm(b"P4 3 2\n\xa0").is_err());
+        assert!(Mask::from_pbm(b"P2 3 2\n").is_err());
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use crate::maze::interface::{Lock, Terrain};
+
+/// Everything that gets laid over the cells and passages of a maze once it
+/// has been carved: one way passages, rough terrain, portals and locked doors.
+/// None of it depends on the shape, so every shape holds one of these and
+/// hands it out through `Maze::overlay`. Most mazes don't have any of it, so
+/// this stays cheap when it's empty.
+#[derive(Debug, Clone)]
+pub struct Overlay<T> {
+    // from the cell a passage may be entered from to the cell it leads to
+    one_way: HashSet<(T, T)>,
+    // cells that aren't plain ground
+    terrain: HashMap<T, Terrain>,
+    portals: Vec<(T, T)>,
+    locks: Vec<Lock<T>>,
+}
+
+impl<T> Default for Overlay<T> {
+    fn default() -> Self {
+        Self {
+            one_way: HashSet::new(),
+            terrain: HashMap::new(),
+            portals: vec![],
+            locks: vec![],
+        }
+    }
+}
+
+impl<T: Copy + Eq + Hash> Overlay<T> {
+    /// Only allow walking the passage between two cells from `from` to `to`,
+    /// even if it could only be walked the other way before.
+    pub fn set_one_way(&mut self, from: T, to: T) {
+        self.one_way.remove(&(to, from));
+        self.one_way.insert((from, to));
+    }
+
+    /// Forget about the direction of the passage between `a` and `b`, e.g.
+    /// because it got carved again or walled off.
+    pub fn clear_one_way(&mut self, a: T, b: T) {
+        if !self.one_way.is_empty() {
+            self.one_way.remove(&(a, b));
+            self.one_way.remove(&(b, a));
+        }
+    }
+
+    /// Whether the passage between two cells can only be walked from `from`
+    /// to `to`.
+    pub fn is_one_way(&self, from: T, to: T) -> bool {
+        !self.one_way.is_empty() && self.one_way.contains(&(from, to))
+    }
+
+    /// What the ground in `node` is like. Every cell starts out as
+    /// `Terrain::Ground`.
+    pub fn get_terrain(&self, node: T) -> Terrain {
+        if self.terrain.is_empty() {
+            return Terrain::Ground;
+        }
+        self.terrain.get(&node).copied().unwrap_or_default()
+    }
+
+    pub fn set_terrain(&mut self, node: T, terrain: Terrain) {
+        if terrain == Terrain::Ground {
+            self.terrain.remove(&node);
+        } else {
+            self.terrain.insert(node, terrain);
+        }
+    }
+
+    /// Link two cells anywhere in the maze with a pair of portals, so that
+    /// stepping into one of them leads straight to the other. A cell should
+    /// have one portal at most.
+    pub fn add_portal(&mut self, node: T, other: T) {
+        self.portals.push((node, other));
+    }
+
+    /// All pairs of portals, in the order they were added.
+    pub fn get_portals(&self) -> &[(T, T)] {
+        &self.portals
+    }
+
+    /// Where the portal in `node` leads, if there is one.
+    pub fn get_portal(&self, node: T) -> Option<T> {
+        self.portals.iter().find_map(|(a, b)| {
+            if *a == node {
+                Some(*b)
+            } else if *b == node {
+                Some(*a)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Lock a door in a passage, see `Lock`. Finding a way through the maze
+    /// then means picking up the key first, see `find_route`.
+    pub fn add_lock(&mut self, lock: Lock<T>) {
+        self.locks.push(lock);
+    }
+
+    /// All locked doors with their keys, in the order they were added.
+    pub fn get_locks(&self) -> &[Lock<T>] {
+        &self.locks
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Overlay;
+    use crate::maze::interface::Terrain;
+
+    #[test]
+    fn passages_only_go_one_way_at_a_time() {
+        let mut overlay = Overlay::default();
+        overlay.set_one_way(1, 2);
+        assert!(overlay.is_one_way(1, 2));
+        assert!(!overlay.is_one_way(2, 1));
+        overlay.set_one_way(2, 1);
+        assert!(overlay.is_one_way(2, 1));
+        assert!(!overlay.is_one_way(1, 2));
+        overlay.clear_one_way(1, 2);
+        assert!(overlay.one_way.is_empty());
+    }
+
+    #[test]
+    fn cells_start_out_as_plain_ground() {
+        let mut overlay = Overlay::default();
+        assert_eq!(overlay.get_terrain(3), Terrain::Ground);
+        overlay.set_terrain(3, Terrain::Mud);
+        assert_eq!(overlay.get_terrain(3), Terrain::Mud);
+        overlay.set_terrain(3, Terrain::Ground);
+        assert!(overlay.terrain.is_empty());
+    }
+}
@@ -1,18 +1,69 @@
 use itertools::Itertools;
 
-use crate::maze::algorithms::find_path;
+use crate::maze::algorithms::{furthest, pairs, solve_puzzle};
 use crate::maze::arengee::Arengee;
-use crate::maze::interface::Solution;
-use crate::maze::{algorithms::dijkstra, interface::Maze};
+use crate::maze::interface::{Axis, Puzzle, Solution, Symmetry};
+use crate::maze::{
+    algorithms::{dijkstra, dijkstra_to},
+    interface::Maze,
+};
+
+use super::mask::{check_mask, openings, Mask, MaskCell, MaskError};
+use super::overlay::Overlay;
+use super::wrap::{Wrap, WrapError};
 
 #[derive(Debug, Clone)]
 pub struct RectilinearMaze {
     fields: std::vec::Vec<Vec<u8>>,
-    entrance: usize,
-    exit: usize,
+    entrance: (usize, usize),
+    exit: (usize, usize),
+    // row by row
+    mask: Vec<MaskCell>,
+    overlay: Overlay<(usize, usize)>,
+    rooms: Vec<Room>,
+    wrap: Wrap,
+    pub extents: (usize, usize),
+}
+
+/// An open area without any walls inside, `extents` cells wide and high, with
+/// its top left cell at `origin`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Room {
+    pub origin: (usize, usize),
     pub extents: (usize, usize),
 }
 
+impl Room {
+    pub fn cells(&self) -> impl Iterator<Item = (usize, usize)> {
+        let (ox, oy) = self.origin;
+        let (w, h) = self.extents;
+        (oy..oy + h).flat_map(move |y| (ox..ox + w).map(move |x| (x, y)))
+    }
+
+    pub fn contains(&self, (x, y): (usize, usize)) -> bool {
+        (self.origin.0..self.origin.0 + self.extents.0).contains(&x)
+            && (self.origin.1..self.origin.1 + self.extents.1).contains(&y)
+    }
+
+    /// Whether the rooms overlap or are less than `gap` cells apart.
+    pub fn is_near(&self, other: &Room, gap: usize) -> bool {
+        let apart = |a: usize, a_len: usize, b: usize, b_len: usize| {
+            a + a_len + gap <= b || b + b_len + gap <= a
+        };
+        !(apart(
+            self.origin.0,
+            self.extents.0,
+            other.origin.0,
+            other.extents.0,
+        ) || apart(
+            self.origin.1,
+            self.extents.1,
+            other.origin.1,
+            other.extents.1,
+        ))
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Direction {
     Up,
@@ -31,6 +82,16 @@ impl Direction {
         }
     }
 
+    /// How far a step in this direction moves along x and y.
+    pub fn offset(&self) -> (isize, isize) {
+        match self {
+            Direction::Left => (-1, 0),
+            Direction::Right => (1, 0),
+            Direction::Up => (0, -1),
+            Direction::Down => (0, 1),
+        }
+    }
+
     fn bitmask(&self) -> u8 {
         match self {
             Direction::Left => LEFT,
@@ -56,25 +117,69 @@ const LEFT: u8 = 2u8;
 const UP: u8 = 4u8;
 const RIGHT: u8 = 8u8;
 const DOWN: u8 = 16u8;
+/// A passage runs beneath this cell, perpendicular to the cell's own passage.
+const UNDER: u8 = 32u8;
 
 impl RectilinearMaze {
     pub fn new(extents: (usize, usize)) -> Self {
         let min_extents = (extents.0.max(2), extents.1.max(2));
         RectilinearMaze {
             extents: min_extents,
-            entrance: 0,
-            exit: 0,
+            entrance: (0, 0),
+            exit: (0, 0),
             fields: vec![vec![0u8; min_extents.1]; min_extents.0],
+            mask: vec![MaskCell::Cell; min_extents.0 * min_extents.1],
+            overlay: Overlay::default(),
+            rooms: vec![],
+            wrap: Wrap::default(),
+        }
+    }
+
+    /// Let the edges of the maze lead around to the opposite edge. Must be
+    /// called before applying a mask or generating the maze.
+    pub fn set_wrap(&mut self, wrap: Wrap) -> Result<(), WrapError> {
+        if (wrap.horizontal && self.extents.0 < 3) || (wrap.vertical && self.extents.1 < 3) {
+            return Err(WrapError::TooSmall);
+        }
+        self.wrap = wrap;
+        Ok(())
+    }
+
+    pub fn get_wrap(&self) -> Wrap {
+        self.wrap
+    }
+
+    /// Remove the cells that `mask` leaves out. Must be called before
+    /// generating the maze.
+    pub fn apply_mask(&mut self, mask: &Mask) -> Result<(), MaskError> {
+        self.mask = mask.sample(self.extents.0, self.extents.1);
+        check_mask(self)?;
+        if self.get_possible_entrances().is_empty() || self.get_possible_exits().is_empty() {
+            return Err(MaskError::NoEntrance);
         }
+        Ok(())
+    }
+
+    pub fn is_masked(&self) -> bool {
+        self.mask.iter().any(|cell| !cell.is_cell())
     }
-    pub fn set_entrance(&mut self, entrance: usize) {
+
+    pub fn is_enabled(&self, node: (usize, usize)) -> bool {
+        self.mask[self.get_index(node)].is_cell()
+    }
+
+    /// The entrance leads into `entrance` from above, so there mustn't be a
+    /// cell above it.
+    pub fn set_entrance(&mut self, entrance: (usize, usize)) {
         self.entrance = entrance;
-        self.remove_wall((entrance, 0), Direction::Up);
+        self.remove_wall(entrance, Direction::Up);
     }
 
-    pub fn set_exit(&mut self, exit: usize) {
+    /// The exit leads out of `exit` downwards, so there mustn't be a cell
+    /// below it.
+    pub fn set_exit(&mut self, exit: (usize, usize)) {
         self.exit = exit;
-        self.remove_wall((exit, self.extents.1 - 1), Direction::Down);
+        self.remove_wall(exit, Direction::Down);
     }
 
     pub fn has_wall(&self, (x, y): (usize, usize), direction: Direction) -> bool {
@@ -85,25 +190,185 @@ impl RectilinearMaze {
         self.fields[x][y] |= direction.bitmask()
     }
 
-    fn translate(&self, (x, y): (usize, usize), direction: Direction) -> Option<(usize, usize)> {
-        match direction {
-            Direction::Left if x > 0 => Some((x - 1, y)),
-            Direction::Right if x < self.extents.0 - 1 => Some((x + 1, y)),
-            Direction::Up if y > 0 => Some((x, y - 1)),
-            Direction::Down if y < self.extents.1 - 1 => Some((x, y + 1)),
-            _ => None,
+    /// The neighbour of a cell in `direction`, unless it's off the grid or
+    /// masked out. Edges that wrap around lead to the other side of the grid.
+    pub fn translate(&self, cell: (usize, usize), direction: Direction) -> Option<(usize, usize)> {
+        self.adjacent(cell, direction)
+            .filter(|neighbour| self.is_enabled(*neighbour))
+    }
+
+    /// Like `translate`, but doesn't care about the mask.
+    fn adjacent(&self, (x, y): (usize, usize), direction: Direction) -> Option<(usize, usize)> {
+        let (dx, dy) = direction.offset();
+        self.wrap
+            .apply((x as isize + dx, y as isize + dy), self.extents)
+    }
+
+    /// Whether the side of a cell in `direction` leads around to the opposite
+    /// edge of the maze.
+    pub fn wraps(&self, (x, y): (usize, usize), direction: Direction) -> bool {
+        let on_edge = match direction {
+            Direction::Left => x == 0,
+            Direction::Right => x == self.extents.0 - 1,
+            Direction::Up => y == 0,
+            Direction::Down => y == self.extents.1 - 1,
+        };
+        on_edge && self.translate((x, y), direction).is_some()
+    }
+
+    /// Like `translate`, but passes under crossings: if the neighbouring
+    /// cell in `direction` has a tunnel running in that direction, we end up
+    /// on the other side of it.
+    pub fn step(&self, (x, y): (usize, usize), direction: Direction) -> Option<(usize, usize)> {
+        let next = self.translate((x, y), direction)?;
+        if self.is_crossing(next) && self.has_wall(next, direction) {
+            self.translate(next, direction)
+        } else {
+            Some(next)
         }
     }
+
+    /// Whether a tunnel passes beneath the cell. The cell's own passage is
+    /// the one without walls, the tunnel runs perpendicular to it.
+    pub fn is_crossing(&self, (x, y): (usize, usize)) -> bool {
+        self.fields[x][y] & UNDER != 0
+    }
+
+    /// Let a passage run straight through the cell in the direction of
+    /// `over`, and a tunnel beneath it that connects the cells on the other
+    /// two sides. Returns false if the cell is on the border and there is
+    /// no room for that.
+    pub fn add_crossing(&mut self, (x, y): (usize, usize), over: Direction) -> bool {
+        let under = match over {
+            Direction::Left | Direction::Right => Direction::Up,
+            Direction::Up | Direction::Down => Direction::Left,
+        };
+        let sides = [over, over.reciprocal(), under, under.reciprocal()];
+        let neighbours = sides
+            .iter()
+            .filter_map(|direction| self.translate((x, y), *direction))
+            .collect::<Vec<_>>();
+        if neighbours.len() < 4 {
+            return false;
+        }
+        self.fields[x][y] |= VISIT | UNDER | over.bitmask() | over.reciprocal().bitmask();
+        for (direction, (nx, ny)) in sides.iter().zip(neighbours) {
+            self.fields[nx][ny] |= VISIT | direction.reciprocal().bitmask();
+        }
+        true
+    }
+
+    /// Take down every wall inside `room`.
+    pub fn add_room(&mut self, room: Room) {
+        for cell in room.cells() {
+            for direction in [Direction::Right, Direction::Down] {
+                if let Some(neighbour) = self.translate(cell, direction) {
+                    if room.contains(neighbour) {
+                        self.move_from_to(cell, neighbour);
+                    }
+                }
+            }
+        }
+        self.rooms.push(room);
+    }
+
+    pub fn get_rooms(&self) -> &[Room] {
+        &self.rooms
+    }
+
     pub fn get_extents(&self) -> (usize, usize) {
         self.extents
     }
 
     pub fn get_entrance(&self) -> (usize, usize) {
-        (self.entrance, 0)
+        self.entrance
     }
 
     pub fn get_exit(&self) -> (usize, usize) {
-        (self.exit, self.extents.1 - 1)
+        self.exit
+    }
+
+    /// The topmost cell of every column, which can be entered from above.
+    fn get_possible_entrances(&self) -> Vec<(usize, usize)> {
+        let candidates = (0..self.extents.0)
+            .filter_map(|x| {
+                (0..self.extents.1)
+                    .map(|y| (x, y))
+                    .find(|node| self.is_enabled(*node))
+            })
+            .collect();
+        openings(
+            &self.mask,
+            candidates,
+            |node| self.get_index(node),
+            MaskCell::Start,
+        )
+    }
+
+    /// The bottommost cell of every column, which can be left downwards.
+    fn get_possible_exits(&self) -> Vec<(usize, usize)> {
+        let candidates = (0..self.extents.0)
+            .filter_map(|x| {
+                (0..self.extents.1)
+                    .rev()
+                    .map(|y| (x, y))
+                    .find(|node| self.is_enabled(*node))
+            })
+            .collect();
+        openings(
+            &self.mask,
+            candidates,
+            |node| self.get_index(node),
+            MaskCell::Finish,
+        )
+    }
+
+    /// Every cell with a side that doesn't lead to another cell, where a pair
+    /// of the solution can start or finish.
+    fn get_edge_cells(&self) -> Vec<(usize, usize)> {
+        self.get_all_nodes()
+            .into_iter()
+            .filter(|node| Direction::iterator().any(|d| self.translate(*node, d).is_none()))
+            .collect()
+    }
+
+    /// Open the first side of `cell` that doesn't lead to another cell.
+    fn open(&mut self, cell: (usize, usize)) {
+        if let Some(direction) = Direction::iterator().find(|d| self.translate(cell, *d).is_none())
+        {
+            self.remove_wall(cell, direction);
+        }
+    }
+
+    /// The side of `cell` that leads out of the maze, if it has been opened
+    /// for a way in or out.
+    pub fn opening(&self, cell: (usize, usize)) -> Option<Direction> {
+        Direction::iterator()
+            .find(|d| !self.has_wall(cell, *d) && self.translate(cell, *d).is_none())
+    }
+
+    /// Connect the `puzzle`'s further pairs without crossing `path`, and open
+    /// the maze where each of them starts and finishes.
+    fn connect_pairs(
+        &mut self,
+        rng: &mut Arengee,
+        puzzle: Puzzle,
+        path: &[(usize, usize)],
+    ) -> Vec<Vec<(usize, usize)>> {
+        let edge = self.get_edge_cells();
+        let ways = pairs(
+            self,
+            rng,
+            puzzle.pairs.saturating_sub(1),
+            path,
+            &edge,
+            &edge,
+        );
+        for way in ways.iter() {
+            self.open(way[0]);
+            self.open(way[way.len() - 1]);
+        }
+        ways
     }
 
     fn get_walkable_edges(
@@ -113,44 +378,48 @@ impl RectilinearMaze {
         Box::new(
             Direction::iterator()
                 .filter(move |direction| self.fields[x][y] & direction.bitmask() != 0)
-                .filter_map(move |direction| self.translate((x, y), direction)),
+                .filter_map(move |direction| self.step((x, y), direction)),
         )
     }
 
-    fn get_possible_targets(&self, (x, y): (usize, usize)) -> Vec<(usize, usize)> {
+    fn get_walled_neighbours(&self, (x, y): (usize, usize)) -> Vec<(usize, usize)> {
+        // the tunnel takes up both sides of a crossing that its passage doesn't
+        if self.is_crossing((x, y)) {
+            return vec![];
+        }
         Direction::iterator()
-            .filter_map(|direction| match self.translate((x, y), direction) {
-                Some((tx, ty))
-                    if self.fields[tx][ty] & VISIT == 0
-                        && self.fields[x][y] & direction.bitmask() == 0 =>
-                {
-                    Some((tx, ty))
-                }
-                _ => None,
-            })
+            .filter(|direction| self.fields[x][y] & direction.bitmask() == 0)
+            .filter_map(|direction| self.translate((x, y), direction))
             .collect()
     }
 
+    /// Which side of `from` leads to `to`, if they're neighbours.
+    pub fn direction_between(&self, from: (usize, usize), to: (usize, usize)) -> Option<Direction> {
+        Direction::iterator().find(|direction| self.adjacent(from, *direction) == Some(to))
+    }
+
     pub fn move_from_to(&mut self, (fx, fy): (usize, usize), (tx, ty): (usize, usize)) -> bool {
-        use Direction::*;
         // assert!(
         //     (tx < self.extents.0) && (ty < self.extents.1),
         //     "Attempted to move outside of extents"
         // );
-        let direction = match ((fx.abs_diff(tx)), (fy.abs_diff(ty))) {
-            (1, 0) if fx < tx => Some(Right),
-            (1, 0) => Some(Left),
-            (0, 1) if fy < ty => Some(Down),
-            (0, 1) => Some(Up),
-            _ => None,
-        };
-        direction
+        self.direction_between((fx, fy), (tx, ty))
             .map(|d| {
                 self.fields[fx][fy] |= VISIT | d.bitmask();
                 self.fields[tx][ty] |= VISIT | d.reciprocal().bitmask();
             })
             .is_some()
     }
+
+    /// The opposite of `move_from_to`.
+    pub fn wall_off(&mut self, (fx, fy): (usize, usize), (tx, ty): (usize, usize)) -> bool {
+        self.direction_between((fx, fy), (tx, ty))
+            .map(|d| {
+                self.fields[fx][fy] &= !d.bitmask();
+                self.fields[tx][ty] &= !d.reciprocal().bitmask();
+            })
+            .is_some()
+    }
 }
 
 impl Maze for RectilinearMaze {
@@ -158,23 +427,70 @@ impl Maze for RectilinearMaze {
 
     fn carve(&mut self, node: Self::Idx, neighbour: Self::Idx) {
         self.move_from_to(node, neighbour);
+        self.overlay.clear_one_way(node, neighbour);
+    }
+
+    fn build_wall(&mut self, node: Self::Idx, neighbour: Self::Idx) {
+        self.wall_off(node, neighbour);
+        self.overlay.clear_one_way(node, neighbour);
     }
 
     fn get_walls(&self, node: Self::Idx) -> Vec<Self::Idx> {
-        self.get_possible_targets(node)
+        self.get_walled_neighbours(node)
     }
 
     fn get_paths(&self, node: Self::Idx) -> Vec<Self::Idx> {
         self.get_walkable_edges(node).collect()
     }
 
-    fn get_random_node(&self, rng: &mut Arengee) -> Self::Idx {
+    fn overlay(&self) -> &Overlay<Self::Idx> {
+        &self.overlay
+    }
+
+    fn overlay_mut(&mut self) -> &mut Overlay<Self::Idx> {
+        &mut self.overlay
+    }
+
+    /// Square mazes can also be turned by a quarter, unless that turns the
+    /// way they wrap around. Masks have to be just as symmetric as the maze.
+    fn get_symmetric(&self, (x, y): Self::Idx, symmetry: Symmetry) -> Option<Self::Idx> {
+        let (w, h) = self.extents;
+        let turnable = w == h && self.wrap.horizontal == self.wrap.vertical && !self.wrap.twist;
+        let image = match symmetry {
+            Symmetry::Mirror => (w - 1 - x, y),
+            Symmetry::Rotation(2) => (w - 1 - x, h - 1 - y),
+            Symmetry::Rotation(4) if turnable => (w - 1 - y, x),
+            Symmetry::Rotation(_) => return None,
+        };
+        self.is_enabled(image).then_some(image)
+    }
+
+    fn get_position(&self, (x, y): Self::Idx) -> (f64, f64) {
         (
-            rng.u32(0..self.extents.0 as u32) as usize,
-            rng.u32(0..self.extents.1 as u32) as usize,
+            (x as f64 + 0.5) / self.extents.0 as f64,
+            (y as f64 + 0.5) / self.extents.1 as f64,
         )
     }
 
+    fn get_axis(&self, node: Self::Idx, neighbour: Self::Idx) -> Option<Axis> {
+        Some(match self.direction_between(node, neighbour) {
+            Some(Direction::Left | Direction::Right) => Axis::Horizontal,
+            _ => Axis::Vertical,
+        })
+    }
+
+    fn get_random_node(&self, rng: &mut Arengee) -> Self::Idx {
+        loop {
+            let node = (
+                rng.u32(0..self.extents.0 as u32) as usize,
+                rng.u32(0..self.extents.1 as u32) as usize,
+            );
+            if self.is_enabled(node) {
+                return node;
+            }
+        }
+    }
+
     fn get_all_edges(&self) -> Vec<(Self::Idx, Self::Idx)> {
         let (ex, ey) = self.extents;
         (0..(ey - 1))
@@ -183,6 +499,13 @@ impl Maze for RectilinearMaze {
             })
             .merge((0..ey - 1).map(|y| ((ex - 1, y), (ex - 1, y + 1))))
             .merge((0..ex - 1).map(|x| ((x, ey - 1), (x + 1, ey - 1))))
+            .filter(|(a, b)| self.is_enabled(*a) && self.is_enabled(*b))
+            .chain(self.get_all_nodes().into_iter().flat_map(|node| {
+                [Direction::Right, Direction::Down]
+                    .into_iter()
+                    .filter(move |direction| self.wraps(node, *direction))
+                    .filter_map(move |direction| Some((node, self.translate(node, direction)?)))
+            }))
             .collect()
     }
 
@@ -190,6 +513,7 @@ impl Maze for RectilinearMaze {
         let (ex, ey) = self.extents;
         (0..(ey))
             .flat_map(|y| (0..(ex)).map(move |x| (x, y)))
+            .filter(|node| self.is_enabled(*node))
             .collect()
     }
 
@@ -197,37 +521,58 @@ impl Maze for RectilinearMaze {
         self.extents.0 * y + x
     }
 
-    fn make_solution(&mut self, rng: &mut Arengee) -> Solution<Self::Idx> {
+    fn get_index_bound(&self) -> usize {
+        self.extents.0 * self.extents.1
+    }
+
+    fn make_solution(&mut self, rng: &mut Arengee, puzzle: Puzzle) -> Solution<Self::Idx> {
+        if self.wrap.vertical {
+            // there's no top or bottom edge to come in and leave by, so the
+            // solution runs between two cells far apart inside the maze
+            let entrance = furthest(self, self.get_random_node(rng));
+            let exit = furthest(self, entrance);
+            self.entrance = entrance;
+            self.exit = exit;
+            let (path, checkpoints) = solve_puzzle(self, rng, puzzle, entrance, exit);
+            let pairs = self.connect_pairs(rng, puzzle, &path);
+            return Solution {
+                path,
+                distances: dijkstra(self, entrance),
+                checkpoints,
+                pairs,
+            };
+        }
+        let entrances = self.get_possible_entrances();
+        let exits = self.get_possible_exits();
         let seed_topo = {
-            let start = (rng.u32(0..self.get_extents().0 as u32) as usize, 0);
+            let start = entrances[rng.get_portable_usize(0..entrances.len())];
             dijkstra(self, start)
         };
-        let exit = {
-            let y = self.get_extents().1 - 1;
-            (0..self.get_extents().0)
-                .map(|x| (x, y))
-                .max_by_key(|node| seed_topo[self.get_index(*node)])
-                .unwrap_or((
-                    rng.u32(0..self.get_extents().0 as u32) as usize,
-                    self.get_extents().1 - 1,
-                ))
-        };
-
-        let exit_topo = dijkstra(self, exit);
-        let entrance = (0..self.get_extents().0)
-            .map(|x| (x, 0))
+        let exit = exits
+            .iter()
+            .copied()
+            .max_by_key(|node| seed_topo[self.get_index(*node)])
+            .unwrap_or(exits[rng.get_portable_usize(0..exits.len())]);
+
+        let exit_topo = dijkstra_to(self, exit);
+        let entrance = entrances
+            .iter()
+            .copied()
             .max_by_key(|node| exit_topo[self.get_index(*node)])
-            .unwrap_or((rng.u32(0..self.get_extents().0 as u32) as usize, 0));
+            .unwrap_or(entrances[rng.get_portable_usize(0..entrances.len())]);
         let entrance_topo = dijkstra(self, entrance);
 
-        self.set_entrance(entrance.0);
-        self.set_exit(exit.0);
+        self.set_entrance(entrance);
+        self.set_exit(exit);
 
-        let path = find_path(self, &exit_topo, entrance, exit);
+        let (path, checkpoints) = solve_puzzle(self, rng, puzzle, entrance, exit);
+        let pairs = self.connect_pairs(rng, puzzle, &path);
 
         Solution {
             path,
             distances: entrance_topo,
+            checkpoints,
+            pairs,
         }
     }
 }
@@ -236,6 +581,7 @@ impl Maze for RectilinearMaze {
 mod test {
     use super::Direction::*;
     use crate::maze::shape::regular::{RectilinearMaze, DOWN, LEFT, RIGHT, UP};
+    use crate::maze::shape::wrap::Wrap;
 
     #[test]
     fn move_tears_down_the_walls_on_both_sides() {
@@ -252,6 +598,34 @@ mod test {
         assert_eq!(m.fields[1][2] & DOWN, 0);
     }
 
+    #[test]
+    fn wall_off_undoes_move() {
+        let mut m = RectilinearMaze::new((12, 12));
+        m.move_from_to((1, 1), (2, 1));
+        assert!(m.wall_off((2, 1), (1, 1)));
+        assert!(m.has_wall((1, 1), Right));
+        assert!(m.has_wall((2, 1), Left));
+        assert!(
+            !m.wall_off((1, 1), (3, 1)),
+            "Can't wall off cells that aren't neighbours"
+        );
+    }
+
+    #[test]
+    fn crossings_tunnel_beneath_the_passage() {
+        use crate::maze::interface::Maze;
+        let mut m = RectilinearMaze::new((5, 5));
+        assert!(!m.add_crossing((0, 2), Left), "No room for a tunnel");
+        assert!(m.add_crossing((2, 2), Left));
+        assert!(m.is_crossing((2, 2)));
+        assert!(m.has_wall((2, 2), Up));
+        assert_eq!(m.get_paths((2, 2)), vec![(1, 2), (3, 2)]);
+        assert_eq!(m.get_paths((2, 1)), vec![(2, 3)]);
+        assert_eq!(m.get_paths((2, 3)), vec![(2, 1)]);
+        assert!(m.get_walls((2, 2)).is_empty());
+        assert!(!m.get_walls((2, 1)).contains(&(2, 2)));
+    }
+
     #[test]
     fn translate_does_not_allow_going_off_grid() {
         let m = RectilinearMaze::new((10, 10));
@@ -261,6 +635,38 @@ mod test {
         assert_eq!(m.translate((9, 9), Right), None);
     }
 
+    #[test]
+    fn translate_wraps_around_the_edges() {
+        let mut m = RectilinearMaze::new((5, 4));
+        let wrap = |horizontal, vertical, twist| Wrap {
+            horizontal,
+            vertical,
+            twist,
+        };
+        m.set_wrap(wrap(true, false, false)).unwrap();
+        assert_eq!(m.translate((0, 1), Left), Some((4, 1)));
+        assert_eq!(m.translate((4, 1), Right), Some((0, 1)));
+        assert_eq!(m.translate((0, 0), Up), None);
+        assert!(m.wraps((0, 1), Left));
+        assert!(!m.wraps((1, 1), Left));
+
+        m.set_wrap(wrap(true, true, true)).unwrap();
+        assert_eq!(m.translate((4, 0), Right), Some((0, 3)));
+        assert_eq!(m.translate((0, 3), Left), Some((4, 0)));
+        assert_eq!(m.translate((1, 3), Down), Some((1, 0)));
+
+        m.set_wrap(wrap(false, true, true)).unwrap();
+        assert_eq!(m.translate((1, 3), Down), Some((3, 0)));
+        assert_eq!(m.translate((4, 0), Right), None);
+        assert!(m.move_from_to((3, 0), (1, 3)));
+        assert!(!m.has_wall((3, 0), Up));
+        assert!(!m.has_wall((1, 3), Down));
+
+        assert!(RectilinearMaze::new((2, 4))
+            .set_wrap(wrap(true, false, false))
+            .is_err());
+    }
+
     #[test]
     fn move_with_two_coordinates_removes_walls_when_coordinates_valid() {
         let mut m = RectilinearMaze::new((10, 10));
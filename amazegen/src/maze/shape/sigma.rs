@@ -1,12 +1,17 @@
 use std::ops::{Index, IndexMut};
 
 use crate::maze::{
-    algorithms::{dijkstra, find_path},
+    algorithms::{dijkstra, dijkstra_to, furthest, pairs, solve_puzzle},
     arengee::Arengee,
-    interface::{Maze, Solution},
+    interface::{Axis, Maze, Puzzle, Solution},
 };
 
-use super::coordinates::Cartesian;
+use super::{
+    coordinates::Cartesian,
+    mask::{check_mask, openings, Mask, MaskCell, MaskError},
+    overlay::Overlay,
+    wrap::{Wrap, WrapError},
+};
 
 #[derive(Debug, Clone, Copy)]
 pub enum Direction {
@@ -18,6 +23,13 @@ pub enum Direction {
     SouthWest,
 }
 
+impl Direction {
+    pub fn all() -> [Direction; 6] {
+        use Direction::*;
+        [North, South, NorthEast, NorthWest, SouthEast, SouthWest]
+    }
+}
+
 impl Index<Direction> for Neighbours {
     type Output = Option<Cartesian<usize>>;
 
@@ -48,49 +60,42 @@ pub struct SigmaCell {
     inaccessible: Neighbours,
 }
 
+/// Where a step in direction `d` from `coordinates` ends up, which may be off
+/// the grid.
+fn step(coordinates: Cartesian<usize>, d: Direction) -> (isize, isize) {
+    let (x, y) = coordinates.get();
+    let (x, y) = (x as isize, y as isize);
+    // odd columns are half a cell lower, so their diagonals reach a row further down
+    let shift = x % 2;
+    match d {
+        Direction::North => (x, y - 1),
+        Direction::South => (x, y + 1),
+        Direction::NorthEast => (x + 1, y + shift - 1),
+        Direction::NorthWest => (x - 1, y + shift - 1),
+        Direction::SouthEast => (x + 1, y + shift),
+        Direction::SouthWest => (x - 1, y + shift),
+    }
+}
+
+/// All neighbours of the cell at `coordinates` in a maze of `size` that
+/// wraps around like `wrap`.
+fn neighbours(coordinates: Cartesian<usize>, size: usize, wrap: Wrap) -> Neighbours {
+    let mut inaccessible = Neighbours::new();
+    for d in Direction::all() {
+        inaccessible[d] = wrap
+            .apply(step(coordinates, d), (size, size))
+            .map(Cartesian::from);
+    }
+    inaccessible
+}
+
 impl SigmaCell {
-    fn new<C: Into<Cartesian<usize>>>(coordinates: C, size: usize) -> Self {
+    fn new<C: Into<Cartesian<usize>>>(coordinates: C, size: usize, wrap: Wrap) -> Self {
         let coordinates = coordinates.into();
-        let (x, y) = coordinates.get();
-        let mut inaccessible = Neighbours::new();
-        if y > 0 {
-            inaccessible[Direction::North] = Some((x, y - 1).into());
-        }
-        if y < size - 1 {
-            inaccessible[Direction::South] = Some((x, y + 1).into());
-        }
-
-        if x % 2 == 0 {
-            if x < size - 1 {
-                inaccessible[Direction::SouthEast] = Some((x + 1, y).into());
-                if y > 0 {
-                    inaccessible[Direction::NorthEast] = Some((x + 1, y - 1).into());
-                }
-            }
-            if x > 0 {
-                inaccessible[Direction::SouthWest] = Some((x - 1, y).into());
-                if y > 0 {
-                    inaccessible[Direction::NorthWest] = Some((x - 1, y - 1).into());
-                }
-            }
-        } else {
-            if x < size - 1 {
-                inaccessible[Direction::NorthEast] = Some((x + 1, y).into());
-                if y < size - 1 {
-                    inaccessible[Direction::SouthEast] = Some((x + 1, y + 1).into());
-                }
-            }
-            if x > 0 {
-                inaccessible[Direction::NorthWest] = Some((x - 1, y).into());
-                if y < size - 1 {
-                    inaccessible[Direction::SouthWest] = Some((x - 1, y + 1).into());
-                }
-            }
-        }
         Self {
             coordinates,
             accessible: Neighbours::new(),
-            inaccessible,
+            inaccessible: neighbours(coordinates, size, wrap),
         }
     }
 
@@ -105,12 +110,22 @@ impl SigmaCell {
             self.accessible.0[index] = Some(neighbour);
         }
     }
+
+    fn build_wall(&mut self, neighbour: Cartesian<usize>) {
+        if let Some(index) = self.accessible.0.iter().position(|&n| n == Some(neighbour)) {
+            self.accessible.0[index] = None;
+            self.inaccessible.0[index] = Some(neighbour);
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct SigmaMaze {
     pub size: usize,
     pub cells: Vec<SigmaCell>,
+    mask: Vec<MaskCell>,
+    overlay: Overlay<Cartesian<usize>>,
+    wrap: Wrap,
 }
 
 impl SigmaMaze {
@@ -118,34 +133,213 @@ impl SigmaMaze {
         let min_size = size.max(2);
         let cells = (0..min_size)
             .flat_map(|y| (0..min_size).map(move |x| (x, y)))
-            .map(|coordinates| SigmaCell::new(coordinates, min_size))
+            .map(|coordinates| SigmaCell::new(coordinates, min_size, Wrap::default()))
             .collect();
         Self {
             size: min_size,
             cells,
+            mask: vec![MaskCell::Cell; min_size * min_size],
+            overlay: Overlay::default(),
+            wrap: Wrap::default(),
         }
     }
 
-    fn set_exit(&mut self, x: usize, rng: &mut Arengee) {
-        let y = self.size - 1;
-        let index = Cartesian::new(x, y).regular_index(self.size);
-        let d = if x % 2 == 0 {
-            &Direction::South
+    /// Let the edges of the maze lead around to the opposite edge. Every
+    /// other column is lower, so that only works horizontally if there's an
+    /// even number of columns, and twisting only works vertically, with an odd
+    /// number of columns. Must be called before applying a mask or generating
+    /// the maze.
+    pub fn set_wrap(&mut self, wrap: Wrap) -> Result<(), WrapError> {
+        let size = self.size;
+        if !wrap.is_flat() && size < 3 {
+            return Err(WrapError::TooSmall);
+        }
+        if wrap.horizontal && !size.is_multiple_of(2) {
+            return Err(WrapError::OddSize);
+        }
+        if wrap.twist && wrap.horizontal {
+            return Err(WrapError::Untwistable);
+        }
+        if wrap.twist && wrap.vertical && size.is_multiple_of(2) {
+            return Err(WrapError::EvenSize);
+        }
+        self.wrap = wrap;
+        for cell in self.cells.iter_mut() {
+            *cell = SigmaCell::new(cell.coordinates, size, wrap);
+        }
+        Ok(())
+    }
+
+    pub fn get_wrap(&self) -> Wrap {
+        self.wrap
+    }
+
+    /// Remove the cells that `mask` leaves out. Must be called before
+    /// generating the maze.
+    pub fn apply_mask(&mut self, mask: &Mask) -> Result<(), MaskError> {
+        let size = self.size;
+        self.mask = mask.sample(size, size);
+        for (cell, kind) in self.cells.iter_mut().zip(self.mask.iter()) {
+            for neighbour in cell.inaccessible.0.iter_mut() {
+                if !kind.is_cell()
+                    || neighbour.is_some_and(|n| !self.mask[n.regular_index(size)].is_cell())
+                {
+                    *neighbour = None;
+                }
+            }
+        }
+        check_mask(self)?;
+        if self.get_possible_entrances().is_empty() || self.get_possible_exits().is_empty() {
+            return Err(MaskError::NoEntrance);
+        }
+        Ok(())
+    }
+
+    fn is_enabled(&self, cell: Cartesian<usize>) -> bool {
+        self.mask[self.get_index(cell)].is_cell()
+    }
+
+    /// Whether the side of `cell` in direction `d` is on the border of the
+    /// maze, i.e. there's no cell on the other side, or it's been masked out.
+    pub fn faces_outside(&self, cell: &Cartesian<usize>, d: Direction) -> bool {
+        neighbours(*cell, self.size, self.wrap)[d].is_none_or(|n| !self.is_enabled(n))
+    }
+
+    /// Whether the side of `cell` in direction `d` leads around to the
+    /// opposite edge of the maze.
+    pub fn wraps(&self, cell: &Cartesian<usize>, d: Direction) -> bool {
+        let (x, y) = step(*cell, d);
+        let size = self.size as isize;
+        let off_grid = !(0..size).contains(&x) || !(0..size).contains(&y);
+        off_grid && !self.faces_outside(cell, d)
+    }
+
+    /// Which side of `from` leads to `to`, if they're neighbours.
+    pub fn direction_between(
+        &self,
+        from: Cartesian<usize>,
+        to: Cartesian<usize>,
+    ) -> Option<Direction> {
+        let sides = neighbours(from, self.size, self.wrap);
+        Direction::all().into_iter().find(|d| sides[*d] == Some(to))
+    }
+
+    /// The exit is marked by a path from the cell to itself. It leads down if
+    /// there's nothing below the cell.
+    fn set_exit(&mut self, exit: Cartesian<usize>, rng: &mut Arengee) {
+        let d = if exit.x().is_multiple_of(2) {
+            Direction::South
         } else {
-            rng.choice(&[Direction::South, Direction::SouthEast, Direction::SouthWest])
+            let ways = [Direction::South, Direction::SouthEast, Direction::SouthWest]
+                .into_iter()
+                .filter(|d| self.faces_outside(&exit, *d))
+                .collect::<Vec<_>>();
+            *rng.choice(&ways)
         };
-        self.cells[index].accessible[*d] = Some((x, y + 1).into());
+        let index = self.get_index(exit);
+        self.cells[index].accessible[d] = Some(exit);
     }
 
-    fn set_entrance(&mut self, x: usize, rng: &mut Arengee) {
-        let y = 0;
-        let index = Cartesian::new(x, y).regular_index(self.size);
-        let d = if x % 2 == 0 {
-            rng.choice(&[Direction::North, Direction::NorthEast, Direction::NorthWest])
+    /// Like the exit, but up.
+    fn set_entrance(&mut self, entrance: Cartesian<usize>, rng: &mut Arengee) {
+        let d = if entrance.x().is_multiple_of(2) {
+            let ways = [Direction::North, Direction::NorthEast, Direction::NorthWest]
+                .into_iter()
+                .filter(|d| self.faces_outside(&entrance, *d))
+                .collect::<Vec<_>>();
+            *rng.choice(&ways)
         } else {
-            &Direction::North
+            Direction::North
         };
-        self.cells[index].accessible[*d] = Some((x, y).into());
+        let index = self.get_index(entrance);
+        self.cells[index].accessible[d] = Some(entrance);
+    }
+
+    /// The topmost cell of every column.
+    fn get_possible_entrances(&self) -> Vec<Cartesian<usize>> {
+        let candidates = (0..self.size)
+            .filter_map(|x| {
+                (0..self.size)
+                    .map(|y| Cartesian::new(x, y))
+                    .find(|c| self.is_enabled(*c))
+            })
+            .collect();
+        openings(
+            &self.mask,
+            candidates,
+            |c| self.get_index(c),
+            MaskCell::Start,
+        )
+    }
+
+    /// The bottommost cell of every column.
+    fn get_possible_exits(&self) -> Vec<Cartesian<usize>> {
+        let candidates = (0..self.size)
+            .filter_map(|x| {
+                (0..self.size)
+                    .rev()
+                    .map(|y| Cartesian::new(x, y))
+                    .find(|c| self.is_enabled(*c))
+            })
+            .collect();
+        openings(
+            &self.mask,
+            candidates,
+            |c| self.get_index(c),
+            MaskCell::Finish,
+        )
+    }
+
+    /// Every cell with a side on the border, where a pair of the solution
+    /// can start or finish.
+    fn get_edge_cells(&self) -> Vec<Cartesian<usize>> {
+        self.get_all_nodes()
+            .into_iter()
+            .filter(|c| Direction::all().iter().any(|d| self.faces_outside(c, *d)))
+            .collect()
+    }
+
+    /// Like the entrance, but through any side on the border.
+    fn open(&mut self, cell: Cartesian<usize>, rng: &mut Arengee) {
+        let ways = Direction::all()
+            .into_iter()
+            .filter(|d| self.faces_outside(&cell, *d))
+            .collect::<Vec<_>>();
+        let index = self.get_index(cell);
+        self.cells[index].accessible[*rng.choice(&ways)] = Some(cell);
+    }
+
+    /// The side of `cell` that leads out of the maze, if it has been opened
+    /// for a way in or out.
+    pub fn opening(&self, cell: Cartesian<usize>) -> Option<Direction> {
+        let accessible = &self.cells[self.get_index(cell)].accessible;
+        Direction::all()
+            .into_iter()
+            .find(|d| accessible[*d] == Some(cell))
+    }
+
+    /// Connect the `puzzle`'s further pairs without crossing `path`, and open
+    /// the maze where each of them starts and finishes.
+    fn connect_pairs(
+        &mut self,
+        rng: &mut Arengee,
+        puzzle: Puzzle,
+        path: &[Cartesian<usize>],
+    ) -> Vec<Vec<Cartesian<usize>>> {
+        let edge = self.get_edge_cells();
+        let ways = pairs(
+            self,
+            rng,
+            puzzle.pairs.saturating_sub(1),
+            path,
+            &edge,
+            &edge,
+        );
+        for way in ways.iter() {
+            self.open(way[0], rng);
+            self.open(way[way.len() - 1], rng);
+        }
+        ways
     }
 
     pub fn has_path(&self, a: &Cartesian<usize>, d: Direction) -> bool {
@@ -165,6 +359,15 @@ impl Maze for SigmaMaze {
         let b = self.get_index(neighbour);
         self.cells[a].carve(neighbour);
         self.cells[b].carve(node);
+        self.overlay.clear_one_way(node, neighbour);
+    }
+
+    fn build_wall(&mut self, node: Self::Idx, neighbour: Self::Idx) {
+        let a = self.get_index(node);
+        let b = self.get_index(neighbour);
+        self.cells[a].build_wall(neighbour);
+        self.cells[b].build_wall(node);
+        self.overlay.clear_one_way(node, neighbour);
     }
 
     fn get_walls(&self, node: Self::Idx) -> Vec<Self::Idx> {
@@ -185,8 +388,41 @@ impl Maze for SigmaMaze {
             .collect()
     }
 
+    fn overlay(&self) -> &Overlay<Self::Idx> {
+        &self.overlay
+    }
+
+    fn overlay_mut(&mut self) -> &mut Overlay<Self::Idx> {
+        &mut self.overlay
+    }
+
+    /// Every other column is half a cell lower, and the hexagons of
+    /// neighbouring columns interlock.
+    fn get_position(&self, node: Self::Idx) -> (f64, f64) {
+        let size = self.size() as f64;
+        let shift = if node.x().is_multiple_of(2) { 1.0 } else { 2.0 };
+        (
+            (3.0 * node.x() as f64 + 2.0) / (3.0 * size + 1.0),
+            (2.0 * node.y() as f64 + shift) / (2.0 * size + 1.0),
+        )
+    }
+
+    /// Only north and south are vertical, the diagonals are closer to
+    /// horizontal.
+    fn get_axis(&self, node: Self::Idx, neighbour: Self::Idx) -> Option<Axis> {
+        Some(match self.direction_between(node, neighbour) {
+            Some(Direction::North | Direction::South) => Axis::Vertical,
+            _ => Axis::Horizontal,
+        })
+    }
+
     fn get_random_node(&self, rng: &mut Arengee) -> Self::Idx {
-        Cartesian::new(self.size, self.size).get_random_contained_coordinate(rng)
+        loop {
+            let node = Cartesian::new(self.size, self.size).get_random_contained_coordinate(rng);
+            if self.is_enabled(node) {
+                return node;
+            }
+        }
     }
 
     fn get_all_edges(&self) -> Vec<(Self::Idx, Self::Idx)> {
@@ -204,40 +440,64 @@ impl Maze for SigmaMaze {
     }
 
     fn get_all_nodes(&self) -> Vec<Self::Idx> {
-        self.cells.iter().map(|c| c.coordinates).collect()
+        self.cells
+            .iter()
+            .map(|c| c.coordinates)
+            .filter(|c| self.is_enabled(*c))
+            .collect()
     }
 
     fn get_index(&self, node: Self::Idx) -> usize {
         node.regular_index(self.size)
     }
 
-    fn make_solution(&mut self, rng: &mut Arengee) -> Solution<Self::Idx> {
-        let seed_topo = dijkstra(self, (rng.get_portable_usize(0..self.size), 0).into());
+    fn get_index_bound(&self) -> usize {
+        self.cells.len()
+    }
 
-        let exit: Cartesian<usize> = {
-            let y = self.size - 1;
-            (0..self.size)
-                .map(|x| (x, y))
-                .max_by_key(|&c| seed_topo.get(self.get_index(c.into())))
-                .unwrap_or((rng.get_portable_usize(0..self.size), y))
+    fn make_solution(&mut self, rng: &mut Arengee, puzzle: Puzzle) -> Solution<Self::Idx> {
+        if self.wrap.vertical {
+            // there's no top or bottom edge to come in and leave by, so the
+            // solution runs between two cells far apart inside the maze
+            let entrance = furthest(self, self.get_random_node(rng));
+            let exit = furthest(self, entrance);
+            let (path, checkpoints) = solve_puzzle(self, rng, puzzle, entrance, exit);
+            let pairs = self.connect_pairs(rng, puzzle, &path);
+            return Solution {
+                path,
+                distances: dijkstra(self, entrance),
+                checkpoints,
+                pairs,
+            };
         }
-        .into();
+        let entrances = self.get_possible_entrances();
+        let exits = self.get_possible_exits();
+        let seed_topo = dijkstra(self, entrances[rng.get_portable_usize(0..entrances.len())]);
 
-        let exit_topo = dijkstra(self, exit);
-        let entrance: Cartesian<usize> = (0..self.size)
-            .map(|x| (x, 0))
-            .max_by_key(|&c| exit_topo.get(self.get_index(c.into())))
-            .unwrap_or((rng.get_portable_usize(0..self.size), 0))
-            .into();
+        let exit: Cartesian<usize> = exits
+            .iter()
+            .copied()
+            .max_by_key(|&c| seed_topo.get(self.get_index(c)))
+            .unwrap_or(exits[rng.get_portable_usize(0..exits.len())]);
+
+        let exit_topo = dijkstra_to(self, exit);
+        let entrance: Cartesian<usize> = entrances
+            .iter()
+            .copied()
+            .max_by_key(|&c| exit_topo.get(self.get_index(c)))
+            .unwrap_or(entrances[rng.get_portable_usize(0..entrances.len())]);
 
         let entrance_topo = dijkstra(self, entrance);
-        self.set_entrance(entrance.x(), rng);
-        self.set_exit(exit.x(), rng);
-        let path = find_path(self, &exit_topo, entrance, exit);
+        self.set_entrance(entrance, rng);
+        self.set_exit(exit, rng);
+        let (path, checkpoints) = solve_puzzle(self, rng, puzzle, entrance, exit);
+        let pairs = self.connect_pairs(rng, puzzle, &path);
 
         Solution {
             path,
             distances: entrance_topo,
+            checkpoints,
+            pairs,
         }
     }
 }
@@ -248,7 +508,7 @@ mod tests {
 
     #[test]
     fn cell_edge_case_00() {
-        let cell = SigmaCell::new((0, 0), 3);
+        let cell = SigmaCell::new((0, 0), 3, Wrap::default());
         assert_eq!(cell.inaccessible[Direction::North], None);
         assert_eq!(cell.inaccessible[Direction::NorthEast], None);
         assert_eq!(cell.inaccessible[Direction::NorthWest], None);
@@ -260,7 +520,7 @@ mod tests {
 
     #[test]
     fn cell_edge_case_10() {
-        let cell = SigmaCell::new((1, 0), 3);
+        let cell = SigmaCell::new((1, 0), 3, Wrap::default());
         assert_eq!(cell.inaccessible[Direction::North], None);
 
         assert_eq!(cell.inaccessible[Direction::South], Some((1, 1).into()));
@@ -272,7 +532,7 @@ mod tests {
 
     #[test]
     fn cell_edge_case_end0_even() {
-        let cell = SigmaCell::new((3, 0), 4);
+        let cell = SigmaCell::new((3, 0), 4, Wrap::default());
         assert_eq!(cell.inaccessible[Direction::North], None);
         assert_eq!(cell.inaccessible[Direction::NorthEast], None);
         assert_eq!(cell.inaccessible[Direction::SouthEast], None);
@@ -284,7 +544,7 @@ mod tests {
 
     #[test]
     fn cell_edge_case_end0_odd() {
-        let cell = SigmaCell::new((2, 0), 3);
+        let cell = SigmaCell::new((2, 0), 3, Wrap::default());
         assert_eq!(cell.inaccessible[Direction::North], None);
         assert_eq!(cell.inaccessible[Direction::NorthEast], None);
         assert_eq!(cell.inaccessible[Direction::NorthWest], None);
@@ -296,7 +556,7 @@ mod tests {
 
     #[test]
     fn cell_edge_case_0end() {
-        let cell = SigmaCell::new((0, 2), 3);
+        let cell = SigmaCell::new((0, 2), 3, Wrap::default());
         assert_eq!(cell.inaccessible[Direction::South], None);
         assert_eq!(cell.inaccessible[Direction::SouthWest], None);
         assert_eq!(cell.inaccessible[Direction::NorthWest], None);
@@ -308,7 +568,7 @@ mod tests {
 
     #[test]
     fn cell_edge_case_1end() {
-        let cell = SigmaCell::new((1, 2), 3);
+        let cell = SigmaCell::new((1, 2), 3, Wrap::default());
         assert_eq!(cell.inaccessible[Direction::South], None);
         assert_eq!(cell.inaccessible[Direction::SouthWest], None);
         assert_eq!(cell.inaccessible[Direction::SouthEast], None);
@@ -320,7 +580,7 @@ mod tests {
 
     #[test]
     fn cell_edge_case_endend_odd() {
-        let cell = SigmaCell::new((2, 2), 3);
+        let cell = SigmaCell::new((2, 2), 3, Wrap::default());
         assert_eq!(cell.inaccessible[Direction::South], None);
         assert_eq!(cell.inaccessible[Direction::SouthEast], None);
         assert_eq!(cell.inaccessible[Direction::NorthEast], None);
@@ -330,9 +590,55 @@ mod tests {
         assert_eq!(cell.inaccessible[Direction::SouthWest], Some((1, 2).into()));
     }
 
+    #[test]
+    fn wrapped_neighbours_lead_back() {
+        let wrap = |horizontal, vertical, twist| Wrap {
+            horizontal,
+            vertical,
+            twist,
+        };
+        for (size, wrap) in [
+            (6, wrap(true, false, false)),
+            (6, wrap(true, true, false)),
+            (5, wrap(false, true, false)),
+            (5, wrap(false, true, true)),
+        ] {
+            let mut maze = SigmaMaze::new(size);
+            maze.set_wrap(wrap).unwrap();
+            for cell in maze.get_all_nodes() {
+                let walls = maze.get_walls(cell);
+                if wrap.horizontal && wrap.vertical {
+                    assert_eq!(walls.len(), 6, "{:?}", cell);
+                }
+                for neighbour in walls {
+                    assert!(maze.get_walls(neighbour).contains(&cell), "{:?}", wrap);
+                    assert!(maze.direction_between(neighbour, cell).is_some());
+                }
+            }
+        }
+        let cell = SigmaCell::new((0, 0), 5, wrap(false, true, true));
+        assert_eq!(cell.inaccessible[Direction::North], Some((4, 4).into()));
+        assert_eq!(cell.inaccessible[Direction::NorthEast], Some((3, 4).into()));
+    }
+
+    #[test]
+    fn wrapping_needs_the_right_size() {
+        let wrap = |horizontal, vertical, twist| Wrap {
+            horizontal,
+            vertical,
+            twist,
+        };
+        assert!(SigmaMaze::new(5)
+            .set_wrap(wrap(true, false, false))
+            .is_err());
+        assert!(SigmaMaze::new(6).set_wrap(wrap(false, true, true)).is_err());
+        assert!(SigmaMaze::new(5).set_wrap(wrap(true, true, true)).is_err());
+        assert!(SigmaMaze::new(5).set_wrap(wrap(false, true, true)).is_ok());
+    }
+
     #[test]
     fn cell_edge_case_endend_even() {
-        let cell = SigmaCell::new((3, 3), 4);
+        let cell = SigmaCell::new((3, 3), 4, Wrap::default());
         assert_eq!(cell.inaccessible[Direction::South], None);
         assert_eq!(cell.inaccessible[Direction::SouthEast], None);
         assert_eq!(cell.inaccessible[Direction::NorthEast], None);
@@ -1,9 +1,10 @@
 use std::ops::{Index, IndexMut};
 
+use super::overlay::Overlay;
 use crate::maze::{
-    algorithms::dijkstra,
+    algorithms::{dijkstra, pairs, solve_puzzle},
     arengee::Arengee,
-    interface::{Maze, Solution},
+    interface::{Axis, Maze, Puzzle, Solution, Symmetry},
 };
 
 #[derive(Clone, Debug)]
@@ -25,6 +26,17 @@ impl RingCell {
         }
     }
 
+    fn build_wall(&mut self, neighbour: RingNode) {
+        if let Some(index) = self
+            .accessible_neighbours
+            .iter()
+            .position(|value| *value == neighbour)
+        {
+            self.accessible_neighbours.swap_remove(index);
+            self.inaccessible_neighbours.push(neighbour);
+        }
+    }
+
     fn get_walls(&self) -> Vec<RingNode> {
         self.inaccessible_neighbours.clone()
     }
@@ -109,6 +121,7 @@ pub struct RingMaze {
     pub ring_sizes: Vec<usize>,
     pub cells: Vec<RingCell>,
     extents: Vec<usize>,
+    overlay: Overlay<RingNode>,
 }
 
 impl Maze for RingMaze {
@@ -116,6 +129,13 @@ impl Maze for RingMaze {
     fn carve(&mut self, node: RingNode, neighbour: RingNode) {
         self[node].carve(neighbour);
         self[neighbour].carve(node);
+        self.overlay.clear_one_way(node, neighbour);
+    }
+
+    fn build_wall(&mut self, node: RingNode, neighbour: RingNode) {
+        self[node].build_wall(neighbour);
+        self[neighbour].build_wall(node);
+        self.overlay.clear_one_way(node, neighbour);
     }
 
     fn get_walls(&self, node: RingNode) -> Vec<RingNode> {
@@ -126,6 +146,52 @@ impl Maze for RingMaze {
         self[node].get_paths()
     }
 
+    fn overlay(&self) -> &Overlay<RingNode> {
+        &self.overlay
+    }
+
+    fn overlay_mut(&mut self) -> &mut Overlay<RingNode> {
+        &mut self.overlay
+    }
+
+    fn get_position(&self, node: RingNode) -> (f64, f64) {
+        if node.row == 0 {
+            return (0.5, 0.5);
+        }
+        let radius = (node.row as f64 + 0.5) / self.ring_sizes.len() as f64 / 2.0;
+        let θ = 2.0 * std::f64::consts::PI * (node.column as f64 + 0.5)
+            / self.max_column(node.row) as f64;
+        (0.5 + radius * θ.cos(), 0.5 + radius * θ.sin())
+    }
+
+    /// Neighbours in the same ring are around the centre, the others are
+    /// towards or away from it.
+    fn get_axis(&self, node: RingNode, neighbour: RingNode) -> Option<Axis> {
+        Some(if node.row == neighbour.row {
+            Axis::Horizontal
+        } else {
+            Axis::Vertical
+        })
+    }
+
+    /// Mirrors along the line through the centre between the first and the
+    /// last column. Turns by any `k` that divides the number of columns in
+    /// the innermost ring, since all the others are multiples of it.
+    fn get_symmetric(&self, node: RingNode, symmetry: Symmetry) -> Option<RingNode> {
+        if node.row == 0 {
+            return Some(node);
+        }
+        let columns = self.max_column(node.row);
+        let column = match symmetry {
+            Symmetry::Mirror => columns - 1 - node.column,
+            Symmetry::Rotation(k) if k > 1 && self.ring_sizes[1].is_multiple_of(k as usize) => {
+                (node.column + columns / k as usize) % columns
+            }
+            Symmetry::Rotation(_) => return None,
+        };
+        Some(RingNode { column, ..node })
+    }
+
     fn get_random_node(&self, rng: &mut Arengee) -> RingNode {
         self.cells[rng.u32(0..self.cells.len() as u32) as usize].coordinates
     }
@@ -162,17 +228,39 @@ impl Maze for RingMaze {
         node.column + self.extents[node.row]
     }
 
-    fn make_solution(&mut self, rng: &mut Arengee) -> Solution<RingNode> {
+    fn make_solution(&mut self, rng: &mut Arengee, puzzle: Puzzle) -> Solution<RingNode> {
         let start = self.get_random_cell_on_the_outside(rng);
         let exit = self.get_node_furthest_away_from(start);
         let entrance = self.get_node_furthest_away_from(exit);
-        let (path_to_solution, distances) = self.find_shortest_path(entrance, exit);
+        let (path, checkpoints) = solve_puzzle(self, rng, puzzle, entrance, exit);
+        let distances = dijkstra(self, entrance);
+        let outer_ring = self.ring_sizes.len() - 1;
+        let edge = (0..self.ring_sizes[outer_ring])
+            .map(|column| RingNode {
+                row: outer_ring,
+                column,
+            })
+            .collect::<Vec<_>>();
+        let pairs = pairs(
+            self,
+            rng,
+            puzzle.pairs.saturating_sub(1),
+            &path,
+            &edge,
+            &edge,
+        );
         self.open(entrance);
         self.open(exit);
+        for way in pairs.iter() {
+            self.open(way[0]);
+            self.open(way[way.len() - 1]);
+        }
 
         Solution {
-            path: path_to_solution,
+            path,
             distances,
+            checkpoints,
+            pairs,
         }
     }
 }
@@ -219,6 +307,7 @@ impl RingMaze {
             ring_sizes: rings,
             extents,
             cells,
+            overlay: Overlay::default(),
         }
     }
 
@@ -233,26 +322,6 @@ impl RingMaze {
         RingNode { row: ring, column }
     }
 
-    fn find_shortest_path(&self, start: RingNode, end: RingNode) -> (Vec<RingNode>, Vec<usize>) {
-        let distances = dijkstra(self, start);
-        let mut cursor = end;
-        let mut path = vec![cursor];
-        loop {
-            cursor = *self
-                .get_paths(cursor)
-                .iter()
-                .min_by_key(|n| distances[self.get_index(**n)])
-                .unwrap();
-            path.push(cursor);
-            if distances[self.get_index(cursor)] == 1 {
-                break;
-            }
-        }
-        path.push(start);
-
-        (path, distances)
-    }
-
     fn open(&mut self, node: RingNode) {
         self[node].accessible_neighbours.push(RingNode {
             row: node.row + 1,
@@ -277,7 +346,6 @@ impl RingMaze {
     }
 }
 
-// we only need hash for one test. it's not used in the runtime code
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct RingNode {
     pub row: usize,
@@ -307,6 +375,31 @@ mod test {
     use itertools::Itertools;
 
     use super::*;
+    use crate::maze::algorithms::{kruskal, Weights};
+
+    #[test]
+    fn solution_runs_from_entrance_to_exit() {
+        for puzzle in [
+            Puzzle::default(),
+            Puzzle {
+                checkpoints: 2,
+                ..Default::default()
+            },
+        ] {
+            let mut rng = Arengee::new(3);
+            let mut maze = kruskal(RingMaze::new(6, 8), &mut rng, Weights::default());
+            let solution = maze.make_solution(&mut rng, puzzle);
+            // the distances are counted from the entrance
+            let entrance = maze
+                .get_all_nodes()
+                .into_iter()
+                .min_by_key(|n| solution.distances[maze.get_index(*n)])
+                .unwrap();
+            assert_eq!(solution.path.first(), Some(&entrance));
+            assert_eq!(entrance.row, maze.ring_sizes.len() - 1);
+            assert_ne!(solution.path.last(), Some(&entrance));
+        }
+    }
 
     #[test]
     fn east_west_of_wraps_around_properly() {
@@ -0,0 +1,68 @@
+use thiserror::Error;
+
+/// Which edges of a maze lead around to the opposite edge. Wrapping
+/// horizontally rolls the maze up into a cylinder, wrapping both ways into a
+/// torus. A twist turns the cells upside down on their way around, which
+/// makes a cylinder a Möbius strip and a torus a Klein bottle. It applies to
+/// the horizontal wrap, unless the maze only wraps vertically.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct Wrap {
+    #[serde(default)]
+    pub horizontal: bool,
+    #[serde(default)]
+    pub vertical: bool,
+    #[serde(default)]
+    pub twist: bool,
+}
+
+#[derive(Error, Debug)]
+pub enum WrapError {
+    #[error("a maze needs to be at least three cells across to wrap around")]
+    TooSmall,
+    #[error("sigma mazes need an even size to wrap around horizontally")]
+    OddSize,
+    #[error("sigma mazes need an odd size to be twisted")]
+    EvenSize,
+    #[error("sigma mazes can only be twisted when they wrap vertically")]
+    Untwistable,
+}
+
+impl Wrap {
+    pub fn is_flat(&self) -> bool {
+        !self.horizontal && !self.vertical
+    }
+
+    fn twists_vertically(&self) -> bool {
+        self.twist && self.vertical && !self.horizontal
+    }
+
+    /// Where the coordinates `(x, y)`, which may be one step off a grid of
+    /// `extents`, end up, or `None` if they're off the grid for good.
+    pub fn apply(
+        &self,
+        (x, y): (isize, isize),
+        (width, height): (usize, usize),
+    ) -> Option<(usize, usize)> {
+        let (width, height) = (width as isize, height as isize);
+        let (mut x, mut y) = (x, y);
+        if !(0..width).contains(&x) {
+            if !self.horizontal {
+                return None;
+            }
+            x = x.rem_euclid(width);
+            if self.twist {
+                y = height - 1 - y;
+            }
+        }
+        if !(0..height).contains(&y) {
+            if !self.vertical {
+                return None;
+            }
+            y = y.rem_euclid(height);
+            if self.twists_vertically() {
+                x = width - 1 - x;
+            }
+        }
+        Some((x as usize, y as usize))
+    }
+}
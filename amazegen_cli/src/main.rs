@@ -1,8 +1,15 @@
 #![allow(mixed_script_confusables)]
 
 use amazegen::maze::{
+    algorithms::{Bias, Frontier, Selection, MAX_PORTALS},
     feature::{Algorithm, Configuration, Feature, Shape, Svg},
+    interface::{Axis, Puzzle, Symmetry},
     paint::WebColour,
+    picture::Picture,
+    shape::{
+        mask::{Mask, MaskCell},
+        wrap::Wrap,
+    },
 };
 use amazegen_printer::{
     metadata::Metadata,
@@ -14,6 +21,17 @@ use clap::{Parser, ValueEnum};
 enum CliAlgorithm {
     GrowingTree,
     Kruskal,
+    Prim,
+    Wilson,
+    AldousBroder,
+    Eller,
+    RecursiveDivision,
+    BlobbyDivision,
+    HuntAndKill,
+    BinaryTree,
+    Sidewinder,
+    Weave,
+    Dungeon,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
@@ -22,6 +40,26 @@ enum CliShape {
     Theta,
     Sigma,
     Delta,
+    Layered,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum CliAxis {
+    Horizontal,
+    Vertical,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum CliSymmetry {
+    Mirror,
+    Rotation,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum CliWrap {
+    Horizontal,
+    Vertical,
+    Both,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
@@ -46,9 +84,134 @@ struct Cli {
         value_enum,
         default_value = "growing-tree",
         help = "Selects the algorithm to genreate the maze.",
-        long_help = "growing-tree is a backtracking algorithm that will generate long winding passages with few dead ends. The path through the maze will tend to be very long. kruskal will use Kruskal's algorithm, which tends to generate lots of dead ends but a relatively short path."
+        long_help = "growing-tree is a backtracking algorithm that will generate long winding passages with few dead ends. The path through the maze will tend to be very long. kruskal will use Kruskal's algorithm, which tends to generate lots of dead ends but a relatively short path. prim uses a randomised version of Prim's algorithm, which creates lots of short dead ends all over the maze. wilson will use Wilson's algorithm, which picks any possible maze with the same probability. aldous-broder does the same, but is much slower on big mazes. eller generates the maze row by row while only remembering the current row, so the library can also stream mazes too tall to hold in memory. It only works with the rectilinear shape. recursive-division adds walls to an open area instead of carving passages, which creates boxy, room-like mazes. It only works with the rectilinear shape, blobby-division works the same way for any shape. hunt-and-kill creates long passages like growing-tree, but scans the maze for a new starting point when it gets stuck instead of backtracking. binary-tree and sidewinder are simple, heavily biased algorithms that only work with the rectilinear shape. weave uses Kruskal's algorithm to create a rectilinear maze whose passages cross over and under each other. dungeon places open rooms without walls inside, see --rooms, and connects them with corridors using Kruskal's algorithm. It only works with the rectilinear shape."
     )]
     algorithm: Option<CliAlgorithm>,
+    #[arg(
+        long,
+        help = "Selection criterion for growing-tree, e.g. newest:75,random:25",
+        long_help = "Which cell growing-tree continues from. One of newest, oldest, random or middle, or a weighted mix of them such as newest:75,random:25. newest (the default) creates long winding passages, random and oldest create many more junctions and dead ends. Ignored by other algorithms."
+    )]
+    selection: Option<Selection>,
+    #[arg(
+        long,
+        default_value = "false",
+        help = "Let prim pick the lightest edge instead of a random one.",
+        long_help = "Gives every edge a random weight and makes prim add the lightest edge next, which turns the maze into a minimum spanning tree. Ignored by other algorithms."
+    )]
+    weighted: bool,
+    #[arg(
+        long,
+        default_value = "0",
+        help = "Percentage of cells a random walk visits before wilson takes over.",
+        long_help = "wilson's first walk takes very long to find the maze on big mazes. An Aldous–Broder random walk is fast at first and just as unbiased, so letting it visit a share of the cells first speeds things up. Ignored by other algorithms."
+    )]
+    aldous_broder: Option<u8>,
+    #[arg(
+        long,
+        default_value = "20",
+        help = "Percentage of cells weave tries to place crossings in.",
+        long_help = "weave places crossings before it creates the rest of the maze, and skips cells where a crossing would be next to another one or would create a loop, so the maze ends up with fewer crossings than this. Ignored by other algorithms."
+    )]
+    crossings: Option<u8>,
+    #[arg(
+        long,
+        default_value = "6",
+        help = "Number of rooms dungeon tries to place.",
+        long_help = "dungeon places open rooms without walls inside before it creates the rest of the maze, and skips rooms that would overlap or touch another one, so on small mazes it ends up with fewer rooms than this. Ignored by other algorithms."
+    )]
+    rooms: Option<u8>,
+    #[arg(
+        long,
+        default_value = "0",
+        help = "Percentage of dead ends to remove, which adds loops.",
+        long_help = "Carves a passage out of this percentage of the maze's dead ends after generating it. The maze gets loops, and at 100 it's a braid maze without any dead ends, which is much harder to solve."
+    )]
+    braid: Option<u8>,
+    #[arg(
+        long,
+        default_value = "0",
+        help = "Percentage of passages to turn into one way passages.",
+        long_help = "A passage only becomes one way if every part of the maze can still be reached from everywhere else, which is only possible for passages on loops. Use --braid to add some, a maze without loops has no one way passages."
+    )]
+    one_way: Option<u8>,
+    #[arg(
+        long,
+        default_value = "0",
+        help = "Percentage of cells to cover with mud and water.",
+        long_help = "Walking through mud costs three times as much as walking on plain ground, and water six times as much. The solution is the cheapest way through the maze rather than the shortest, and --stain shows how much it costs to get to each cell. The cheapest way can only differ from the shortest one if there are loops, see --braid."
+    )]
+    terrain: Option<u8>,
+    #[arg(
+        long,
+        default_value = "0",
+        help = "Number of pairs of portals, up to 12.",
+        long_help = "Each pair of portals links two cells that are far apart, and both get the same symbol. Stepping into one of them leads straight to the other, so they make shortcuts, and the solution shows where it jumps through a portal."
+    )]
+    portals: Option<u8>,
+    #[arg(
+        long,
+        default_value = "0",
+        help = "Number of checkpoints the solution has to visit, up to 20.",
+        long_help = "Checkpoints are numbered, and the way through the maze has to visit them in order. With --solve, each leg of the solution from one checkpoint to the next gets a colour of its own."
+    )]
+    checkpoints: Option<u8>,
+    #[arg(
+        long,
+        default_value = "0",
+        help = "Number of locked doors on the way through, up to 8.",
+        long_help = "Each locked door blocks a passage the way through the maze can't get around, and its key lies somewhere off that way. Doors and keys come in matching colours and letters, and the solution fetches every key before it walks through its door."
+    )]
+    locks: Option<u8>,
+    #[arg(
+        long,
+        value_enum,
+        help = "Prefer passages that run this way.",
+        long_help = "Creates long corridors that run horizontally or vertically. For theta mazes, horizontal means around the centre, and vertical away from it. Only kruskal and growing-tree honour the bias."
+    )]
+    bias: Option<CliAxis>,
+    #[arg(
+        long,
+        default_value = "50",
+        help = "How strongly to prefer the --bias direction, in percent.",
+        long_help = "At 100, passages only run the other way where there's no other way to connect the maze."
+    )]
+    bias_strength: Option<u8>,
+    #[arg(
+        long,
+        value_enum,
+        help = "Make the maze look the same when mirrored or turned.",
+        long_help = "Mirror makes the right half of the maze the mirror image of its left half. Rotation makes it look the same after turning it around its centre, see --folds. Only square and theta mazes can be symmetric, and weave and dungeon can't generate symmetric mazes."
+    )]
+    symmetry: Option<CliSymmetry>,
+    #[arg(
+        long,
+        default_value = "2",
+        help = "How many times --symmetry rotation repeats the maze in a full turn.",
+        long_help = "2 turns the maze upside down. Square mazes can also be turned by 4, and theta mazes by 4 or 8."
+    )]
+    folds: Option<u8>,
+    #[arg(
+        long,
+        value_enum,
+        help = "Let the edges of the maze lead around to the opposite edge.",
+        long_help = "Horizontal rolls the maze up into a cylinder, both ways makes it a torus. Passages that wrap around carry the same number on both edges. Mazes that wrap vertically have their start and finish inside. Only square and sigma mazes can wrap around, and eller, recursive-division, binary-tree and sidewinder can't generate them."
+    )]
+    wrap: Option<CliWrap>,
+    #[arg(
+        long,
+        default_value = "false",
+        help = "Turn the cells upside down on their way around, see --wrap.",
+        long_help = "Makes a cylinder a Möbius strip and a torus a Klein bottle. It's the horizontal wrap that's twisted, unless the maze only wraps vertically. Sigma mazes can only be twisted if they only wrap vertically and their size is odd."
+    )]
+    twist: bool,
+    #[arg(
+        long,
+        default_value = "0",
+        help = "Number of labelled start and finish pairs to connect, up to 8.",
+        long_help = "Each pair starts and finishes on the edge of the maze, with the same letter and colour at both ends: A to A, B to B and so on. The entrance and exit are pair A. The ways between the pairs don't share any cells, so finding one can block another. Layered mazes only have one pair."
+    )]
+    pairs: Option<u8>,
     #[arg(
         short,
         long,
@@ -57,6 +220,40 @@ struct Cli {
         long_help = "What size means may depend on the shape of the maze. For square mazes, it's the number of cells in each row and column. Theta mazes use size to determine the number of rows from the origin."
     )]
     size: Option<u32>,
+    #[arg(
+        long,
+        default_value = "3",
+        help = "Number of levels of a layered maze.",
+        long_help = "Only works with the layered shape. Each level is a square maze, and stairs connect it to the levels above and below."
+    )]
+    levels: Option<usize>,
+    #[arg(
+        long,
+        help = "Only use the cells covered by this mask.",
+        long_help = "A text file with one line per row, where . is a cell and X is a hole, S marks cells where the maze may start and F cells where it may finish, or a black and white PNG or PBM image, where black pixels are cells. The mask is stretched over the maze, so it doesn't need to have the maze's size. Only works with the rectilinear, sigma and delta shapes, and not with eller, recursive-division, binary-tree and sidewinder."
+    )]
+    mask: Option<String>,
+    #[arg(
+        long,
+        help = "Trace this picture with the maze's passages.",
+        long_help = "A PNG image. Where it's bright, passages tend to become long corridors, and where it's dark, the maze fills up with short dead ends, so the picture shows in the maze's structure. The corridors run horizontally unless --bias says otherwise, and --bias-strength sets how long they get in the brightest parts. The picture is stretched over the maze. Only kruskal and growing-tree trace pictures."
+    )]
+    picture: Option<String>,
+    #[arg(
+        long,
+        requires = "picture",
+        help = "Stain the maze with the picture's shades.",
+        long_help = "Only works in combination with --stain and --picture. Instead of colouring the cells by their distance from the entrance, use the picture's shade where they are."
+    )]
+    picture_stain: bool,
+    #[arg(
+        long,
+        requires = "font",
+        conflicts_with = "mask",
+        help = "Shape the maze like this text.",
+        long_help = "Uses the outlines of the letters in --font as a mask. The maze is --size cells high and as wide as the text needs. The letters are connected from left to right, so the solution leads through all of them. Only works with the rectilinear shape."
+    )]
+    text: Option<String>,
     #[clap(
         long,
         short = 'b',
@@ -70,7 +267,7 @@ struct Cli {
         value_enum,
         default_value = "rectilinear",
         help = "Shape of the maze.",
-        long_help = "rectilinear will draw a square maze with square cells. theta will draw a circular maze with square-ish cells. sigma will draw a square (in cell count) maze with hexagonal cells. delta will draw a circular maze with equilateral triangular cells."
+        long_help = "rectilinear will draw a square maze with square cells. theta will draw a circular maze with square-ish cells. sigma will draw a square (in cell count) maze with hexagonal cells. delta will draw a circular maze with equilateral triangular cells. layered will draw square mazes next to each other, one per level, connected by stairs."
     )]
     shape: Option<CliShape>,
     #[arg(long, default_value = "false", help = "Also draw a solution.")]
@@ -111,7 +308,7 @@ struct Cli {
     #[arg(
         long,
         help = "Font file to use for metadata",
-        long_help = "Only works in combination with --pdf. If provided, will use this font file to render metadata on the PDF. If this option is ommitted, we'll attempt to use the default font (Helvetica), which may not be available. If no metadata is visible in the PDF, try providing a font file here."
+        long_help = "Used for --text, and for the metadata in combination with --pdf. If provided, will use this font file to render metadata on the PDF. If this option is ommitted, we'll attempt to use the default font (Helvetica), which may not be available. If no metadata is visible in the PDF, try providing a font file here."
     )]
     font: Option<String>,
 }
@@ -136,6 +333,7 @@ impl Cli {
                 Some(CliShape::Sigma) => Shape::Sigma(size),
                 Some(CliShape::Theta) => Shape::Theta(size),
                 Some(CliShape::Delta) => Shape::Delta(size),
+                Some(CliShape::Layered) => Shape::Layered(size, self.levels.unwrap_or(3)),
                 _ => Shape::Rectilinear(size, size),
             },
             colour: self
@@ -147,26 +345,153 @@ impl Cli {
             features,
             algorithm: match self.algorithm {
                 Some(CliAlgorithm::Kruskal) => Algorithm::Kruskal,
-                _ => Algorithm::GrowingTree,
+                Some(CliAlgorithm::Prim) => Algorithm::Prim(if self.weighted {
+                    Frontier::Weighted
+                } else {
+                    Frontier::Random
+                }),
+                Some(CliAlgorithm::Wilson) => Algorithm::Wilson(self.aldous_broder.unwrap_or(0)),
+                Some(CliAlgorithm::AldousBroder) => Algorithm::AldousBroder,
+                Some(CliAlgorithm::Eller) => Algorithm::Eller,
+                Some(CliAlgorithm::RecursiveDivision) => Algorithm::RecursiveDivision,
+                Some(CliAlgorithm::BlobbyDivision) => Algorithm::BlobbyDivision,
+                Some(CliAlgorithm::HuntAndKill) => Algorithm::HuntAndKill,
+                Some(CliAlgorithm::BinaryTree) => Algorithm::BinaryTree,
+                Some(CliAlgorithm::Sidewinder) => Algorithm::Sidewinder,
+                Some(CliAlgorithm::Weave) => Algorithm::Weave(self.crossings.unwrap_or(20)),
+                Some(CliAlgorithm::Dungeon) => Algorithm::Dungeon(self.rooms.unwrap_or(6)),
+                _ => Algorithm::GrowingTree(self.selection.clone().unwrap_or_default()),
             },
             stroke_width: self.stroke_width.unwrap_or(2) as f64,
+            braid: self.braid.unwrap_or(0).min(100),
+            mask: None,
+            bias: self.bias.map(|axis| Bias {
+                axis: match axis {
+                    CliAxis::Horizontal => Axis::Horizontal,
+                    CliAxis::Vertical => Axis::Vertical,
+                },
+                strength: self.bias_strength.unwrap_or(50).min(100),
+            }),
+            picture: None,
+            one_way: self.one_way.unwrap_or(0).min(100),
+            terrain: self.terrain.unwrap_or(0).min(100),
+            portals: self.portals.unwrap_or(0).min(MAX_PORTALS),
+            checkpoints: self.checkpoints.unwrap_or(0).min(Puzzle::MAX_CHECKPOINTS),
+            locks: self.locks.unwrap_or(0).min(Puzzle::MAX_LOCKS),
+            symmetry: self.symmetry.map(|symmetry| match symmetry {
+                CliSymmetry::Mirror => Symmetry::Mirror,
+                CliSymmetry::Rotation => Symmetry::Rotation(self.folds.unwrap_or(2)),
+            }),
+            wrap: Wrap {
+                horizontal: matches!(self.wrap, Some(CliWrap::Horizontal | CliWrap::Both)),
+                vertical: matches!(self.wrap, Some(CliWrap::Vertical | CliWrap::Both)),
+                twist: self.twist,
+            },
+            pairs: self.pairs.unwrap_or(0).min(Puzzle::MAX_PAIRS),
         }
     }
 }
 
+/// Reads a PNG image as a grey scale picture, where transparent pixels are
+/// white.
+fn read_png(data: &[u8]) -> Result<Picture, String> {
+    let mut decoder = png::Decoder::new(data);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(|err| err.to_string())?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut buffer)
+        .map_err(|err| err.to_string())?;
+    let channels = info.color_type.samples();
+    let pixels = buffer[..info.buffer_size()]
+        .chunks(info.line_size)
+        .flat_map(|line| line.chunks(channels).take(info.width as usize))
+        .map(|pixel| {
+            let (colour, alpha) = match pixel {
+                [grey] => (*grey as u32, 255),
+                [grey, alpha] => (*grey as u32, *alpha),
+                [r, g, b] => ((*r as u32 + *g as u32 + *b as u32) / 3, 255),
+                [r, g, b, alpha, ..] => ((*r as u32 + *g as u32 + *b as u32) / 3, *alpha),
+                [] => (255, 0),
+            };
+            if alpha >= 128 {
+                colour as u8
+            } else {
+                u8::MAX
+            }
+        })
+        .collect();
+    Picture::new(info.width as usize, info.height as usize, pixels).map_err(|err| err.to_string())
+}
+
+/// Black pixels are cells, white or transparent ones are holes.
+fn read_png_mask(data: &[u8]) -> Result<Mask, String> {
+    let picture = read_png(data)?;
+    let (width, height) = picture.size();
+    let cells = picture
+        .pixels()
+        .iter()
+        .map(|grey| MaskCell::from(*grey < 128))
+        .collect();
+    Mask::new(width, height, cells).map_err(|err| err.to_string())
+}
+
+fn read_mask(file: &str) -> Result<Mask, String> {
+    let data = std::fs::read(file).map_err(|err| format!("Failed to read mask: {}", err))?;
+    if data.starts_with(b"\x89PNG") {
+        read_png_mask(&data)
+    } else if data.starts_with(b"P1") || data.starts_with(b"P4") {
+        Mask::from_pbm(&data).map_err(|err| err.to_string())
+    } else {
+        let text = String::from_utf8(data).map_err(|err| err.to_string())?;
+        Mask::from_ascii(&text).map_err(|err| err.to_string())
+    }
+}
+
 fn main() -> Result<(), ()> {
     let cli = Cli::parse();
     let mut configuration = cli.get_configuration();
+    if let Some(file) = &cli.mask {
+        configuration.mask = Some(read_mask(file).map_err(|err| eprintln!("{}", err))?);
+    }
+    if let Some(file) = &cli.picture {
+        let mut picture = std::fs::read(file)
+            .map_err(|err| format!("Failed to read picture: {}", err))
+            .and_then(|data| read_png(&data))
+            .map_err(|err| eprintln!("{}", err))?;
+        picture.stain = cli.picture_stain;
+        configuration.picture = Some(picture);
+    }
 
-    let font_data = cli
-        .font
-        .map(|f| std::fs::read(&f).expect("Failed to read font"))
-        .and_then(|data| Font::new(data));
+    let font_data = cli.font.as_ref().and_then(|f| {
+        let name = std::path::Path::new(f)
+            .file_stem()
+            .map_or(f.clone(), |stem| stem.to_string_lossy().to_string());
+        Font::with_fallback_name(std::fs::read(f).expect("Failed to read font"), &name)
+    });
     let font_name = font_data.as_ref().map(|f| f.name.clone());
 
+    if let Some(text) = &cli.text {
+        if !matches!(configuration.shape, Shape::Rectilinear(..)) {
+            eprintln!("--text only works with the rectilinear shape");
+            return Err(());
+        }
+        let font = font_data
+            .as_ref()
+            .ok_or_else(|| eprintln!("Failed to load the font for --text"))?;
+        let mask = font
+            .text_mask(text, cli.size.unwrap_or(20) as usize)
+            .map_err(|err| eprintln!("{}", err))?;
+        let (width, height) = mask.size();
+        configuration.shape = Shape::Rectilinear(width, height);
+        configuration.mask = Some(mask);
+    }
+
     if let Some(svg_file) = cli.svg {
-        let (maze, _) = configuration.execute_for_svg();
-        std::fs::write(svg_file, &maze.document.to_string()).expect("Failed to write SVG");
+        let (maze, _) = configuration
+            .execute_for_svg()
+            .map_err(|err| eprintln!("{}", err))?;
+        std::fs::write(svg_file, maze.document.to_string()).expect("Failed to write SVG");
     }
 
     if let Some(pdf_file) = cli.pdf {
@@ -174,7 +499,9 @@ fn main() -> Result<(), ()> {
         let pages = cli.pages.unwrap_or(1);
 
         for _ in 0..pages {
-            let (maze, new_seed) = configuration.execute_for_svg();
+            let (maze, new_seed) = configuration
+                .execute_for_svg()
+                .map_err(|err| eprintln!("{}", err))?;
             let metadata = Metadata::from_configuration(&configuration, cli.url.clone());
             let maze_with_metadata = metadata.metadata_to_render(maze, &font_name.clone());
             let svg = Svg {
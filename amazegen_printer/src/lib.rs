@@ -1,6 +1,7 @@
 #![allow(mixed_script_confusables)]
 pub mod metadata;
 pub mod pdf;
+pub mod text;
 
 use amazegen::maze::feature::{Configuration, Svg};
 use metadata::Metadata;
@@ -21,7 +22,13 @@ pub fn generate_pdf(js: JsValue, pages: u32, baseurl: String, font: Vec<u8>) ->
     let mut pdf = PdfWriter::new(font);
     let url = Some(baseurl);
     for _ in 0..pages {
-        let (maze, new_seed) = configuration.execute_for_svg();
+        let (maze, new_seed) = match configuration.execute_for_svg() {
+            Ok(result) => result,
+            Err(err) => {
+                log(&format!("Error while generating maze: {}", err));
+                break;
+            }
+        };
         let metadata = Metadata::from_configuration(&configuration, url.clone());
         let maze_with_metadata = metadata.metadata_to_render(maze, &font_name.clone());
         let svg = Svg {
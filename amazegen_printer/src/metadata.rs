@@ -35,7 +35,7 @@ impl Metadata {
         let font_family = family.as_deref().unwrap_or("sans-serif");
         for (i, text) in [
             format!("Shape: {}", shape_to_str(&self.shape),),
-            format!("Algorithm: {:?}", self.algorithm,),
+            format!("Algorithm: {}", self.algorithm,),
             format!("Seed: {}", self.seed),
         ]
         .iter()
@@ -111,6 +111,7 @@ fn shape_to_str(shape: &Shape) -> String {
         Shape::Theta(size) => format!("Theta {}", size),
         Shape::Sigma(size) => format!("Sigma {}", size),
         Shape::Delta(size) => format!("Delta {}", size),
+        Shape::Layered(size, levels) => format!("Layered {}×{}×{}", size, size, levels),
     }
     .to_string()
 }
@@ -4,7 +4,9 @@ use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref};
 use svg2pdf::usvg::Options;
 use ttf_parser::{name_id, Face};
 
-use amazegen::maze::feature::Svg;
+use amazegen::maze::{feature::Svg, shape::mask::Mask};
+
+use crate::text::{text_mask, TextError};
 
 pub struct PdfWriter {
     alloc: Ref,
@@ -25,22 +27,34 @@ pub struct Font {
 }
 
 impl Font {
-    fn get_font_name_from_data(data: Vec<u8>) -> Option<(String, Vec<u8>)> {
-        if let Ok(face) = Face::parse(&data, 0) {
-            face.names()
-                .into_iter()
-                .find(|name| {
-                    name.name_id == name_id::TYPOGRAPHIC_FAMILY || name.name_id == name_id::FAMILY
-                })
-                .and_then(|name| name.to_string())
-                .map(|name| (name, data))
-        } else {
-            None
-        }
+    /// The family name from the first name record that can be decoded. Fonts
+    /// often start with records for old Mac encodings that can't.
+    fn get_font_name(face: &Face) -> Option<String> {
+        face.names()
+            .into_iter()
+            .filter(|name| {
+                name.name_id == name_id::TYPOGRAPHIC_FAMILY || name.name_id == name_id::FAMILY
+            })
+            .find_map(|name| name.to_string())
     }
 
     pub fn new(data: Vec<u8>) -> Option<Self> {
-        Self::get_font_name_from_data(data).map(|(name, data)| Font { name, data })
+        let name = Self::get_font_name(&Face::parse(&data, 0).ok()?)?;
+        Some(Font { name, data })
+    }
+
+    /// Like [`Font::new`], but names the font `fallback` if it doesn't have a
+    /// family name that can be decoded, e.g. after the file it came from.
+    pub fn with_fallback_name(data: Vec<u8>, fallback: &str) -> Option<Self> {
+        let face = Face::parse(&data, 0).ok()?;
+        let name = Self::get_font_name(&face).unwrap_or_else(|| fallback.to_string());
+        Some(Font { name, data })
+    }
+
+    /// A mask in the shape of `text`, `rows` cells high. See [`text_mask`].
+    pub fn text_mask(&self, text: &str, rows: usize) -> Result<Mask, TextError> {
+        let face = Face::parse(&self.data, 0).expect("The font was parsed when it was loaded");
+        text_mask(&face, text, rows)
     }
 }
 
@@ -0,0 +1,177 @@
+use amazegen::maze::shape::mask::{Mask, MaskCell, MaskError};
+use ttf_parser::{Face, OutlineBuilder};
+
+#[derive(Debug)]
+pub enum TextError {
+    MissingGlyph(char),
+    Mask(MaskError),
+}
+
+impl std::fmt::Display for TextError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TextError::MissingGlyph(c) => write!(f, "the font doesn't have a glyph for {:?}", c),
+            TextError::Mask(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for TextError {}
+
+impl From<MaskError> for TextError {
+    fn from(err: MaskError) -> Self {
+        TextError::Mask(err)
+    }
+}
+
+/// Curves get split into this many straight edges.
+const CURVE_STEPS: usize = 8;
+
+/// The outlines of a line of text, made of straight edges, in font units.
+#[derive(Default)]
+struct Outline {
+    edges: Vec<((f32, f32), (f32, f32))>,
+    // where the current glyph starts
+    pen: f32,
+    start: (f32, f32),
+    current: (f32, f32),
+}
+
+impl Outline {
+    fn point(&self, x: f32, y: f32) -> (f32, f32) {
+        (x + self.pen, y)
+    }
+
+    fn edge_to(&mut self, to: (f32, f32)) {
+        self.edges.push((self.current, to));
+        self.current = to;
+    }
+
+    /// The lower left and upper right corner of the box around the outlines.
+    fn bounds(&self) -> Option<((f32, f32), (f32, f32))> {
+        let points = self.edges.iter().map(|(from, _)| *from);
+        points.clone().next().map(|first| {
+            points.fold((first, first), |(min, max), (x, y)| {
+                ((min.0.min(x), min.1.min(y)), (max.0.max(x), max.1.max(y)))
+            })
+        })
+    }
+
+    /// Whether the point is inside the outlines, by the non-zero winding rule
+    /// that TrueType uses.
+    fn contains(&self, (px, py): (f32, f32)) -> bool {
+        self.edges
+            .iter()
+            .map(|&((x0, y0), (x1, y1))| {
+                if (y0 <= py) == (y1 <= py) {
+                    return 0;
+                }
+                let x = x0 + (py - y0) / (y1 - y0) * (x1 - x0);
+                match (x > px, y1 > y0) {
+                    (false, _) => 0,
+                    (true, true) => 1,
+                    (true, false) => -1,
+                }
+            })
+            .sum::<i32>()
+            != 0
+    }
+}
+
+impl OutlineBuilder for Outline {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.start = self.point(x, y);
+        self.current = self.start;
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.edge_to(self.point(x, y));
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let (p0, p1, p2) = (self.current, self.point(x1, y1), self.point(x, y));
+        for step in 1..=CURVE_STEPS {
+            let t = step as f32 / CURVE_STEPS as f32;
+            let u = 1.0 - t;
+            let along = |a: f32, b: f32, c: f32| u * u * a + 2.0 * u * t * b + t * t * c;
+            self.edge_to((along(p0.0, p1.0, p2.0), along(p0.1, p1.1, p2.1)));
+        }
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let (p0, p1, p2, p3) = (
+            self.current,
+            self.point(x1, y1),
+            self.point(x2, y2),
+            self.point(x, y),
+        );
+        for step in 1..=CURVE_STEPS {
+            let t = step as f32 / CURVE_STEPS as f32;
+            let u = 1.0 - t;
+            let along = |a: f32, b: f32, c: f32, d: f32| {
+                u * u * u * a + 3.0 * u * u * t * b + 3.0 * u * t * t * c + t * t * t * d
+            };
+            self.edge_to((along(p0.0, p1.0, p2.0, p3.0), along(p0.1, p1.1, p2.1, p3.1)));
+        }
+    }
+
+    fn close(&mut self) {
+        if self.current != self.start {
+            self.edge_to(self.start);
+        }
+    }
+}
+
+/// Rasterise `text` onto a grid of square cells that's `rows` cells high and
+/// as wide as the text needs. The letters get connected one after the other,
+/// so that the solution of a maze with this mask leads through all of them.
+pub fn text_mask(face: &Face, text: &str, rows: usize) -> Result<Mask, TextError> {
+    let mut outline = Outline::default();
+    for c in text.chars() {
+        let glyph = face.glyph_index(c).ok_or(TextError::MissingGlyph(c))?;
+        // spaces don't have an outline, but still take up room
+        face.outline_glyph(glyph, &mut outline);
+        outline.pen += face.glyph_hor_advance(glyph).unwrap_or(0) as f32;
+    }
+    let (min, max) = outline.bounds().ok_or(MaskError::Empty)?;
+    let rows = rows.max(1);
+    let cell = (max.1 - min.1) / rows as f32;
+    let columns = ((max.0 - min.0) / cell).ceil().max(1.0) as usize;
+    let cells = (0..rows)
+        .flat_map(|y| (0..columns).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            // rows go down, but the font's y axis goes up
+            let centre = (
+                min.0 + (x as f32 + 0.5) * cell,
+                max.1 - (y as f32 + 0.5) * cell,
+            );
+            MaskCell::from(outline.contains(centre))
+        })
+        .collect();
+    Ok(Mask::new(columns, rows, cells)?.chain())
+}
+
+#[cfg(test)]
+mod test {
+    use super::text_mask;
+    use amazegen::maze::shape::regular::RectilinearMaze;
+    use ttf_parser::Face;
+
+    #[test]
+    fn words_with_dots_and_descenders_become_mazes() {
+        let font =
+            include_bytes!("../../web/amazegen-frontend/src/assets/fonts/Bitter-Regular.ttf");
+        let face = Face::parse(font, 0).unwrap();
+        for word in [
+            "Hi", "Ali", "Kai", "Max", "Emily", "Happy", "Birthday", "Café",
+        ] {
+            let mask = text_mask(&face, word, 14).unwrap();
+            let mut maze = RectilinearMaze::new(mask.size());
+            assert!(
+                maze.apply_mask(&mask).is_ok(),
+                "{:?} should have an entrance and an exit",
+                word
+            );
+        }
+    }
+}
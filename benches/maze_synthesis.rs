@@ -1,37 +1,75 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
 use amazegen::{
     generate_seed,
-    maze::feature::{Algorithm, Configuration, Shape},
+    maze::{
+        algorithms::{Selection, Weights},
+        arengee::Arengee,
+        feature::{Algorithm, Configuration, Shape},
+        interface::Maze,
+        shape::{delta::DeltaMaze, regular::RectilinearMaze, sigma::SigmaMaze, theta::RingMaze},
+    },
+};
+use criterion::{
+    criterion_group, criterion_main, measurement::WallTime, BatchSize, BenchmarkGroup, Criterion,
 };
-use criterion::{criterion_group, criterion_main, Criterion};
+
+/// Keeps track of the most heap memory in use at any one time, so we can
+/// compare how much memory the algorithms need.
+struct PeakAllocator;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for PeakAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let now = CURRENT.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(now, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: PeakAllocator = PeakAllocator;
+
+const LARGE_RINGS: usize = 60;
+const LARGE_SIZE: usize = 200;
 
 fn kruskal_rect(c: &mut Criterion) {
-    c.bench_function("maze_synthesis", |b| {
+    c.bench_function("kruskal_rect", |b| {
         b.iter(|| {
             Configuration {
                 algorithm: Algorithm::Kruskal,
-                colour: "000000".into(),
-                features: vec![],
                 seed: generate_seed(),
                 shape: Shape::Rectilinear(10, 10),
                 stroke_width: 4.0,
+                ..Default::default()
             }
-            .display_maze()
+            .execute_for_svg()
         })
     });
 }
 
 fn jarník_rect(c: &mut Criterion) {
-    c.bench_function("maze_synthesis", |b| {
+    c.bench_function("jarník_rect", |b| {
         b.iter(|| {
             Configuration {
-                algorithm: Algorithm::GrowingTree,
-                colour: "000000".into(),
-                features: vec![],
+                algorithm: Algorithm::GrowingTree(Selection::default()),
                 seed: generate_seed(),
                 shape: Shape::Rectilinear(10, 10),
                 stroke_width: 4.0,
+                ..Default::default()
             }
-            .display_maze()
+            .execute_for_svg()
         })
     });
 }
@@ -40,17 +78,16 @@ fn jarník_theta(c: &mut Criterion) {
     let mut group = c.benchmark_group("theta");
     group.warm_up_time(std::time::Duration::from_secs(6));
     group.measurement_time(std::time::Duration::from_secs(10));
-    group.bench_function("maze_synthesis", |b| {
+    group.bench_function("jarník", |b| {
         b.iter(|| {
             Configuration {
-                algorithm: Algorithm::GrowingTree,
-                colour: "000000".into(),
-                features: vec![],
+                algorithm: Algorithm::GrowingTree(Selection::default()),
                 seed: generate_seed(),
                 shape: Shape::Theta(10),
                 stroke_width: 4.0,
+                ..Default::default()
             }
-            .display_maze()
+            .execute_for_svg()
         })
     });
     group.finish();
@@ -60,27 +97,104 @@ fn kruskal_theta(c: &mut Criterion) {
     let mut group = c.benchmark_group("theta");
     group.warm_up_time(std::time::Duration::from_secs(6));
     group.measurement_time(std::time::Duration::from_secs(10));
-    group.bench_function("maze_synthesis", |b| {
+    group.bench_function("kruskal", |b| {
         b.iter(|| {
             Configuration {
                 algorithm: Algorithm::Kruskal,
-                colour: "000000".into(),
-                features: vec![],
                 seed: generate_seed(),
                 shape: Shape::Theta(10),
                 stroke_width: 4.0,
+                ..Default::default()
             }
-            .display_maze()
+            .execute_for_svg()
         })
     });
     group.finish();
 }
 
+/// Only runs the algorithm, without solving or rendering the maze.
+fn generate_large_theta(algorithm: &Algorithm) -> RingMaze {
+    let mut rng = Arengee::new(generate_seed());
+    algorithm
+        .execute(RingMaze::new(LARGE_RINGS, 8), &mut rng, Weights::default())
+        .unwrap()
+}
+
+/// Criterion only measures time, so we print the peak memory use here.
+fn peak_memory(algorithm: &Algorithm) -> usize {
+    let template = RingMaze::new(LARGE_RINGS, 8);
+    let mut rng = Arengee::new(1);
+    let before = CURRENT.load(Ordering::Relaxed);
+    PEAK.store(before, Ordering::Relaxed);
+    let maze = algorithm
+        .execute(template, &mut rng, Weights::default())
+        .unwrap();
+    let peak = PEAK.load(Ordering::Relaxed) - before;
+    drop(maze);
+    peak
+}
+
+fn large_theta(c: &mut Criterion) {
+    let algorithms = [
+        ("jarník", Algorithm::GrowingTree(Selection::default())),
+        ("hunt_and_kill", Algorithm::HuntAndKill),
+    ];
+    for (name, algorithm) in algorithms.iter() {
+        println!(
+            "{} on a {} ring theta maze: {} bytes peak heap use",
+            name,
+            LARGE_RINGS,
+            peak_memory(algorithm)
+        );
+    }
+
+    let mut group = c.benchmark_group("large_theta");
+    group.sample_size(20);
+    for (name, algorithm) in algorithms.iter() {
+        group.bench_function(*name, |b| b.iter(|| generate_large_theta(algorithm)));
+    }
+    group.finish();
+}
+
+/// Kruskal on mazes of the size we generate in batch, for every shape.
+fn large_kruskal(c: &mut Criterion) {
+    fn bench<M: Maze>(
+        group: &mut BenchmarkGroup<'_, WallTime>,
+        name: &str,
+        template: impl Fn() -> M,
+    ) {
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                &template,
+                |maze| {
+                    let mut rng = Arengee::new(generate_seed());
+                    Algorithm::Kruskal
+                        .execute(maze, &mut rng, Weights::default())
+                        .unwrap()
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+
+    let mut group = c.benchmark_group("large_kruskal");
+    group.sample_size(10);
+    bench(&mut group, "rectilinear", || {
+        RectilinearMaze::new((LARGE_SIZE, LARGE_SIZE))
+    });
+    bench(&mut group, "theta", || RingMaze::new(LARGE_RINGS, 8));
+    bench(&mut group, "sigma", || SigmaMaze::new(LARGE_SIZE));
+    bench(&mut group, "delta", || DeltaMaze::new(LARGE_SIZE as u32));
+    group.finish();
+}
+
 criterion_group!(
     benches,
     kruskal_rect,
     jarník_rect,
     kruskal_theta,
-    jarník_theta
+    jarník_theta,
+    large_theta,
+    large_kruskal
 );
 criterion_main!(benches);
@@ -8,8 +8,94 @@ import {
 } from 'solid-js';
 import { generate_seed, run_configuration } from 'amazegen';
 
-export const algorithms = ['Kruskal', 'GrowingTree'] as const;
-export type Algorithm = (typeof algorithms)[number];
+export const picks = ['Newest', 'Oldest', 'Random', 'Middle'] as const;
+export type Pick = (typeof picks)[number];
+export type Selection = [Pick, number][];
+export const DEFAULT_SELECTION: Selection = [['Newest', 1]];
+
+export const pickWeight = (selection: Selection, pick: Pick): number =>
+  selection.find(([p]) => p === pick)?.[1] ?? 0;
+
+// Sets the weight of one pick, dropping picks without weight. Falls back to
+// the default when nothing is left, since a selection needs at least one pick.
+export const withPickWeight = (
+  selection: Selection,
+  pick: Pick,
+  weight: number,
+): Selection => {
+  const updated: Selection = picks
+    .map((p): [Pick, number] => [
+      p,
+      p === pick ? Math.floor(weight) : pickWeight(selection, p),
+    ])
+    .filter(([, w]) => w > 0);
+  return updated.length > 0 ? updated : DEFAULT_SELECTION;
+};
+
+export interface AlgorithmGrowingTree {
+  GrowingTree: Selection;
+}
+
+export interface AlgorithmWilson {
+  Wilson: number;
+}
+
+export interface AlgorithmWeave {
+  Weave: number;
+}
+
+export interface AlgorithmDungeon {
+  Dungeon: number;
+}
+
+export type Frontier = 'Random' | 'Weighted';
+
+export interface AlgorithmPrim {
+  Prim: Frontier;
+}
+
+// Algorithms without parameters
+export const simpleAlgorithms = [
+  'Kruskal',
+  'AldousBroder',
+  'Eller',
+  'RecursiveDivision',
+  'BlobbyDivision',
+  'HuntAndKill',
+  'BinaryTree',
+  'Sidewinder',
+] as const;
+export type SimpleAlgorithm = (typeof simpleAlgorithms)[number];
+
+export type Algorithm =
+  | SimpleAlgorithm
+  | AlgorithmGrowingTree
+  | AlgorithmWilson
+  | AlgorithmPrim
+  | AlgorithmWeave
+  | AlgorithmDungeon;
+export type AlgorithmKeys =
+  | SimpleAlgorithm
+  | 'GrowingTree'
+  | 'Wilson'
+  | 'Prim'
+  | 'Weave'
+  | 'Dungeon';
+
+const isSimpleAlgorithm = (
+  algorithm: Algorithm | string,
+): algorithm is SimpleAlgorithm =>
+  simpleAlgorithms.includes(algorithm as SimpleAlgorithm);
+
+// These only work for rectilinear mazes
+export const rectilinearAlgorithms: AlgorithmKeys[] = [
+  'Eller',
+  'RecursiveDivision',
+  'BinaryTree',
+  'Sidewinder',
+  'Weave',
+  'Dungeon',
+];
 export const features = ['Stain', 'Solve'] as const;
 export type Feature = (typeof features)[number];
 
@@ -29,9 +115,67 @@ export interface ShapeDelta {
   Delta: number;
 }
 
+// size and number of levels
+export interface ShapeLayered {
+  Layered: [number, number];
+}
+
 type KeysOfUnion<T> = T extends T ? keyof T : never;
 export type ShapeKeys = KeysOfUnion<Shape>;
-export type Shape = ShapeRectilinear | ShapeTheta | ShapeSigma | ShapeDelta;
+export type Shape =
+  | ShapeRectilinear
+  | ShapeTheta
+  | ShapeSigma
+  | ShapeDelta
+  | ShapeLayered;
+
+export type Axis = 'Horizontal' | 'Vertical';
+
+export interface Bias {
+  axis: Axis;
+  strength: number;
+}
+
+export type Symmetry = 'Mirror' | { Rotation: number };
+
+// Only these shapes can be symmetric, and only circles can be turned by an
+// eighth
+export const symmetryFits = (
+  symmetry: Symmetry | null,
+  shape: ShapeKeys,
+): boolean =>
+  symmetry === null ||
+  shape === 'Theta' ||
+  (shape === 'Rectilinear' && (symmetry === 'Mirror' || symmetry.Rotation < 8));
+
+// These can't generate symmetric mazes
+export const asymmetricAlgorithms: AlgorithmKeys[] = ['Weave', 'Dungeon'];
+
+export interface Wrap {
+  horizontal: boolean;
+  vertical: boolean;
+  twist: boolean;
+}
+
+export const NO_WRAP: Wrap = {
+  horizontal: false,
+  vertical: false,
+  twist: false,
+};
+
+// Only these shapes can wrap around
+export const wrapFits = (wrap: Wrap, shape: ShapeKeys): boolean =>
+  (!wrap.horizontal && !wrap.vertical) ||
+  shape === 'Rectilinear' ||
+  shape === 'Sigma';
+
+// These can't generate mazes that wrap around
+export const unwrappableAlgorithms: AlgorithmKeys[] = [
+  'Eller',
+  'RecursiveDivision',
+  'BinaryTree',
+  'Sidewinder',
+];
 
 export interface Configuration {
   algorithm: Algorithm;
@@ -40,6 +184,16 @@ export interface Configuration {
   seed: bigint;
   shape: Shape;
   stroke_width: number;
+  braid: number;
+  bias: Bias | null;
+  one_way: number;
+  terrain: number;
+  portals: number;
+  checkpoints: number;
+  locks: number;
+  symmetry: Symmetry | null;
+  wrap: Wrap;
+  pairs: number;
 }
 
 export type SVG = string;
@@ -49,11 +203,21 @@ export const DEFAULT_MAZE_SIZE = 10;
 const readFromHash = (): Configuration => {
   const getDefaultConfiguration = (): Configuration => ({
     seed: generate_seed(),
-    algorithm: 'GrowingTree',
+    algorithm: { GrowingTree: DEFAULT_SELECTION },
     shape: { Rectilinear: [DEFAULT_MAZE_SIZE, DEFAULT_MAZE_SIZE] },
     features: [],
     colour: 'EEEEEE',
     stroke_width: 8,
+    braid: 0,
+    bias: null,
+    one_way: 0,
+    terrain: 0,
+    portals: 0,
+    checkpoints: 0,
+    locks: 0,
+    symmetry: null,
+    wrap: NO_WRAP,
+    pairs: 0,
   });
 
   const parseSize = (str: string | undefined): number | undefined => {
@@ -77,6 +241,10 @@ const readFromHash = (): Configuration => {
     if (size !== undefined && str.startsWith('D')) {
       return delta(size);
     }
+    const levels = /^L(\d+)x(\d+)$/.exec(str);
+    if (levels !== null) {
+      return layered(Number(levels[1]), Number(levels[2]));
+    }
 
     const legacy = parseSize(str);
     if (legacy !== undefined) {
@@ -94,29 +262,116 @@ const readFromHash = (): Configuration => {
     }
   };
 
+  const parsePick = (str: string): Pick | undefined =>
+    picks.find((p) => p.toLowerCase() === str.trim().toLowerCase());
+
+  // e.g. newest:75,random:25
+  const parseSelection = (str: string): Selection | undefined => {
+    const selection: Selection = [];
+    for (const part of str.split(',')) {
+      const [name, weight] = part.split(':');
+      const pick = parsePick(name);
+      const n = weight === undefined ? 1 : parseSize(weight);
+      if (pick === undefined || n === undefined || n < 1) return undefined;
+      selection.push([pick, Math.floor(n)]);
+    }
+    return selection.length > 0 ? selection : undefined;
+  };
+
   const parseAlgorithm = (str: string | undefined): Algorithm | undefined => {
-    if (algorithms.includes(str as Algorithm)) {
-      return str as Algorithm;
+    if (str === undefined) return undefined;
+    if (isSimpleAlgorithm(str)) return str;
+    if (str === 'GrowingTree') return { GrowingTree: DEFAULT_SELECTION };
+    if (str === 'Wilson') return { Wilson: 0 };
+    if (str === 'Prim') return { Prim: 'Random' };
+    if (str === 'Prim[weighted]') return { Prim: 'Weighted' };
+    const crossings = /^Weave\[(\d+)\]$/.exec(str);
+    if (crossings !== null) {
+      return { Weave: Math.min(100, Number(crossings[1])) };
+    }
+    const rooms = /^Dungeon\[(\d+)\]$/.exec(str);
+    if (rooms !== null) {
+      return { Dungeon: Math.min(255, Number(rooms[1])) };
+    }
+    const aldousBroder = /^Wilson\[(\d+)\]$/.exec(str);
+    if (aldousBroder !== null) {
+      return { Wilson: Math.min(100, Number(aldousBroder[1])) };
+    }
+    const selection = /^GrowingTree\[(.*)\]$/.exec(decodeURIComponent(str));
+    if (selection !== null) {
+      const parsed = parseSelection(selection[1]);
+      return parsed !== undefined ? { GrowingTree: parsed } : undefined;
     }
     return undefined;
   };
 
-  const parse = [parseShape, parseAlgorithm, parseBigint];
-  const [shape, algorithm, seed] =
-    (document?.location.hash
-      .substring(1)
-      .split('|')
-      .map((str, index) => parse[index](str)) as [
-      Shape | undefined,
-      Algorithm | undefined,
-      bigint | undefined,
-    ]) ?? [];
+  // everything after the seed is optional, e.g. B50
+  const parseOptions = (parts: string[]): Partial<Configuration> => {
+    const options: Partial<Configuration> = {};
+    for (const part of parts) {
+      const braid = /^B(\d+)$/.exec(part);
+      if (braid !== null) {
+        options.braid = Math.min(100, Number(braid[1]));
+      }
+      const bias = /^([HV])(\d+)$/.exec(part);
+      if (bias !== null) {
+        options.bias = {
+          axis: bias[1] === 'H' ? 'Horizontal' : 'Vertical',
+          strength: Math.min(100, Number(bias[2])),
+        };
+      }
+      const oneWay = /^O(\d+)$/.exec(part);
+      if (oneWay !== null) {
+        options.one_way = Math.min(100, Number(oneWay[1]));
+      }
+      const terrain = /^C(\d+)$/.exec(part);
+      if (terrain !== null) {
+        options.terrain = Math.min(100, Number(terrain[1]));
+      }
+      const portals = /^P(\d+)$/.exec(part);
+      if (portals !== null) {
+        options.portals = Math.min(12, Number(portals[1]));
+      }
+      const checkpoints = /^W(\d+)$/.exec(part);
+      if (checkpoints !== null) {
+        options.checkpoints = Math.min(20, Number(checkpoints[1]));
+      }
+      const locks = /^K(\d+)$/.exec(part);
+      if (locks !== null) {
+        options.locks = Math.min(8, Number(locks[1]));
+      }
+      const symmetry = /^Y(M|\d+)$/.exec(part);
+      if (symmetry !== null) {
+        options.symmetry =
+          symmetry[1] === 'M' ? 'Mirror' : { Rotation: Number(symmetry[1]) };
+      }
+      const wrap = /^X(H?)(V?)(T?)$/.exec(part);
+      if (wrap !== null) {
+        options.wrap = {
+          horizontal: wrap[1] !== '',
+          vertical: wrap[2] !== '',
+          twist: wrap[3] !== '',
+        };
+      }
+      const pairs = /^A(\d+)$/.exec(part);
+      if (pairs !== null) {
+        options.pairs = Math.min(8, Number(pairs[1]));
+      }
+    }
+    return options;
+  };
+
+  const parts = document?.location.hash.substring(1).split('|') ?? [];
+  const shape = parseShape(parts[0]);
+  const algorithm = parseAlgorithm(parts[1]);
+  const seed = parseBigint(parts[2]);
 
   return {
     ...getDefaultConfiguration(),
     ...(shape !== undefined && { shape }),
     ...(seed !== undefined && { seed }),
     ...(algorithm !== undefined && { algorithm }),
+    ...parseOptions(parts.slice(3)),
   };
 };
 
@@ -130,14 +385,100 @@ const hashShape = (shape: Shape): string => {
   if ('Delta' in shape) {
     return `D${shape.Delta}`;
   }
+  if ('Layered' in shape) {
+    return `L${shape.Layered[0]}x${shape.Layered[1]}`;
+  }
   return `T${shape.Theta}`;
 };
 
+export const hashAlgorithm = (algorithm: Algorithm): string => {
+  if (isSimpleAlgorithm(algorithm)) {
+    return algorithm;
+  }
+  if ('Wilson' in algorithm) {
+    return algorithm.Wilson === 0 ? 'Wilson' : `Wilson[${algorithm.Wilson}]`;
+  }
+  if ('Prim' in algorithm) {
+    return algorithm.Prim === 'Random' ? 'Prim' : 'Prim[weighted]';
+  }
+  if ('Weave' in algorithm) {
+    return `Weave[${algorithm.Weave}]`;
+  }
+  if ('Dungeon' in algorithm) {
+    return `Dungeon[${algorithm.Dungeon}]`;
+  }
+  const selection = algorithm.GrowingTree;
+  if (
+    selection.length === 1 &&
+    selection[0][0] === 'Newest' &&
+    selection[0][1] === 1
+  ) {
+    return 'GrowingTree';
+  }
+  return `GrowingTree[${selection
+    .map(([pick, weight]) => `${pick.toLowerCase()}:${weight}`)
+    .join(',')}]`;
+};
+
+export const algorithmKey = (algorithm: Algorithm): AlgorithmKeys => {
+  if (isSimpleAlgorithm(algorithm)) {
+    return algorithm;
+  }
+  if ('Prim' in algorithm) {
+    return 'Prim';
+  }
+  if ('Weave' in algorithm) {
+    return 'Weave';
+  }
+  if ('Dungeon' in algorithm) {
+    return 'Dungeon';
+  }
+  return 'Wilson' in algorithm ? 'Wilson' : 'GrowingTree';
+};
+
+const hashSymmetry = (symmetry: Symmetry | null): string =>
+  symmetry === null
+    ? ''
+    : symmetry === 'Mirror'
+      ? '|YM'
+      : `|Y${symmetry.Rotation}`;
+
+const hashWrap = ({ horizontal, vertical, twist }: Wrap): string =>
+  horizontal || vertical
+    ? `|X${horizontal ? 'H' : ''}${vertical ? 'V' : ''}${twist ? 'T' : ''}`
+    : '';
+
+const hashBias = (bias: Bias | null): string =>
+  bias === null
+    ? ''
+    : `|${bias.axis === 'Horizontal' ? 'H' : 'V'}${bias.strength}`;
+
 export const computeHash = ({
   seed,
   shape,
   algorithm,
-}: Configuration): string => `${hashShape(shape)}|${algorithm}|${seed}`;
+  braid,
+  bias,
+  one_way,
+  terrain,
+  portals,
+  checkpoints,
+  locks,
+  symmetry,
+  wrap,
+  pairs,
+}: Configuration): string =>
+  `${hashShape(shape)}|${hashAlgorithm(algorithm)}|${seed}` +
+  (braid > 0 ? `|B${braid}` : '') +
+  hashBias(bias) +
+  (one_way > 0 ? `|O${one_way}` : '') +
+  (terrain > 0 ? `|C${terrain}` : '') +
+  (portals > 0 ? `|P${portals}` : '') +
+  (checkpoints > 0 ? `|W${checkpoints}` : '') +
+  (locks > 0 ? `|K${locks}` : '') +
+  hashSymmetry(symmetry) +
+  hashWrap(wrap) +
+  (pairs > 1 ? `|A${pairs}` : '');
 
 export const configurationHashSignal = (): {
   configuration: Accessor<Configuration>;
@@ -148,24 +489,41 @@ export const configurationHashSignal = (): {
   newSeed: () => Configuration;
   getSize: () => number;
   setAlgorithm: (a: Algorithm) => Configuration;
+  setBraid: (b: number) => Configuration;
+  setBias: (b: Bias | null) => Configuration;
+  setOneWay: (o: number) => Configuration;
+  setTerrain: (t: number) => Configuration;
+  setPortals: (p: number) => Configuration;
+  setCheckpoints: (c: number) => Configuration;
+  setLocks: (k: number) => Configuration;
+  setSymmetry: (s: Symmetry | null) => Configuration;
+  setWrap: (w: Wrap) => Configuration;
+  setPairs: (p: number) => Configuration;
   addFeature: (f: Feature) => Configuration;
   removeFeature: (f: Feature) => Configuration;
   toggleFeature: (f: Feature) => Configuration;
   svg: Accessor<SVG>;
+  error: Accessor<string | undefined>;
 } => {
   const [configuration, setConfiguration] = createSignal(readFromHash());
-  const result = createMemo(() => {
-    const r: { svg: string; hash: string } | null = run_configuration(
-      configuration(),
-    );
-    if (r !== null) {
-      return r;
-    }
-  });
+  // run_configuration throws a message if the configuration can't be built
+  const result = createMemo(
+    (): { svg: string; hash: string } | { error: string } => {
+      try {
+        return run_configuration(configuration());
+      } catch (e) {
+        return { error: String(e) };
+      }
+    },
+  );
+  const response = (): { svg: string; hash: string } | undefined => {
+    const r = result();
+    return 'error' in r ? undefined : r;
+  };
 
   createEffect(() => {
     if (document.location !== undefined) {
-      document.location.hash = result()?.hash ?? '';
+      document.location.hash = response()?.hash ?? '';
     }
   });
 
@@ -182,6 +540,11 @@ export const configurationHashSignal = (): {
     if ('Delta' in a && 'Delta' in b) {
       return a.Delta === b.Delta;
     }
+    if ('Layered' in a && 'Layered' in b) {
+      return (
+        a.Layered[0] === b.Layered[0] && a.Layered[1] === b.Layered[1]
+      );
+    }
     return false;
   };
 
@@ -190,7 +553,17 @@ export const configurationHashSignal = (): {
     const hash = readFromHash();
     if (
       current.seed !== hash.seed ||
-      current.algorithm !== hash.algorithm ||
+      hashAlgorithm(current.algorithm) !== hashAlgorithm(hash.algorithm) ||
+      current.braid !== hash.braid ||
+      hashBias(current.bias) !== hashBias(hash.bias) ||
+      current.one_way !== hash.one_way ||
+      current.terrain !== hash.terrain ||
+      current.portals !== hash.portals ||
+      current.checkpoints !== hash.checkpoints ||
+      current.locks !== hash.locks ||
+      hashSymmetry(current.symmetry) !== hashSymmetry(hash.symmetry) ||
+      hashWrap(current.wrap) !== hashWrap(hash.wrap) ||
+      current.pairs !== hash.pairs ||
       !shapeEquals(current.shape, hash.shape)
     ) {
       setConfiguration(readFromHash());
@@ -232,6 +605,11 @@ export const configurationHashSignal = (): {
         ...configuration(),
         shape: { Delta: by(shape.Delta) },
       });
+    } else if ('Layered' in shape) {
+      return setConfiguration({
+        ...configuration(),
+        shape: layered(by(shape.Layered[0]), shape.Layered[1]),
+      });
     } else {
       return setConfiguration({
         ...configuration(),
@@ -248,6 +626,8 @@ export const configurationHashSignal = (): {
       return shape.Theta;
     } else if ('Delta' in shape) {
       return shape.Delta;
+    } else if ('Layered' in shape) {
+      return shape.Layered[0];
     } else {
       return shape.Sigma;
     }
@@ -275,6 +655,8 @@ export const configurationHashSignal = (): {
         return delta(size);
       case 'Sigma':
         return sigma(size);
+      case 'Layered':
+        return layered(size);
     }
   };
 
@@ -284,6 +666,15 @@ export const configurationHashSignal = (): {
       setConfiguration({
         ...configuration(),
         shape: setShape(shape),
+        ...(shape !== 'Rectilinear' &&
+          rectilinearAlgorithms.includes(
+            algorithmKey(configuration().algorithm),
+          ) && { algorithm: { GrowingTree: DEFAULT_SELECTION } }),
+        ...(!symmetryFits(configuration().symmetry, shape) && {
+          symmetry: null,
+        }),
+        ...(!wrapFits(configuration().wrap, shape) && { wrap: NO_WRAP }),
+        ...(shape === 'Layered' && { pairs: 0 }),
       }),
     setSize: (s: number): Configuration => adjustSize(() => s),
     incrementSize: (): Configuration => adjustSize((old) => old + 1),
@@ -291,13 +682,67 @@ export const configurationHashSignal = (): {
     newSeed: (): Configuration =>
       setConfiguration({ ...configuration(), seed: generate_seed() }),
     setAlgorithm: (algorithm: Algorithm): Configuration =>
-      setConfiguration({ ...configuration(), algorithm }),
+      setConfiguration({
+        ...configuration(),
+        algorithm,
+        ...(asymmetricAlgorithms.includes(algorithmKey(algorithm)) && {
+          symmetry: null,
+        }),
+        ...(unwrappableAlgorithms.includes(algorithmKey(algorithm)) && {
+          wrap: NO_WRAP,
+        }),
+      }),
+    setBraid: (braid: number): Configuration =>
+      setConfiguration({
+        ...configuration(),
+        braid: Math.floor(Math.max(0, Math.min(100, braid))),
+      }),
+    setBias: (bias: Bias | null): Configuration =>
+      setConfiguration({ ...configuration(), bias }),
+    setOneWay: (oneWay: number): Configuration =>
+      setConfiguration({
+        ...configuration(),
+        one_way: Math.floor(Math.max(0, Math.min(100, oneWay))),
+      }),
+    setTerrain: (terrain: number): Configuration =>
+      setConfiguration({
+        ...configuration(),
+        terrain: Math.floor(Math.max(0, Math.min(100, terrain))),
+      }),
+    setPortals: (portals: number): Configuration =>
+      setConfiguration({
+        ...configuration(),
+        portals: Math.floor(Math.max(0, Math.min(12, portals))),
+      }),
+    setCheckpoints: (checkpoints: number): Configuration =>
+      setConfiguration({
+        ...configuration(),
+        checkpoints: Math.floor(Math.max(0, Math.min(20, checkpoints))),
+      }),
+    setLocks: (locks: number): Configuration =>
+      setConfiguration({
+        ...configuration(),
+        locks: Math.floor(Math.max(0, Math.min(8, locks))),
+      }),
+    setSymmetry: (symmetry: Symmetry | null): Configuration =>
+      setConfiguration({ ...configuration(), symmetry }),
+    setWrap: (wrap: Wrap): Configuration =>
+      setConfiguration({ ...configuration(), wrap }),
+    setPairs: (pairs: number): Configuration =>
+      setConfiguration({
+        ...configuration(),
+        pairs: Math.floor(Math.max(0, Math.min(8, pairs))),
+      }),
     getSize,
     addFeature,
     removeFeature,
     toggleFeature: (f): Configuration =>
       configuration().features.includes(f) ? removeFeature(f) : addFeature(f),
-    svg: () => result()?.svg ?? '',
+    svg: () => response()?.svg ?? '',
+    error: () => {
+      const r = result();
+      return 'error' in r ? r.error : undefined;
+    },
   };
 };
 
@@ -309,3 +754,7 @@ const rect = (size: number): Shape => ({
 const theta = (size: number): Shape => ({ Theta: clamp(size, 50) });
 const sigma = (size: number): Shape => ({ Sigma: clamp(size, 100) });
 const delta = (size: number): Shape => ({ Delta: clamp(size, 100) });
+export const DEFAULT_LEVELS = 3;
+const layered = (size: number, levels = DEFAULT_LEVELS): Shape => ({
+  Layered: [clamp(size, 50), clamp(levels, 10)],
+});
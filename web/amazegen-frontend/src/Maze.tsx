@@ -1,5 +1,22 @@
-import { JSX, createSignal, createEffect } from 'solid-js';
-import { Configuration, configurationHashSignal } from './Configuration';
+import { JSX, Show, createSignal, createEffect } from 'solid-js';
+import {
+  Bias,
+  Configuration,
+  DEFAULT_SELECTION,
+  Selection,
+  ShapeKeys,
+  Symmetry,
+  algorithmKey,
+  asymmetricAlgorithms,
+  hashAlgorithm,
+  configurationHashSignal,
+  pickWeight,
+  picks,
+  symmetryFits,
+  unwrappableAlgorithms,
+  withPickWeight,
+  wrapFits,
+} from './Configuration';
 import { saveAs } from 'file-saver';
 import { fetchFont } from './font';
 
@@ -20,23 +37,101 @@ const generatePdf = async (config: Configuration, pages: number) => {
   saveAs(blob, 'maze.pdf');
 };
 
+// vertical on the left, horizontal on the right
+const signedBias = (bias: Bias | null): number =>
+  bias === null
+    ? 0
+    : bias.axis === 'Horizontal'
+      ? bias.strength
+      : -bias.strength;
+
+const describeBias = (bias: Bias | null): string =>
+  bias === null
+    ? 'No preferred direction'
+    : `Prefer ${bias.axis.toLowerCase()} passages by ${bias.strength}%`;
+
+const sameSymmetry = (a: Symmetry | null, b: Symmetry | null): boolean =>
+  JSON.stringify(a) === JSON.stringify(b);
+
 export default function Maze(): JSX.Element {
   let svgRef: HTMLDivElement | undefined;
   let input: HTMLInputElement | undefined;
   let pdfInput: HTMLInputElement | undefined;
+  let braidInput: HTMLInputElement | undefined;
+  let oneWayInput: HTMLInputElement | undefined;
+  let terrainInput: HTMLInputElement | undefined;
+  let portalsInput: HTMLInputElement | undefined;
+  let checkpointsInput: HTMLInputElement | undefined;
+  let locksInput: HTMLInputElement | undefined;
+  let pairsInput: HTMLInputElement | undefined;
+  let biasInput: HTMLInputElement | undefined;
 
   const {
     configuration,
     toggleFeature,
     newSeed,
     setAlgorithm,
+    setBraid,
+    setOneWay,
+    setTerrain,
+    setPortals,
+    setCheckpoints,
+    setLocks,
+    setSymmetry,
+    setWrap,
+    setPairs,
+    setBias,
     setSize,
     setShape,
     incrementSize,
     decrementSize,
     getSize,
     svg,
+    error,
   } = configurationHashSignal();
+
+  const symmetryInput = (symmetry: Symmetry | null): JSX.Element => (
+    <input
+      type="radio"
+      onInput={() => setSymmetry(symmetry)}
+      disabled={
+        !symmetryFits(
+          symmetry,
+          Object.keys(configuration().shape)[0] as ShapeKeys,
+        ) ||
+        (symmetry !== null &&
+          asymmetricAlgorithms.includes(
+            algorithmKey(configuration().algorithm),
+          ))
+      }
+      checked={sameSymmetry(configuration().symmetry, symmetry)}
+    />
+  );
+  const cannotWrap = (): boolean =>
+    !wrapFits(
+      { horizontal: true, vertical: true, twist: false },
+      Object.keys(configuration().shape)[0] as ShapeKeys,
+    ) ||
+    unwrappableAlgorithms.includes(algorithmKey(configuration().algorithm));
+  const wrapInput = (horizontal: boolean, vertical: boolean): JSX.Element => (
+    <input
+      type="radio"
+      onInput={() =>
+        setWrap({ ...configuration().wrap, horizontal, vertical })
+      }
+      disabled={(horizontal || vertical) && cannotWrap()}
+      checked={
+        configuration().wrap.horizontal === horizontal &&
+        configuration().wrap.vertical === vertical
+      }
+    />
+  );
+  const selection = (): Selection | undefined => {
+    const algorithm = configuration().algorithm;
+    return typeof algorithm === 'object' && 'GrowingTree' in algorithm
+      ? algorithm.GrowingTree
+      : undefined;
+  };
   const [numberOfMazes, setNumberOfMazes] = createSignal(4);
 
   createEffect(() => {
@@ -97,25 +192,357 @@ export default function Maze(): JSX.Element {
           />
           Triangle
         </label>
+        <label>
+          <input
+            type="radio"
+            onInput={() => setShape('Layered')}
+            checked={'Layered' in configuration().shape}
+          />
+          Levels
+        </label>
       </section>
       <section>
         <h2>Algorithm</h2>
         <label>
           <input
             type="radio"
-            onInput={() => setAlgorithm('GrowingTree')}
-            checked={configuration().algorithm === 'GrowingTree'}
+            onInput={() => setAlgorithm({ GrowingTree: DEFAULT_SELECTION })}
+            checked={algorithmKey(configuration().algorithm) === 'GrowingTree'}
           />
           Growing Tree
         </label>
+        {picks.map((pick) => (
+          <label>
+            <input
+              type="range"
+              min="0"
+              max="4"
+              step="1"
+              value={pickWeight(selection() ?? DEFAULT_SELECTION, pick)}
+              disabled={selection() === undefined}
+              onChange={(e) =>
+                setAlgorithm({
+                  GrowingTree: withPickWeight(
+                    selection() ?? DEFAULT_SELECTION,
+                    pick,
+                    Number(e.currentTarget.value),
+                  ),
+                })
+              }
+            />
+            {pick} {pickWeight(selection() ?? DEFAULT_SELECTION, pick)}
+          </label>
+        ))}
+        <label>
+          <input
+            type="radio"
+            onInput={() => setAlgorithm({ Prim: 'Random' })}
+            checked={hashAlgorithm(configuration().algorithm) === 'Prim'}
+          />
+          Prim's
+        </label>
+        <label>
+          <input
+            type="radio"
+            onInput={() => setAlgorithm({ Prim: 'Weighted' })}
+            checked={
+              hashAlgorithm(configuration().algorithm) === 'Prim[weighted]'
+            }
+          />
+          Prim's (weighted)
+        </label>
         <label>
           <input
             onInput={() => setAlgorithm('Kruskal')}
             type="radio"
-            checked={configuration().algorithm === 'Kruskal'}
+            checked={algorithmKey(configuration().algorithm) === 'Kruskal'}
           />
           Kruskal's
         </label>
+        <label>
+          <input
+            onInput={() => setAlgorithm({ Wilson: 0 })}
+            type="radio"
+            checked={algorithmKey(configuration().algorithm) === 'Wilson'}
+          />
+          Wilson's
+        </label>
+        <label>
+          <input
+            onInput={() => setAlgorithm('AldousBroder')}
+            type="radio"
+            checked={algorithmKey(configuration().algorithm) === 'AldousBroder'}
+          />
+          Aldous–Broder
+        </label>
+        <label>
+          <input
+            onInput={() => setAlgorithm('Eller')}
+            type="radio"
+            disabled={!('Rectilinear' in configuration().shape)}
+            checked={algorithmKey(configuration().algorithm) === 'Eller'}
+          />
+          Eller's
+        </label>
+        <label>
+          <input
+            onInput={() => setAlgorithm('RecursiveDivision')}
+            type="radio"
+            disabled={!('Rectilinear' in configuration().shape)}
+            checked={
+              algorithmKey(configuration().algorithm) === 'RecursiveDivision'
+            }
+          />
+          Recursive Division
+        </label>
+        <label>
+          <input
+            onInput={() => setAlgorithm('BlobbyDivision')}
+            type="radio"
+            checked={
+              algorithmKey(configuration().algorithm) === 'BlobbyDivision'
+            }
+          />
+          Blobby Division
+        </label>
+        <label>
+          <input
+            onInput={() => setAlgorithm('HuntAndKill')}
+            type="radio"
+            checked={algorithmKey(configuration().algorithm) === 'HuntAndKill'}
+          />
+          Hunt and Kill
+        </label>
+        <label>
+          <input
+            onInput={() => setAlgorithm('BinaryTree')}
+            type="radio"
+            disabled={!('Rectilinear' in configuration().shape)}
+            checked={algorithmKey(configuration().algorithm) === 'BinaryTree'}
+          />
+          Binary Tree
+        </label>
+        <label>
+          <input
+            onInput={() => setAlgorithm('Sidewinder')}
+            type="radio"
+            disabled={!('Rectilinear' in configuration().shape)}
+            checked={algorithmKey(configuration().algorithm) === 'Sidewinder'}
+          />
+          Sidewinder
+        </label>
+        <label>
+          <input
+            onInput={() => setAlgorithm({ Weave: 20 })}
+            type="radio"
+            disabled={!('Rectilinear' in configuration().shape)}
+            checked={algorithmKey(configuration().algorithm) === 'Weave'}
+          />
+          Weave
+        </label>
+        <label>
+          <input
+            onInput={() => setAlgorithm({ Dungeon: 6 })}
+            type="radio"
+            disabled={!('Rectilinear' in configuration().shape)}
+            checked={algorithmKey(configuration().algorithm) === 'Dungeon'}
+          />
+          Dungeon
+        </label>
+      </section>
+      <section>
+        <h2>Braid</h2>
+        <label>
+          <input
+            ref={braidInput}
+            type="range"
+            min="0"
+            max="100"
+            step="10"
+            value={configuration().braid}
+            onChange={(_) => setBraid(Number(braidInput?.value))}
+          />
+          Remove {configuration().braid}% of dead ends
+        </label>
+      </section>
+      <section>
+        <h2>One way</h2>
+        <label>
+          <input
+            ref={oneWayInput}
+            type="range"
+            min="0"
+            max="100"
+            step="10"
+            value={configuration().one_way}
+            disabled={configuration().braid === 0}
+            onChange={(_) => setOneWay(Number(oneWayInput?.value))}
+          />
+          Make up to {configuration().one_way}% of passages one way
+        </label>
+      </section>
+      <section>
+        <h2>Terrain</h2>
+        <label>
+          <input
+            ref={terrainInput}
+            type="range"
+            min="0"
+            max="100"
+            step="10"
+            value={configuration().terrain}
+            onChange={(_) => setTerrain(Number(terrainInput?.value))}
+          />
+          Cover {configuration().terrain}% of cells with mud and water
+        </label>
+      </section>
+      <section>
+        <h2>Portals</h2>
+        <label>
+          <input
+            ref={portalsInput}
+            type="range"
+            min="0"
+            max="12"
+            step="1"
+            value={configuration().portals}
+            onChange={(_) => setPortals(Number(portalsInput?.value))}
+          />
+          Link {configuration().portals} pairs of far apart cells
+        </label>
+      </section>
+      <section>
+        <h2>Checkpoints</h2>
+        <label>
+          <input
+            ref={checkpointsInput}
+            type="range"
+            min="0"
+            max="20"
+            step="1"
+            value={configuration().checkpoints}
+            onChange={(_) => setCheckpoints(Number(checkpointsInput?.value))}
+          />
+          Visit {configuration().checkpoints} numbered checkpoints on the way
+        </label>
+      </section>
+      <section>
+        <h2>Locks</h2>
+        <label>
+          <input
+            ref={locksInput}
+            type="range"
+            min="0"
+            max="8"
+            step="1"
+            value={configuration().locks}
+            onChange={(_) => setLocks(Number(locksInput?.value))}
+          />
+          Lock {configuration().locks} doors and hide their keys
+        </label>
+      </section>
+      <section>
+        <h2>Symmetry</h2>
+        <label>
+          {symmetryInput(null)}
+          None
+        </label>
+        <label>
+          {symmetryInput('Mirror')}
+          Mirror
+        </label>
+        <label>
+          {symmetryInput({ Rotation: 2 })}
+          Half turn
+        </label>
+        <label>
+          {symmetryInput({ Rotation: 4 })}
+          Quarter turn
+        </label>
+        <label>
+          {symmetryInput({ Rotation: 8 })}
+          Eighth turn
+        </label>
+      </section>
+      <section>
+        <h2>Wrap around</h2>
+        <label>
+          {wrapInput(false, false)}
+          None
+        </label>
+        <label>
+          {wrapInput(true, false)}
+          Left and right
+        </label>
+        <label>
+          {wrapInput(false, true)}
+          Top and bottom
+        </label>
+        <label>
+          {wrapInput(true, true)}
+          Both
+        </label>
+        <label>
+          <input
+            type="checkbox"
+            disabled={cannotWrap()}
+            checked={configuration().wrap.twist}
+            onInput={() =>
+              setWrap({
+                ...configuration().wrap,
+                twist: !configuration().wrap.twist,
+              })
+            }
+          />
+          Twist on the way around
+        </label>
+      </section>
+      <section>
+        <h2>Pairs</h2>
+        <label>
+          <input
+            ref={pairsInput}
+            type="range"
+            min="1"
+            max="8"
+            step="1"
+            value={Math.max(1, configuration().pairs)}
+            disabled={'Layered' in configuration().shape}
+            onChange={(_) => setPairs(Number(pairsInput?.value))}
+          />
+          Label {Math.max(1, configuration().pairs)} start and finish pairs,
+          A to A, B to B and so on
+        </label>
+      </section>
+      <section>
+        <h2>Bias</h2>
+        <label>
+          <input
+            ref={biasInput}
+            type="range"
+            min="-100"
+            max="100"
+            step="10"
+            value={signedBias(configuration().bias)}
+            disabled={
+              !['Kruskal', 'GrowingTree'].includes(
+                algorithmKey(configuration().algorithm),
+              )
+            }
+            onChange={(_) => {
+              const n = Number(biasInput?.value);
+              setBias(
+                n === 0
+                  ? null
+                  : {
+                      axis: n < 0 ? 'Vertical' : 'Horizontal',
+                      strength: Math.min(100, Math.abs(n)),
+                    },
+              );
+            }}
+          />
+          {describeBias(configuration().bias)}
+        </label>
       </section>
       <section>
         <details>
@@ -140,6 +567,9 @@ export default function Maze(): JSX.Element {
           </button>
         </details>
       </section>
+      <Show when={error()}>
+        {(message) => <p class="error">{message()}</p>}
+      </Show>
       <div ref={svgRef} />
       <button onClick={newSeed}>Refresh</button>
       <label>